@@ -0,0 +1,333 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::file::hash_file;
+
+/// First line of a cache file; bump the version when the format changes
+const CACHE_HEADER: &str = "ddmerge-cache 1";
+
+/// Files modified this recently are hashed but not cached, because a later
+/// write within the same mtime granularity would go unnoticed
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// File identity recorded next to a cached hash
+///
+/// A cached hash is only used while all of these still match, so touching,
+/// rewriting or replacing a file invalidates its entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    size: u64,
+    mtime_ns: u128,
+    inode: u64,
+}
+
+impl Fingerprint {
+    /// Fingerprint of a file, or None if its mtime cannot be represented
+    fn of(metadata: &fs::Metadata) -> Option<Self> {
+        let mtime_ns = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(metadata);
+        #[cfg(not(unix))]
+        let inode = 0;
+        Some(Self {
+            size: metadata.len(),
+            mtime_ns,
+            inode,
+        })
+    }
+
+    /// Whether the file was modified too recently to be cached safely
+    fn is_racy(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        now.saturating_sub(self.mtime_ns) < RACY_WINDOW.as_nanos()
+    }
+}
+
+/// Content hashes from previous runs, keyed by absolute path
+///
+/// Safe to share between the comparison threads.
+#[derive(Debug, Default)]
+pub struct HashCache {
+    entries: Mutex<HashMap<PathBuf, (Fingerprint, u128)>>,
+    dirty: AtomicBool,
+}
+
+impl HashCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a cache file, starting empty if it does not exist
+    ///
+    /// Malformed lines and files written by another format version are
+    /// ignored rather than reported, since the cache can always be rebuilt.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read cache {}", path.display()))
+            }
+        };
+
+        let mut lines = content.lines();
+        let mut entries = HashMap::new();
+        if lines.next() == Some(CACHE_HEADER) {
+            entries.extend(lines.filter_map(parse_line));
+        }
+        Ok(Self {
+            entries: Mutex::new(entries),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Write the cache file, replacing it atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let entries = self.entries.lock().unwrap();
+        let mut lines: Vec<String> = entries
+            .iter()
+            .filter_map(|(file, (fp, hash))| {
+                let file = file.to_str()?;
+                Some(format!(
+                    "{:032x}\t{}\t{}\t{}\t{}",
+                    hash, fp.size, fp.mtime_ns, fp.inode, file
+                ))
+            })
+            .collect();
+        lines.sort();
+
+        let tmp = path.with_extension("tmp");
+        let mut content = String::from(CACHE_HEADER);
+        content.push('\n');
+        for line in lines {
+            content.push_str(&line);
+            content.push('\n');
+        }
+        fs::write(&tmp, content)
+            .with_context(|| format!("Failed to write cache {}", tmp.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to write cache {}", path.display()))?;
+        self.dirty.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Whether entries were added or removed since the last load or save
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    /// Number of cached files
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Content hash of a file, reused from the cache while the file is unchanged
+    pub fn hash(&self, path: &Path) -> Result<u128> {
+        let metadata = fs::metadata(path)?;
+        if let Some(hash) = self.cached(path, &metadata)? {
+            return Ok(hash);
+        }
+        let hash = hash_file(path)?;
+        self.insert(path, &metadata, hash)?;
+        Ok(hash)
+    }
+
+    /// Cached hash of a file, or None if it was not hashed while in this state
+    ///
+    /// `metadata` is the file's current metadata; the file is never read.
+    pub fn cached(&self, path: &Path, metadata: &fs::Metadata) -> Result<Option<u128>> {
+        let Some(fp) = Fingerprint::of(metadata) else {
+            return Ok(None);
+        };
+        let key = std::path::absolute(path)?;
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|(cached_fp, _)| *cached_fp == fp)
+            .map(|(_, hash)| *hash))
+    }
+
+    /// Record the hash of a file read while it had `metadata`
+    ///
+    /// Racy files are not cached, and any previous entry for them is dropped.
+    pub fn insert(&self, path: &Path, metadata: &fs::Metadata, hash: u128) -> Result<()> {
+        let key = std::path::absolute(path)?;
+        let mut entries = self.entries.lock().unwrap();
+        match Fingerprint::of(metadata) {
+            Some(fp) if !fp.is_racy() => {
+                entries.insert(key, (fp, hash));
+                self.dirty.store(true, Ordering::Relaxed);
+            }
+            _ => {
+                if entries.remove(&key).is_some() {
+                    self.dirty.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop entries for files that no longer exist or have changed
+    ///
+    /// Returns the number of removed entries.
+    pub fn prune(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|file, (fp, _)| {
+            fs::metadata(file)
+                .ok()
+                .and_then(|m| Fingerprint::of(&m))
+                .is_some_and(|current| current == *fp)
+        });
+        let removed = before - entries.len();
+        if removed > 0 {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        removed
+    }
+}
+
+/// Parse one `hash size mtime inode path` line of a cache file
+fn parse_line(line: &str) -> Option<(PathBuf, (Fingerprint, u128))> {
+    let mut fields = line.splitn(5, '\t');
+    let hash = u128::from_str_radix(fields.next()?, 16).ok()?;
+    let fingerprint = Fingerprint {
+        size: fields.next()?.parse().ok()?,
+        mtime_ns: fields.next()?.parse().ok()?,
+        inode: fields.next()?.parse().ok()?,
+    };
+    let path = PathBuf::from(fields.next()?);
+    Some((path, (fingerprint, hash)))
+}
+
+/// Per-user ddmerge cache directory, following the XDG base directory spec
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(base.join("ddmerge"))
+}
+
+/// Default location of the cache file
+pub fn default_cache_path() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("hashes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    /// Set a file's mtime far enough in the past to be cacheable
+    fn make_old(path: &Path) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_hash_is_cached_and_persisted() {
+        // Given: An old file hashed through a cache
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        let cache_path = dir.path().join("cache/hashes");
+        fs::write(&file, "content").unwrap();
+        make_old(&file);
+        let cache = HashCache::new();
+        let hash = cache.hash(&file).unwrap();
+
+        // When: Saving and loading the cache
+        cache.save(&cache_path).unwrap();
+        let loaded = HashCache::load(&cache_path).unwrap();
+
+        // Then: The entry survives the round trip
+        assert_eq!(loaded.len(), 1);
+        assert!(!loaded.is_dirty());
+        assert_eq!(loaded.hash(&file).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_touched_file_is_rehashed() {
+        // Given: A cached file that is rewritten with the same size
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "aaaa").unwrap();
+        make_old(&file);
+        let cache = HashCache::new();
+        let old_hash = cache.hash(&file).unwrap();
+        fs::write(&file, "bbbb").unwrap();
+
+        // When: Hashing it again
+        let new_hash = cache.hash(&file).unwrap();
+
+        // Then: The new content is hashed and the racy entry is not kept
+        assert_ne!(old_hash, new_hash);
+        assert_eq!(new_hash, hash_file(&file).unwrap());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_prune_removes_stale_entries() {
+        // Given: A cache with one file that still exists and one that was deleted
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().join("kept.txt");
+        let deleted = dir.path().join("deleted.txt");
+        fs::write(&kept, "kept").unwrap();
+        fs::write(&deleted, "deleted").unwrap();
+        make_old(&kept);
+        make_old(&deleted);
+        let cache = HashCache::new();
+        cache.hash(&kept).unwrap();
+        cache.hash(&deleted).unwrap();
+        fs::remove_file(&deleted).unwrap();
+
+        // When: Pruning
+        let removed = cache.prune();
+
+        // Then: Only the deleted file's entry is dropped
+        assert_eq!(removed, 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_load_ignores_other_versions() {
+        // Given: A cache file with an unknown header
+        let dir = TempDir::new().unwrap();
+        let cache_path = dir.path().join("hashes");
+        fs::write(&cache_path, "ddmerge-cache 0\nxyz\n").unwrap();
+
+        // When: Loading it
+        let cache = HashCache::load(&cache_path).unwrap();
+
+        // Then: The cache starts empty
+        assert!(cache.is_empty());
+    }
+}
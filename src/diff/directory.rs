@@ -1,9 +1,16 @@
 use anyhow::Result;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use walkdir::WalkDir;
 
-use super::file::compare_files;
+use super::cache::HashCache;
+use super::file::{compare_files_with_options, compare_metadata, compare_symlinks, MetadataDiff};
+use super::filter::PathFilter;
+use super::ignore::IgnoreRules;
+use super::parallel::{map_ordered, resolve_jobs};
+use super::whitespace::WhitespaceOptions;
 
 /// Type of difference between two directories
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -14,8 +21,21 @@ pub enum DiffType {
     RightOnly,
     /// File exists in both but content differs
     Modified,
-    /// Same path but different types (file vs directory)
+    /// Same path but different types (file vs directory vs symlink)
     TypeMismatch,
+    /// Symlink exists in both but points to a different target
+    SymlinkChanged,
+    /// File content is identical but metadata (permissions, owner, mtime) differs
+    MetadataOnly,
+    /// A left-only and a right-only entry that look like the same file moved
+    Renamed {
+        /// Path in the left directory
+        from: PathBuf,
+        /// Path in the right directory
+        to: PathBuf,
+        /// Content similarity in percent (100 means identical)
+        similarity: u8,
+    },
 }
 
 /// A single difference entry
@@ -29,6 +49,12 @@ pub struct DiffEntry {
     pub left_is_dir: Option<bool>,
     /// Whether right side is a directory (if exists)
     pub right_is_dir: Option<bool>,
+    /// Whether left side is a symlink
+    pub left_is_symlink: bool,
+    /// Whether right side is a symlink
+    pub right_is_symlink: bool,
+    /// Metadata differences between both sides (if compared and different)
+    pub metadata: Option<MetadataDiff>,
 }
 
 impl DiffEntry {
@@ -38,6 +64,9 @@ impl DiffEntry {
             diff_type: DiffType::LeftOnly,
             left_is_dir: Some(is_dir),
             right_is_dir: None,
+            left_is_symlink: false,
+            right_is_symlink: false,
+            metadata: None,
         }
     }
 
@@ -47,6 +76,9 @@ impl DiffEntry {
             diff_type: DiffType::RightOnly,
             left_is_dir: None,
             right_is_dir: Some(is_dir),
+            left_is_symlink: false,
+            right_is_symlink: false,
+            metadata: None,
         }
     }
 
@@ -56,6 +88,9 @@ impl DiffEntry {
             diff_type: DiffType::Modified,
             left_is_dir: Some(false),
             right_is_dir: Some(false),
+            left_is_symlink: false,
+            right_is_symlink: false,
+            metadata: None,
         }
     }
 
@@ -65,98 +100,606 @@ impl DiffEntry {
             diff_type: DiffType::TypeMismatch,
             left_is_dir: Some(left_is_dir),
             right_is_dir: Some(right_is_dir),
+            left_is_symlink: false,
+            right_is_symlink: false,
+            metadata: None,
         }
     }
+
+    pub fn symlink_changed(path: PathBuf) -> Self {
+        Self {
+            path,
+            diff_type: DiffType::SymlinkChanged,
+            left_is_dir: Some(false),
+            right_is_dir: Some(false),
+            left_is_symlink: true,
+            right_is_symlink: true,
+            metadata: None,
+        }
+    }
+
+    pub fn metadata_only(path: PathBuf, metadata: MetadataDiff) -> Self {
+        Self {
+            path,
+            diff_type: DiffType::MetadataOnly,
+            left_is_dir: Some(false),
+            right_is_dir: Some(false),
+            left_is_symlink: false,
+            right_is_symlink: false,
+            metadata: Some(metadata),
+        }
+    }
+
+    /// A rename pair; `path` is the left-side path
+    pub fn renamed(from: PathBuf, to: PathBuf, similarity: u8, is_dir: bool) -> Self {
+        Self {
+            path: from.clone(),
+            diff_type: DiffType::Renamed {
+                from,
+                to,
+                similarity,
+            },
+            left_is_dir: Some(is_dir),
+            right_is_dir: Some(is_dir),
+            left_is_symlink: false,
+            right_is_symlink: false,
+            metadata: None,
+        }
+    }
+
+    /// Attach metadata differences found alongside a content difference
+    pub fn with_metadata(mut self, metadata: Option<MetadataDiff>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Mark which sides are symlinks
+    pub fn with_symlinks(mut self, left_is_symlink: bool, right_is_symlink: bool) -> Self {
+        self.left_is_symlink = left_is_symlink;
+        self.right_is_symlink = right_is_symlink;
+        self
+    }
+
+    /// Human-readable type of the left side ("file", "directory" or "symlink")
+    pub fn left_type_name(&self) -> &'static str {
+        type_name(self.left_is_dir.unwrap_or(false), self.left_is_symlink)
+    }
+
+    /// Human-readable type of the right side ("file", "directory" or "symlink")
+    pub fn right_type_name(&self) -> &'static str {
+        type_name(self.right_is_dir.unwrap_or(false), self.right_is_symlink)
+    }
+}
+
+fn type_name(is_dir: bool, is_symlink: bool) -> &'static str {
+    if is_symlink {
+        "symlink"
+    } else if is_dir {
+        "directory"
+    } else {
+        "file"
+    }
 }
 
-/// Collect all relative paths from a directory
-fn collect_paths(root: &Path) -> Result<BTreeSet<PathBuf>> {
-    let mut paths = BTreeSet::new();
+/// Options controlling how directories are compared
+#[derive(Debug, Clone)]
+pub struct CompareOptions {
+    /// Follow symlinks and compare what they point to instead of the links themselves
+    pub follow_symlinks: bool,
+    /// Report differing permission bits
+    pub compare_mode: bool,
+    /// Report differing owner uid/gid
+    pub compare_owner: bool,
+    /// Report differing modification times
+    pub compare_mtime: bool,
+    /// Skip paths matched by .gitignore, .ignore and .ddmergeignore in either tree
+    pub respect_ignore_files: bool,
+    /// Include/exclude filters applied to both trees
+    pub filter: PathFilter,
+    /// Treat files with equal size and modification time as identical without reading them
+    pub trust_mtime: bool,
+    /// Content hashes reused across runs (None compares contents directly)
+    pub cache: Option<Arc<HashCache>>,
+    /// Number of threads for scanning and comparing (0 means one per CPU)
+    pub jobs: usize,
+    /// Whitespace and line-ending differences that do not make text files differ
+    pub whitespace: WhitespaceOptions,
+}
 
-    for entry in WalkDir::new(root).min_depth(1) {
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: false,
+            compare_mode: true,
+            compare_owner: false,
+            compare_mtime: false,
+            respect_ignore_files: true,
+            filter: PathFilter::default(),
+            trust_mtime: false,
+            cache: None,
+            jobs: 0,
+            whitespace: WhitespaceOptions::default(),
+        }
+    }
+}
+
+/// Kind of a filesystem entry as seen by the comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// Get the kind of an entry, or None if it does not exist
+///
+/// Without `follow_symlinks`, a symlink is reported as such (even if dangling).
+fn entry_kind(path: &Path, follow_symlinks: bool) -> Option<EntryKind> {
+    let metadata = if follow_symlinks {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    }
+    .ok()?;
+
+    Some(EntryKind::from(metadata.file_type()))
+}
+
+impl From<fs::FileType> for EntryKind {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        }
+    }
+}
+
+/// Walk the tree below `start` (relative to `root`), up to `max_depth` levels
+///
+/// Ignored and excluded directories are pruned during the walk, so their
+/// contents are never visited. `visit` gets each remaining entry's path,
+/// relative to `root` so filters and ignore files see the same paths
+/// whatever directory the walk starts from, and its kind from the walk
+/// itself, so no extra stat call is needed.
+fn walk_filtered(
+    root: &Path,
+    start: &Path,
+    max_depth: usize,
+    options: &CompareOptions,
+    mut ignore: Option<&mut IgnoreRules>,
+    mut visit: impl FnMut(PathBuf, fs::FileType),
+) -> Result<()> {
+    let walker = WalkDir::new(root.join(start))
+        .min_depth(1)
+        .max_depth(max_depth)
+        .follow_links(options.follow_symlinks)
+        .into_iter()
+        .filter_entry(|entry| {
+            let Ok(rel_path) = entry.path().strip_prefix(root) else {
+                return true;
+            };
+            let is_dir = entry.file_type().is_dir();
+            if options.filter.is_excluded(rel_path, is_dir) {
+                return false;
+            }
+            match ignore.as_deref_mut() {
+                Some(rules) => !rules.is_ignored(rel_path, is_dir),
+                None => true,
+            }
+        });
+
+    for entry in walker {
         let entry = entry?;
-        let rel_path = entry.path().strip_prefix(root)?.to_path_buf();
-        paths.insert(rel_path);
+        visit(
+            entry.path().strip_prefix(root)?.to_path_buf(),
+            entry.file_type(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Add a walked entry to `paths` unless include patterns leave it out
+///
+/// With include patterns, only matching files are collected (directories
+/// are walked but not reported themselves).
+fn collect_entry(
+    paths: &mut BTreeMap<PathBuf, EntryKind>,
+    rel_path: PathBuf,
+    file_type: fs::FileType,
+    options: &CompareOptions,
+) {
+    let is_collected = if file_type.is_dir() {
+        !options.filter.has_includes()
+    } else {
+        options.filter.is_included(&rel_path)
+    };
+    if is_collected {
+        paths.insert(rel_path, EntryKind::from(file_type));
     }
+}
 
+/// Collect all relative paths below `start` (relative to `root`) along with their kinds
+fn collect_paths(
+    root: &Path,
+    start: &Path,
+    options: &CompareOptions,
+    ignore: Option<&mut IgnoreRules>,
+) -> Result<BTreeMap<PathBuf, EntryKind>> {
+    let mut paths = BTreeMap::new();
+    walk_filtered(
+        root,
+        start,
+        usize::MAX,
+        options,
+        ignore,
+        |rel_path, file_type| collect_entry(&mut paths, rel_path, file_type, options),
+    )?;
     Ok(paths)
 }
 
 /// Compare two directories and return all differences
 pub fn compare_directories(left: &Path, right: &Path) -> Result<Vec<DiffEntry>> {
-    let left_paths = collect_paths(left)?;
-    let right_paths = collect_paths(right)?;
+    compare_directories_with_options(left, right, &CompareOptions::default())
+}
 
-    let mut diffs = Vec::new();
+/// Compare two directories with the given options and return all differences
+pub fn compare_directories_with_options(
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+) -> Result<Vec<DiffEntry>> {
+    let jobs = resolve_jobs(options.jobs);
+
+    // Walk the top level of both trees, then spread their subdirectories
+    // across the pool; each walk loads the ignore rules of both roots
+    let roots = [left, right];
+    let new_ignore = || {
+        options
+            .respect_ignore_files
+            .then(|| IgnoreRules::new(&roots))
+    };
+    let mut tree_paths = [BTreeMap::new(), BTreeMap::new()];
+    let mut subdirs = Vec::new();
+    for (side, root) in roots.into_iter().enumerate() {
+        let paths = &mut tree_paths[side];
+        walk_filtered(
+            root,
+            Path::new(""),
+            1,
+            options,
+            new_ignore().as_mut(),
+            |rel_path, file_type| {
+                if file_type.is_dir() {
+                    subdirs.push((side, rel_path.clone()));
+                }
+                collect_entry(paths, rel_path, file_type, options);
+            },
+        )?;
+    }
+    let walks = map_ordered(&subdirs, jobs, |(side, dir)| {
+        collect_paths(roots[*side], dir, options, new_ignore().as_mut())
+    });
+    for ((side, _), walk) in subdirs.iter().zip(walks) {
+        tree_paths[*side].extend(walk?);
+    }
+    let [left_paths, right_paths] = tree_paths;
 
     // Find all unique paths
-    let all_paths: BTreeSet<_> = left_paths.union(&right_paths).cloned().collect();
+    let all_paths: BTreeSet<&PathBuf> = left_paths.keys().chain(right_paths.keys()).collect();
+
+    // Checked before touching the files so excluded entries are never read
+    let is_excluded = |rel_path: &Path| {
+        options.filter.is_excluded_by_regex(
+            rel_path,
+            left_paths.contains_key(rel_path),
+            right_paths.contains_key(rel_path),
+        )
+    };
+    // Contents of a directory that exists on one side only are reported with it
+    let is_one_sided_dir = |rel_path: &Path| {
+        let left_kind = left_paths.get(rel_path);
+        let right_kind = right_paths.get(rel_path);
+        let is_dir = [left_kind, right_kind].contains(&Some(&EntryKind::Dir));
+        is_dir && (left_kind.is_none() || right_kind.is_none()) && !is_excluded(rel_path)
+    };
+    let candidates: Vec<&PathBuf> = all_paths
+        .into_iter()
+        .filter(|rel_path| !is_excluded(rel_path))
+        .filter(|rel_path| {
+            !rel_path
+                .ancestors()
+                .skip(1)
+                .take_while(|a| !a.as_os_str().is_empty())
+                .any(is_one_sided_dir)
+        })
+        .collect();
+
+    let results = map_ordered(&candidates, jobs, |rel_path| {
+        compare_entry(
+            left,
+            right,
+            rel_path,
+            left_paths.get(*rel_path).copied(),
+            right_paths.get(*rel_path).copied(),
+            options,
+        )
+    });
+    let mut diffs = Vec::new();
+    for result in results {
+        diffs.extend(result?);
+    }
 
-    for rel_path in all_paths {
-        let left_full = left.join(&rel_path);
-        let right_full = right.join(&rel_path);
+    // Filter out child entries when parent directory is LeftOnly or RightOnly
+    let diffs = filter_nested_diffs(diffs);
 
-        let left_exists = left_full.exists();
-        let right_exists = right_full.exists();
+    Ok(diffs)
+}
 
-        match (left_exists, right_exists) {
-            (true, false) => {
-                let is_dir = left_full.is_dir();
-                // Skip directory contents if parent directory is already marked as LeftOnly
-                if !is_dir || !has_parent_diff(&diffs, &rel_path, DiffType::LeftOnly) {
-                    diffs.push(DiffEntry::left_only(rel_path, is_dir));
-                }
+/// Entries under a directory that exists in one tree only, as the scan
+/// would see them, read once to pair it with or descend into it for renames
+#[derive(Debug, Clone)]
+pub struct DirListing {
+    /// The directory, relative to its tree's root
+    start: PathBuf,
+    /// Entries keyed relative to `start`
+    entries: BTreeMap<PathBuf, EntryKind>,
+    /// Total size of the files, to rule out pairs without reading them
+    size: u64,
+}
+
+impl DirListing {
+    /// List directory `start` of the left (`in_left`) or right tree
+    ///
+    /// Ignore files, filters and the other options apply as in
+    /// `compare_directories_with_options`, each side seeing its own paths.
+    pub fn read(
+        left: &Path,
+        right: &Path,
+        start: &Path,
+        in_left: bool,
+        options: &CompareOptions,
+    ) -> Result<Self> {
+        let root = if in_left { left } else { right };
+        let mut ignore = options
+            .respect_ignore_files
+            .then(|| IgnoreRules::new(&[left, right]));
+        let mut entries = BTreeMap::new();
+        let mut size = 0;
+        for (path, kind) in collect_paths(root, start, options, ignore.as_mut())? {
+            if options
+                .filter
+                .is_excluded_by_regex(&path, in_left, !in_left)
+            {
+                continue;
             }
-            (false, true) => {
-                let is_dir = right_full.is_dir();
-                // Skip directory contents if parent directory is already marked as RightOnly
-                if !is_dir || !has_parent_diff(&diffs, &rel_path, DiffType::RightOnly) {
-                    diffs.push(DiffEntry::right_only(rel_path, is_dir));
-                }
+            if kind == EntryKind::File {
+                size += fs::metadata(root.join(&path))?.len();
             }
-            (true, true) => {
-                let left_is_dir = left_full.is_dir();
-                let right_is_dir = right_full.is_dir();
-
-                if left_is_dir != right_is_dir {
-                    diffs.push(DiffEntry::type_mismatch(
-                        rel_path,
-                        left_is_dir,
-                        right_is_dir,
-                    ));
-                } else if !left_is_dir {
-                    // Both are files, compare content
-                    if !compare_files(&left_full, &right_full)? {
-                        diffs.push(DiffEntry::modified(rel_path));
-                    }
-                }
-                // If both are directories with same type, no diff for the directory itself
+            if let Ok(rel_path) = path.strip_prefix(start) {
+                entries.insert(rel_path.to_path_buf(), kind);
             }
-            (false, false) => {
-                // This shouldn't happen, but handle gracefully
+        }
+        Ok(Self {
+            start: start.to_path_buf(),
+            entries,
+            size,
+        })
+    }
+
+    /// The directory, relative to its tree's root
+    pub fn start(&self) -> &Path {
+        &self.start
+    }
+
+    /// Number of entries and total file size, equal for identical trees
+    pub fn shape(&self) -> (usize, u64) {
+        (self.entries.len(), self.size)
+    }
+
+    /// Whether both listings hold the same paths with the same kinds
+    pub fn same_paths(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+
+    /// Regular files, relative to the tree's root
+    pub fn files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.entries
+            .iter()
+            .filter(|(_, kind)| **kind == EntryKind::File)
+            .map(|(path, _)| self.start.join(path))
+    }
+
+    /// One-sided entries covering everything but the `paired` paths
+    ///
+    /// Each entry is the largest subtree holding no paired path, so the
+    /// directory is split only as far as the pairs require.
+    pub fn unpaired_entries(
+        &self,
+        diff_type: &DiffType,
+        paired: &BTreeSet<PathBuf>,
+    ) -> Vec<DiffEntry> {
+        let mut split = BTreeSet::new();
+        for path in paired {
+            if let Ok(rel_path) = path.strip_prefix(&self.start) {
+                split.extend(rel_path.ancestors().skip(1).map(Path::to_path_buf));
             }
         }
+        self.entries
+            .iter()
+            .filter(|(rel_path, _)| !split.contains(*rel_path))
+            .filter(|(rel_path, _)| !paired.contains(&self.start.join(rel_path)))
+            .filter(|(rel_path, _)| {
+                rel_path
+                    .parent()
+                    .is_some_and(|parent| split.contains(parent))
+            })
+            .map(|(rel_path, kind)| {
+                let path = self.start.join(rel_path);
+                let (is_dir, is_symlink) = (*kind == EntryKind::Dir, *kind == EntryKind::Symlink);
+                if *diff_type == DiffType::LeftOnly {
+                    DiffEntry::left_only(path, is_dir).with_symlinks(is_symlink, false)
+                } else {
+                    DiffEntry::right_only(path, is_dir).with_symlinks(false, is_symlink)
+                }
+            })
+            .collect()
     }
+}
 
-    // Filter out child entries when parent directory is LeftOnly or RightOnly
-    let diffs = filter_nested_diffs(diffs);
+/// Check whether listed directories of the left and right trees hold the
+/// same entries, e.g. to tell whether a directory was moved unchanged
+pub fn compare_moved_directory(
+    left: &Path,
+    right: &Path,
+    from: &DirListing,
+    to: &DirListing,
+    options: &CompareOptions,
+) -> Result<bool> {
+    if from.shape() != to.shape() || !from.same_paths(to) {
+        return Ok(false);
+    }
 
-    Ok(diffs)
+    let (left_dir, right_dir) = (left.join(&from.start), right.join(&to.start));
+    let rel_paths: Vec<&PathBuf> = from.entries.keys().collect();
+    let results = map_ordered(&rel_paths, resolve_jobs(options.jobs), |rel_path| {
+        compare_entry(
+            &left_dir,
+            &right_dir,
+            rel_path,
+            from.entries.get(*rel_path).copied(),
+            to.entries.get(*rel_path).copied(),
+            options,
+        )
+    });
+    for result in results {
+        if result?.is_some() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Compare one relative path again, e.g. after it changed during the merge
+///
+/// The include/exclude filters are not applied, and a directory present on
+/// both sides reports no difference of its own.
+pub fn compare_path(
+    left: &Path,
+    right: &Path,
+    rel_path: &Path,
+    options: &CompareOptions,
+) -> Result<Option<DiffEntry>> {
+    let left_kind = entry_kind(&left.join(rel_path), options.follow_symlinks);
+    let right_kind = entry_kind(&right.join(rel_path), options.follow_symlinks);
+    compare_entry(left, right, rel_path, left_kind, right_kind, options)
 }
 
-/// Check if there's a parent directory with the given diff type
-fn has_parent_diff(diffs: &[DiffEntry], path: &Path, diff_type: DiffType) -> bool {
-    for ancestor in path.ancestors().skip(1) {
-        if ancestor.as_os_str().is_empty() {
-            break;
+/// Compare one relative path given its kind on each side
+fn compare_entry(
+    left: &Path,
+    right: &Path,
+    rel_path: &Path,
+    left_kind: Option<EntryKind>,
+    right_kind: Option<EntryKind>,
+    options: &CompareOptions,
+) -> Result<Option<DiffEntry>> {
+    let rel_path = rel_path.to_path_buf();
+    let left_full = left.join(&rel_path);
+    let right_full = right.join(&rel_path);
+
+    let entry = match (left_kind, right_kind) {
+        (Some(kind), None) => Some(
+            DiffEntry::left_only(rel_path, kind == EntryKind::Dir)
+                .with_symlinks(kind == EntryKind::Symlink, false),
+        ),
+        (None, Some(kind)) => Some(
+            DiffEntry::right_only(rel_path, kind == EntryKind::Dir)
+                .with_symlinks(false, kind == EntryKind::Symlink),
+        ),
+        (Some(left_kind), Some(right_kind)) if left_kind != right_kind => Some(
+            DiffEntry::type_mismatch(
+                rel_path,
+                left_kind == EntryKind::Dir,
+                right_kind == EntryKind::Dir,
+            )
+            .with_symlinks(
+                left_kind == EntryKind::Symlink,
+                right_kind == EntryKind::Symlink,
+            ),
+        ),
+        (Some(EntryKind::Symlink), Some(_)) => {
+            // Both are symlinks, compare where they point
+            (!compare_symlinks(&left_full, &right_full)?)
+                .then(|| DiffEntry::symlink_changed(rel_path))
         }
-        for diff in diffs {
-            if diff.path == ancestor && diff.diff_type == diff_type {
-                return true;
+        (Some(EntryKind::File), Some(_)) => {
+            // Both are files, compare content and then metadata
+            let metadata = compare_metadata(&left_full, &right_full, options)?;
+            if !compare_files_with_options(&left_full, &right_full, options)? {
+                Some(DiffEntry::modified(rel_path).with_metadata(metadata))
+            } else {
+                metadata.map(|metadata| DiffEntry::metadata_only(rel_path, metadata))
             }
         }
+        // If both are directories with same type, no diff for the directory itself
+        (Some(EntryKind::Dir), Some(_)) | (None, None) => None,
+    };
+    Ok(entry)
+}
+
+/// Which side introduced a difference, relative to a common base directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOrigin {
+    /// Only the left side changed since the base; left should win
+    Left,
+    /// Only the right side changed since the base; right should win
+    Right,
+    /// Both sides changed since the base (or the base cannot tell)
+    Conflict,
+}
+
+/// Check whether a path is in the same state in two trees
+/// (both absent, identical files or links, or directories with no differences)
+fn same_entry(a: &Path, b: &Path, options: &CompareOptions) -> Result<bool> {
+    let a_kind = entry_kind(a, options.follow_symlinks);
+    let b_kind = entry_kind(b, options.follow_symlinks);
+    if a_kind != b_kind {
+        return Ok(false);
+    }
+    match a_kind {
+        None => Ok(true),
+        Some(EntryKind::Dir) => Ok(compare_directories_with_options(a, b, options)?.is_empty()),
+        Some(EntryKind::Symlink) => compare_symlinks(a, b),
+        Some(EntryKind::File) => compare_files_with_options(a, b, options),
+    }
+}
+
+/// Classify a difference between left and right against a common base
+///
+/// A side that still matches the base did not change, so the other side's
+/// version is the one to propagate.
+pub fn classify_change(
+    entry: &DiffEntry,
+    base: &Path,
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+) -> Result<ChangeOrigin> {
+    let base_path = base.join(&entry.path);
+    let left_path = left.join(&entry.path);
+    let right_path = right.join(&entry.path);
+
+    if same_entry(&base_path, &left_path, options)? {
+        Ok(ChangeOrigin::Right)
+    } else if same_entry(&base_path, &right_path, options)? {
+        Ok(ChangeOrigin::Left)
+    } else {
+        Ok(ChangeOrigin::Conflict)
     }
-    false
 }
 
 /// Filter out entries that are children of LeftOnly or RightOnly directories
@@ -533,4 +1076,465 @@ mod tests {
         // Then: No differences are found
         assert!(diffs.is_empty());
     }
+
+    // ========================================
+    // classify_change tests
+    // ========================================
+
+    #[test]
+    fn test_classify_change_left_modified() {
+        // Given: A file changed only on the left since the base
+        let (left, right) = setup_test_dirs();
+        let base = TempDir::new().unwrap();
+        fs::write(base.path().join("file.txt"), "base").unwrap();
+        fs::write(left.path().join("file.txt"), "changed").unwrap();
+        fs::write(right.path().join("file.txt"), "base").unwrap();
+        let entry = DiffEntry::modified(PathBuf::from("file.txt"));
+
+        // When: Classifying the change against the base
+        let origin = classify_change(
+            &entry,
+            base.path(),
+            left.path(),
+            right.path(),
+            &CompareOptions::default(),
+        )
+        .unwrap();
+
+        // Then: The left side is the origin
+        assert_eq!(origin, ChangeOrigin::Left);
+    }
+
+    #[test]
+    fn test_classify_change_right_deleted() {
+        // Given: A file present in base and left but deleted on the right
+        let (left, right) = setup_test_dirs();
+        let base = TempDir::new().unwrap();
+        fs::write(base.path().join("file.txt"), "base").unwrap();
+        fs::write(left.path().join("file.txt"), "base").unwrap();
+        let entry = DiffEntry::left_only(PathBuf::from("file.txt"), false);
+
+        // When: Classifying the change against the base
+        let origin = classify_change(
+            &entry,
+            base.path(),
+            left.path(),
+            right.path(),
+            &CompareOptions::default(),
+        )
+        .unwrap();
+
+        // Then: The right side (deletion) is the origin
+        assert_eq!(origin, ChangeOrigin::Right);
+    }
+
+    #[test]
+    fn test_classify_change_both_modified() {
+        // Given: A file changed differently on both sides
+        let (left, right) = setup_test_dirs();
+        let base = TempDir::new().unwrap();
+        fs::write(base.path().join("file.txt"), "base").unwrap();
+        fs::write(left.path().join("file.txt"), "left").unwrap();
+        fs::write(right.path().join("file.txt"), "right").unwrap();
+        let entry = DiffEntry::modified(PathBuf::from("file.txt"));
+
+        // When: Classifying the change against the base
+        let origin = classify_change(
+            &entry,
+            base.path(),
+            left.path(),
+            right.path(),
+            &CompareOptions::default(),
+        )
+        .unwrap();
+
+        // Then: It is a conflict
+        assert_eq!(origin, ChangeOrigin::Conflict);
+    }
+
+    #[test]
+    fn test_classify_change_left_added_directory() {
+        // Given: A directory added only on the left
+        let (left, right) = setup_test_dirs();
+        let base = TempDir::new().unwrap();
+        fs::create_dir(left.path().join("dir")).unwrap();
+        fs::write(left.path().join("dir/file.txt"), "content").unwrap();
+        let entry = DiffEntry::left_only(PathBuf::from("dir"), true);
+
+        // When: Classifying the change against the base
+        let origin = classify_change(
+            &entry,
+            base.path(),
+            left.path(),
+            right.path(),
+            &CompareOptions::default(),
+        )
+        .unwrap();
+
+        // Then: The left side is the origin
+        assert_eq!(origin, ChangeOrigin::Left);
+    }
+
+    // ========================================
+    // compare_directories tests - Symlinks
+    // ========================================
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_target_changed() {
+        // Given: A symlink in both directories pointing to different targets
+        let (left, right) = setup_test_dirs();
+        std::os::unix::fs::symlink("a.txt", left.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("b.txt", right.path().join("link")).unwrap();
+
+        // When: Comparing the directories
+        let diffs = compare_directories(left.path(), right.path()).unwrap();
+
+        // Then: One SymlinkChanged diff is found
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].diff_type, DiffType::SymlinkChanged);
+        assert!(diffs[0].left_is_symlink);
+        assert!(diffs[0].right_is_symlink);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_vs_file_is_type_mismatch() {
+        // Given: A symlink on the left and a regular file on the right
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join("target.txt"), "content").unwrap();
+        fs::write(right.path().join("target.txt"), "content").unwrap();
+        std::os::unix::fs::symlink("target.txt", left.path().join("item")).unwrap();
+        fs::write(right.path().join("item"), "content").unwrap();
+
+        // When: Comparing the directories
+        let diffs = compare_directories(left.path(), right.path()).unwrap();
+
+        // Then: One TypeMismatch diff is found even though contents match
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].diff_type, DiffType::TypeMismatch);
+        assert_eq!(diffs[0].left_type_name(), "symlink");
+        assert_eq!(diffs[0].right_type_name(), "file");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dangling_symlink_is_left_only() {
+        // Given: A dangling symlink that exists only in the left
+        let (left, right) = setup_test_dirs();
+        std::os::unix::fs::symlink("missing.txt", left.path().join("link")).unwrap();
+
+        // When: Comparing the directories
+        let diffs = compare_directories(left.path(), right.path()).unwrap();
+
+        // Then: The link itself is reported as LeftOnly
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].diff_type, DiffType::LeftOnly);
+        assert!(diffs[0].left_is_symlink);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_compares_contents() {
+        // Given: Symlinks with different targets but identical target contents
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join("a.txt"), "same").unwrap();
+        fs::write(right.path().join("a.txt"), "same").unwrap();
+        fs::write(left.path().join("b.txt"), "same").unwrap();
+        fs::write(right.path().join("b.txt"), "same").unwrap();
+        std::os::unix::fs::symlink("a.txt", left.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("b.txt", right.path().join("link")).unwrap();
+        let options = CompareOptions {
+            follow_symlinks: true,
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing the directories while following symlinks
+        let diffs = compare_directories_with_options(left.path(), right.path(), &options).unwrap();
+
+        // Then: No differences are found
+        assert!(diffs.is_empty());
+    }
+
+    // ========================================
+    // compare_directories tests - Metadata
+    // ========================================
+
+    #[cfg(unix)]
+    #[test]
+    fn test_metadata_only_mode_change() {
+        // Given: Identical scripts where the right one lost its executable bit
+        use std::os::unix::fs::PermissionsExt;
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::write(right.path().join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(
+            left.path().join("run.sh"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        fs::set_permissions(
+            right.path().join("run.sh"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+
+        // When: Comparing the directories
+        let diffs = compare_directories(left.path(), right.path()).unwrap();
+
+        // Then: One MetadataOnly diff with the mode difference is found
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].diff_type, DiffType::MetadataOnly);
+        assert_eq!(
+            diffs[0].metadata.as_ref().unwrap().mode,
+            Some((0o755, 0o644))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_modified_file_carries_metadata_diff() {
+        // Given: Files that differ in both content and mode
+        use std::os::unix::fs::PermissionsExt;
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join("run.sh"), "left").unwrap();
+        fs::write(right.path().join("run.sh"), "right").unwrap();
+        fs::set_permissions(
+            left.path().join("run.sh"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        fs::set_permissions(
+            right.path().join("run.sh"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+
+        // When: Comparing the directories
+        let diffs = compare_directories(left.path(), right.path()).unwrap();
+
+        // Then: The Modified diff also records the metadata difference
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].diff_type, DiffType::Modified);
+        assert!(diffs[0].metadata.is_some());
+    }
+
+    // ========================================
+    // compare_directories tests - Ignore files
+    // ========================================
+
+    #[test]
+    fn test_ignored_directory_is_pruned() {
+        // Given: A .gitignore in the left tree ignoring a directory present on both sides
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join(".gitignore"), "target/\n").unwrap();
+        fs::write(right.path().join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir(left.path().join("target")).unwrap();
+        fs::create_dir(right.path().join("target")).unwrap();
+        fs::write(left.path().join("target/out.o"), "left").unwrap();
+        fs::write(right.path().join("target/other.o"), "right").unwrap();
+
+        // When: Comparing the directories
+        let diffs = compare_directories(left.path(), right.path()).unwrap();
+
+        // Then: Nothing inside the ignored directory is reported
+        assert!(diffs.is_empty());
+    }
+
+    #[test]
+    fn test_ddmergeignore_in_one_tree_applies_to_both() {
+        // Given: A .ddmergeignore only in the right tree and ignored files on both sides
+        let (left, right) = setup_test_dirs();
+        fs::write(right.path().join(".ddmergeignore"), "*.tmp\n").unwrap();
+        fs::write(left.path().join("a.tmp"), "left").unwrap();
+        fs::write(right.path().join("b.tmp"), "right").unwrap();
+
+        // When: Comparing the directories
+        let diffs = compare_directories(left.path(), right.path()).unwrap();
+
+        // Then: Both ignored files are left out and only the ignore file itself differs
+        let paths: Vec<_> = diffs.iter().map(|d| d.path.clone()).collect();
+        assert!(!paths.contains(&PathBuf::from("a.tmp")));
+        assert!(!paths.contains(&PathBuf::from("b.tmp")));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, PathBuf::from(".ddmergeignore"));
+        assert_eq!(diffs[0].diff_type, DiffType::RightOnly);
+    }
+
+    #[test]
+    fn test_no_ignore_reports_ignored_paths() {
+        // Given: An ignored file that exists only in the left tree
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join(".ignore"), "*.log\n").unwrap();
+        fs::write(right.path().join(".ignore"), "*.log\n").unwrap();
+        fs::write(left.path().join("debug.log"), "log").unwrap();
+        let options = CompareOptions {
+            respect_ignore_files: false,
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing with ignore files disabled
+        let diffs = compare_directories_with_options(left.path(), right.path(), &options).unwrap();
+
+        // Then: The file is reported
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, PathBuf::from("debug.log"));
+    }
+
+    // ========================================
+    // compare_directories tests - Filters
+    // ========================================
+
+    #[test]
+    fn test_exclude_glob_applies_to_both_sides() {
+        // Given: Excluded files on both sides
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join("a.bak"), "left").unwrap();
+        fs::write(right.path().join("b.bak"), "right").unwrap();
+        fs::write(left.path().join("keep.txt"), "left").unwrap();
+        let options = CompareOptions {
+            filter: PathFilter::new().exclude("*.bak").unwrap(),
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing the directories
+        let diffs = compare_directories_with_options(left.path(), right.path(), &options).unwrap();
+
+        // Then: Only the non-excluded file is reported
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, PathBuf::from("keep.txt"));
+    }
+
+    #[test]
+    fn test_include_glob_reports_matching_files_individually() {
+        // Given: A left-only directory with matching and non-matching files
+        let (left, right) = setup_test_dirs();
+        fs::create_dir(left.path().join("mod")).unwrap();
+        fs::write(left.path().join("mod/main.tf"), "tf").unwrap();
+        fs::write(left.path().join("mod/notes.md"), "md").unwrap();
+        let options = CompareOptions {
+            filter: PathFilter::new().include("*.tf").unwrap(),
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing the directories
+        let diffs = compare_directories_with_options(left.path(), right.path(), &options).unwrap();
+
+        // Then: Only the included file is reported
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, PathBuf::from("mod/main.tf"));
+        assert_eq!(diffs[0].diff_type, DiffType::LeftOnly);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_excluded_files_are_never_read() {
+        // Given: Modified files on both sides that cannot be read
+        use std::os::unix::fs::PermissionsExt;
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join("secret.key"), "left").unwrap();
+        fs::write(right.path().join("secret.key"), "right").unwrap();
+        fs::set_permissions(
+            left.path().join("secret.key"),
+            fs::Permissions::from_mode(0o000),
+        )
+        .unwrap();
+        let options = CompareOptions {
+            filter: PathFilter::new().exclude_regex_right(r"\.key$").unwrap(),
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing the directories
+        let result = compare_directories_with_options(left.path(), right.path(), &options);
+
+        // Then: The comparison succeeds without reporting the excluded file
+        assert!(result.unwrap().is_empty());
+    }
+
+    // ========================================
+    // compare_directories tests - Parallelism
+    // ========================================
+
+    #[test]
+    fn test_parallel_results_match_sequential() {
+        // Given: Many differences spread over nested directories
+        let (left, right) = setup_test_dirs();
+        for i in 0..50 {
+            let dir = format!("dir{}", i % 5);
+            fs::create_dir_all(left.path().join(&dir)).unwrap();
+            fs::create_dir_all(right.path().join(&dir)).unwrap();
+            fs::write(left.path().join(&dir).join(format!("{}.txt", i)), "left").unwrap();
+            if i % 3 != 0 {
+                fs::write(right.path().join(&dir).join(format!("{}.txt", i)), "right").unwrap();
+            }
+        }
+        fs::create_dir_all(left.path().join("only/nested")).unwrap();
+        fs::write(left.path().join("only/nested/file.txt"), "x").unwrap();
+        let with_jobs = |jobs| CompareOptions {
+            jobs,
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing with one thread and with several threads
+        let sequential =
+            compare_directories_with_options(left.path(), right.path(), &with_jobs(1)).unwrap();
+        let parallel =
+            compare_directories_with_options(left.path(), right.path(), &with_jobs(8)).unwrap();
+
+        // Then: Both report the same entries in the same sorted order
+        assert_eq!(sequential.len(), 51);
+        assert_eq!(
+            sequential.iter().map(|d| &d.path).collect::<Vec<_>>(),
+            parallel.iter().map(|d| &d.path).collect::<Vec<_>>()
+        );
+        assert!(parallel.windows(2).all(|w| w[0].path < w[1].path));
+        assert!(parallel
+            .iter()
+            .any(|d| d.path == Path::new("only") && d.diff_type == DiffType::LeftOnly));
+    }
+
+    #[test]
+    fn test_parallel_walk_applies_root_ignore_rules_in_subdirectories() {
+        // Given: A root ignore file with a pattern that reaches into a subdirectory
+        let (left, right) = setup_test_dirs();
+        for root in [left.path(), right.path()] {
+            fs::create_dir_all(root.join("src/gen")).unwrap();
+            fs::write(root.join(".ddmergeignore"), "src/gen/\n*.log\n").unwrap();
+        }
+        fs::write(left.path().join("src/gen/out.rs"), "left").unwrap();
+        fs::write(left.path().join("src/debug.log"), "left").unwrap();
+        fs::write(left.path().join("src/main.rs"), "left").unwrap();
+        let options = CompareOptions {
+            jobs: 8,
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing with the subdirectories walked on separate threads
+        let diffs = compare_directories_with_options(left.path(), right.path(), &options).unwrap();
+
+        // Then: Only the file the rules leave in is reported
+        let paths: Vec<&Path> = diffs.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(paths, vec![Path::new("src/main.rs")]);
+    }
+
+    // ========================================
+    // compare_path tests
+    // ========================================
+
+    #[test]
+    fn test_compare_path_sees_current_state() {
+        // Given: A left-only file that was then created on the right too
+        let (left, right) = setup_test_dirs();
+        fs::write(left.path().join("file.txt"), "left").unwrap();
+        let options = CompareOptions::default();
+        let before = compare_path(left.path(), right.path(), Path::new("file.txt"), &options);
+        fs::write(right.path().join("file.txt"), "right").unwrap();
+
+        // When: Comparing the path again
+        let after = compare_path(left.path(), right.path(), Path::new("file.txt"), &options);
+
+        // Then: It is now a modified file
+        assert_eq!(before.unwrap().unwrap().diff_type, DiffType::LeftOnly);
+        assert_eq!(after.unwrap().unwrap().diff_type, DiffType::Modified);
+    }
 }
@@ -1,27 +1,275 @@
 use anyhow::Result;
-use std::fs;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
 use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use super::directory::CompareOptions;
+
+/// Metadata differences between two files with the given options
+/// Each field holds (left, right) values and is set only if they differ
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetadataDiff {
+    /// Permission bits
+    pub mode: Option<(u32, u32)>,
+    /// Owner as (uid, gid)
+    pub owner: Option<((u32, u32), (u32, u32))>,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: Option<(i64, i64)>,
+}
+
+impl MetadataDiff {
+    /// Whether no compared metadata differs
+    pub fn is_empty(&self) -> bool {
+        self.mode.is_none() && self.owner.is_none() && self.mtime.is_none()
+    }
+}
+
+impl fmt::Display for MetadataDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some((left, right)) = self.mode {
+            parts.push(format!("mode {:04o} vs {:04o}", left, right));
+        }
+        if let Some(((lu, lg), (ru, rg))) = self.owner {
+            parts.push(format!("owner {}:{} vs {}:{}", lu, lg, ru, rg));
+        }
+        if let Some((left, right)) = self.mtime {
+            let format_time = |secs: i64| {
+                chrono::DateTime::from_timestamp(secs, 0)
+                    .map(|t| {
+                        t.with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M:%S")
+                            .to_string()
+                    })
+                    .unwrap_or_else(|| secs.to_string())
+            };
+            parts.push(format!(
+                "mtime {} vs {}",
+                format_time(left),
+                format_time(right)
+            ));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Compare the metadata of two files selected by `options`
+/// Returns None if nothing differs
+pub fn compare_metadata(
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+) -> Result<Option<MetadataDiff>> {
+    let left_meta = fs::metadata(left)?;
+    let right_meta = fs::metadata(right)?;
+    let mut diff = MetadataDiff::default();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        if options.compare_mode {
+            let (left_mode, right_mode) = (left_meta.mode() & 0o7777, right_meta.mode() & 0o7777);
+            if left_mode != right_mode {
+                diff.mode = Some((left_mode, right_mode));
+            }
+        }
+        if options.compare_owner {
+            let left_owner = (left_meta.uid(), left_meta.gid());
+            let right_owner = (right_meta.uid(), right_meta.gid());
+            if left_owner != right_owner {
+                diff.owner = Some((left_owner, right_owner));
+            }
+        }
+    }
+
+    if options.compare_mtime {
+        let mtime = |meta: &fs::Metadata| -> Result<i64> {
+            Ok(meta.modified()?.duration_since(UNIX_EPOCH)?.as_secs() as i64)
+        };
+        let (left_mtime, right_mtime) = (mtime(&left_meta)?, mtime(&right_meta)?);
+        if left_mtime != right_mtime {
+            diff.mtime = Some((left_mtime, right_mtime));
+        }
+    }
+
+    Ok(if diff.is_empty() { None } else { Some(diff) })
+}
+
+/// Chunk size for streamed reads
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of leading bytes inspected for binary detection
+const BINARY_CHECK_LEN: usize = 8192;
 
 /// Compare two files and return whether they are identical
+///
+/// Files of different sizes are rejected from metadata alone; otherwise the
+/// contents are compared chunk by chunk and the first difference stops the
+/// read, so neither file is ever loaded into memory as a whole.
 pub fn compare_files(left: &Path, right: &Path) -> Result<bool> {
-    let left_content = fs::read(left)?;
-    let right_content = fs::read(right)?;
-    Ok(left_content == right_content)
+    if fs::metadata(left)?.len() != fs::metadata(right)?.len() {
+        return Ok(false);
+    }
+
+    let mut left_file = File::open(left)?;
+    let mut right_file = File::open(right)?;
+    let mut left_buf = vec![0u8; CHUNK_SIZE];
+    let mut right_buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let left_len = read_chunk(&mut left_file, &mut left_buf)?;
+        let right_len = read_chunk(&mut right_file, &mut right_buf)?;
+        if left_buf[..left_len] != right_buf[..right_len] {
+            return Ok(false);
+        }
+        if left_len == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Compare two equal-sized files byte for byte like `compare_files`,
+/// returning their content hash when they are identical
+fn compare_files_hashed(left: &Path, right: &Path) -> Result<Option<u128>> {
+    let mut left_file = File::open(left)?;
+    let mut right_file = File::open(right)?;
+    let mut left_buf = vec![0u8; CHUNK_SIZE];
+    let mut right_buf = vec![0u8; CHUNK_SIZE];
+    let mut hash = FNV_OFFSET_BASIS;
+    loop {
+        let left_len = read_chunk(&mut left_file, &mut left_buf)?;
+        let right_len = read_chunk(&mut right_file, &mut right_buf)?;
+        if left_buf[..left_len] != right_buf[..right_len] {
+            return Ok(None);
+        }
+        if left_len == 0 {
+            return Ok(Some(hash));
+        }
+        hash = fnv1a(hash, &left_buf[..left_len]);
+    }
+}
+
+/// Compare two files honouring `options.trust_mtime`, `options.cache` and
+/// `options.whitespace`
+///
+/// In trust-mtime mode, files with equal size and modification time are
+/// considered identical without reading them (like rsync's quick check).
+/// With a cache, equal-sized files whose hashes are both cached from a
+/// previous run are compared by hash; others are compared byte for byte,
+/// and the hash of files found identical is cached.
+/// When whitespace differences are ignored, text files that are not
+/// byte-identical are read whole and compared line by line.
+pub fn compare_files_with_options(
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+) -> Result<bool> {
+    let left_meta = fs::metadata(left)?;
+    let right_meta = fs::metadata(right)?;
+    let ignoring = options.whitespace.is_active();
+    if left_meta.len() == right_meta.len() {
+        if options.trust_mtime && left_meta.modified()? == right_meta.modified()? {
+            return Ok(true);
+        }
+        let identical = match &options.cache {
+            Some(cache) => match (
+                cache.cached(left, &left_meta)?,
+                cache.cached(right, &right_meta)?,
+            ) {
+                (Some(left_hash), Some(right_hash)) => left_hash == right_hash,
+                // Identical files are read to the end anyway, so only their
+                // hashes are worth keeping
+                _ => match compare_files_hashed(left, right)? {
+                    Some(hash) => {
+                        cache.insert(left, &left_meta, hash)?;
+                        cache.insert(right, &right_meta, hash)?;
+                        true
+                    }
+                    None => false,
+                },
+            },
+            None => compare_files(left, right)?,
+        };
+        if identical || !ignoring {
+            return Ok(identical);
+        }
+    } else if !ignoring {
+        return Ok(false);
+    }
+    match (read_text_file(left)?, read_text_file(right)?) {
+        (Some(left), Some(right)) => Ok(options.whitespace.texts_equal(&left, &right)),
+        _ => Ok(false),
+    }
+}
+
+/// Fill `buf` as far as possible, returning fewer bytes only at end of file
+fn read_chunk(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+/// FNV-1a 128-bit parameters
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Hash a file's content in streamed chunks
+///
+/// Uses 128-bit FNV-1a, whose output is stable across builds and platforms,
+/// so hashes can be stored in the on-disk cache.
+pub fn hash_file(path: &Path) -> Result<u128> {
+    let mut file = File::open(path)?;
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let len = read_chunk(&mut file, &mut buf)?;
+        if len == 0 {
+            return Ok(hash);
+        }
+        hash = fnv1a(hash, &buf[..len]);
+    }
+}
+
+/// Hash in-memory bytes with the same stable function as `hash_file`
+pub fn hash_bytes(bytes: &[u8]) -> u128 {
+    fnv1a(FNV_OFFSET_BASIS, bytes)
+}
+
+fn fnv1a(mut hash: u128, bytes: &[u8]) -> u128 {
+    for byte in bytes {
+        hash ^= u128::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Compare two symlinks and return whether they point to the same target
+pub fn compare_symlinks(left: &Path, right: &Path) -> Result<bool> {
+    Ok(fs::read_link(left)? == fs::read_link(right)?)
 }
 
 /// Check if a file appears to be binary
 pub fn is_binary(path: &Path) -> Result<bool> {
-    let content = fs::read(path)?;
-    // Check for null bytes in the first 8KB
-    let check_len = content.len().min(8192);
-    Ok(content[..check_len].contains(&0))
+    // Check for null bytes in the first 8KB only
+    let mut buf = [0u8; BINARY_CHECK_LEN];
+    let len = read_chunk(&mut File::open(path)?, &mut buf)?;
+    Ok(buf[..len].contains(&0))
 }
 
 /// Get file content as string if it's a text file
 pub fn read_text_file(path: &Path) -> Result<Option<String>> {
     let content = fs::read(path)?;
     // Check for null bytes
-    let check_len = content.len().min(8192);
+    let check_len = content.len().min(BINARY_CHECK_LEN);
     if content[..check_len].contains(&0) {
         return Ok(None);
     }
@@ -137,6 +385,172 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_compare_files_differ_after_first_chunk() {
+        // Given: Two large files of equal size differing only in the last byte
+        let dir = create_temp_dir();
+        let left = dir.path().join("left.bin");
+        let right = dir.path().join("right.bin");
+        let mut content = vec![b'a'; CHUNK_SIZE * 3 + 17];
+        fs::write(&left, &content).unwrap();
+        *content.last_mut().unwrap() = b'b';
+        fs::write(&right, &content).unwrap();
+
+        // When: Comparing the files
+        let result = compare_files(&left, &right).unwrap();
+
+        // Then: The difference in a later chunk is found
+        assert!(!result);
+    }
+
+    #[test]
+    fn test_compare_files_with_options_trust_mtime() {
+        // Given: Two files with equal size and mtime but different content
+        let dir = create_temp_dir();
+        let left = dir.path().join("left.txt");
+        let right = dir.path().join("right.txt");
+        fs::write(&left, "aaaa").unwrap();
+        fs::write(&right, "bbbb").unwrap();
+        let mtime = fs::metadata(&left).unwrap().modified().unwrap();
+        File::options()
+            .write(true)
+            .open(&right)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        let trusting = CompareOptions {
+            trust_mtime: true,
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing with and without trusting mtime
+        let quick = compare_files_with_options(&left, &right, &trusting).unwrap();
+        let full = compare_files_with_options(&left, &right, &CompareOptions::default()).unwrap();
+
+        // Then: Only the quick check reports them as identical
+        assert!(quick);
+        assert!(!full);
+    }
+
+    #[test]
+    fn test_compare_files_with_options_cache() {
+        // Given: Identical and different files compared through a cache
+        let dir = create_temp_dir();
+        let left = dir.path().join("left.txt");
+        let same = dir.path().join("same.txt");
+        let other = dir.path().join("other.txt");
+        fs::write(&left, "aaaa").unwrap();
+        fs::write(&same, "aaaa").unwrap();
+        fs::write(&other, "bbbb").unwrap();
+        let options = CompareOptions {
+            cache: Some(std::sync::Arc::new(crate::diff::HashCache::new())),
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing the files
+
+        // Then: Contents decide equality
+        assert!(compare_files_with_options(&left, &same, &options).unwrap());
+        assert!(!compare_files_with_options(&left, &other, &options).unwrap());
+    }
+
+    #[test]
+    fn test_compare_files_with_options_caches_identical_files_only() {
+        // Given: Old files, two identical and one different, and an empty cache
+        let dir = create_temp_dir();
+        let left = dir.path().join("left.txt");
+        let same = dir.path().join("same.txt");
+        let other = dir.path().join("other.txt");
+        fs::write(&left, "aaaa").unwrap();
+        fs::write(&same, "aaaa").unwrap();
+        fs::write(&other, "bbbb").unwrap();
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        for path in [&left, &same, &other] {
+            File::open(path).unwrap().set_modified(old).unwrap();
+        }
+        let cache = std::sync::Arc::new(crate::diff::HashCache::new());
+        let options = CompareOptions {
+            cache: Some(cache.clone()),
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing the different pair, then the identical pair
+        let different = compare_files_with_options(&left, &other, &options).unwrap();
+        let cached_after_different = cache.len();
+        let identical = compare_files_with_options(&left, &same, &options).unwrap();
+
+        // Then: Only the identical pair is hashed, with the regular file hash
+        assert!(!different);
+        assert_eq!(cached_after_different, 0);
+        assert!(identical);
+        assert_eq!(cache.len(), 2);
+        let metadata = fs::metadata(&same).unwrap();
+        assert_eq!(
+            cache.cached(&same, &metadata).unwrap(),
+            Some(hash_file(&same).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_compare_files_with_options_whitespace() {
+        // Given: Files differing only in line endings, and in content
+        let dir = create_temp_dir();
+        let left = dir.path().join("left.txt");
+        let crlf = dir.path().join("crlf.txt");
+        let other = dir.path().join("other.txt");
+        fs::write(&left, "a\nb\n").unwrap();
+        fs::write(&crlf, "a\r\nb\r\n").unwrap();
+        fs::write(&other, "a\r\nc\r\n").unwrap();
+        let options = CompareOptions {
+            whitespace: crate::diff::WhitespaceOptions {
+                ignore_eol: true,
+                ..Default::default()
+            },
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing with and without ignoring line endings
+
+        // Then: Only real changes count when line endings are ignored
+        assert!(compare_files_with_options(&left, &crlf, &options).unwrap());
+        assert!(!compare_files_with_options(&left, &other, &options).unwrap());
+        assert!(!compare_files_with_options(&left, &crlf, &CompareOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn test_hash_file_matches_for_equal_content() {
+        // Given: Two files with the same content and one with different content
+        let dir = create_temp_dir();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        let c = dir.path().join("c");
+        fs::write(&a, "content").unwrap();
+        fs::write(&b, "content").unwrap();
+        fs::write(&c, "other").unwrap();
+
+        // When: Hashing the files
+
+        // Then: Equal content gives equal hashes
+        assert_eq!(hash_file(&a).unwrap(), hash_file(&b).unwrap());
+        assert_ne!(hash_file(&a).unwrap(), hash_file(&c).unwrap());
+    }
+
+    #[test]
+    fn test_hash_file_is_stable() {
+        // Given: A file with known content
+        let dir = create_temp_dir();
+        let path = dir.path().join("a");
+        fs::write(&path, "a").unwrap();
+
+        // When: Hashing the file
+
+        // Then: The result is the published FNV-1a 128 value
+        assert_eq!(
+            hash_file(&path).unwrap(),
+            0xd228cb696f1a8caf78912b704e4a8964
+        );
+    }
+
     // ========================================
     // is_binary tests
     // ========================================
@@ -315,4 +729,121 @@ mod tests {
         // Then: An error is returned
         assert!(result.is_err());
     }
+
+    // ========================================
+    // compare_symlinks tests
+    // ========================================
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compare_symlinks_same_target() {
+        // Given: Two symlinks pointing to the same (missing) target
+        let dir = create_temp_dir();
+        let left = dir.path().join("left");
+        let right = dir.path().join("right");
+        std::os::unix::fs::symlink("target.txt", &left).unwrap();
+        std::os::unix::fs::symlink("target.txt", &right).unwrap();
+
+        // When: Comparing the symlinks
+        let result = compare_symlinks(&left, &right).unwrap();
+
+        // Then: They are reported as identical without reading the target
+        assert!(result);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compare_symlinks_different_target() {
+        // Given: Two symlinks pointing to different targets with equal content
+        let dir = create_temp_dir();
+        fs::write(dir.path().join("a.txt"), "same").unwrap();
+        fs::write(dir.path().join("b.txt"), "same").unwrap();
+        let left = dir.path().join("left");
+        let right = dir.path().join("right");
+        std::os::unix::fs::symlink("a.txt", &left).unwrap();
+        std::os::unix::fs::symlink("b.txt", &right).unwrap();
+
+        // When: Comparing the symlinks
+        let result = compare_symlinks(&left, &right).unwrap();
+
+        // Then: They are reported as different
+        assert!(!result);
+    }
+
+    // ========================================
+    // compare_metadata tests
+    // ========================================
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compare_metadata_mode_differs() {
+        // Given: Two identical files where only one is executable
+        use std::os::unix::fs::PermissionsExt;
+        let dir = create_temp_dir();
+        let left = dir.path().join("left.sh");
+        let right = dir.path().join("right.sh");
+        fs::write(&left, "#!/bin/sh\n").unwrap();
+        fs::write(&right, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&left, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&right, fs::Permissions::from_mode(0o644)).unwrap();
+
+        // When: Comparing their metadata
+        let result = compare_metadata(&left, &right, &CompareOptions::default()).unwrap();
+
+        // Then: The mode difference is reported
+        let diff = result.unwrap();
+        assert_eq!(diff.mode, Some((0o755, 0o644)));
+        assert_eq!(diff.to_string(), "mode 0755 vs 0644");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compare_metadata_mode_ignored() {
+        // Given: Two files with different modes and mode comparison disabled
+        use std::os::unix::fs::PermissionsExt;
+        let dir = create_temp_dir();
+        let left = dir.path().join("left.sh");
+        let right = dir.path().join("right.sh");
+        fs::write(&left, "").unwrap();
+        fs::write(&right, "").unwrap();
+        fs::set_permissions(&left, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&right, fs::Permissions::from_mode(0o644)).unwrap();
+        let options = CompareOptions {
+            compare_mode: false,
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing their metadata
+        let result = compare_metadata(&left, &right, &options).unwrap();
+
+        // Then: No difference is reported
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_compare_metadata_mtime_differs() {
+        // Given: Two files with different modification times
+        let dir = create_temp_dir();
+        let left = dir.path().join("left.txt");
+        let right = dir.path().join("right.txt");
+        fs::write(&left, "same").unwrap();
+        fs::write(&right, "same").unwrap();
+        let old = UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&left)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let options = CompareOptions {
+            compare_mtime: true,
+            ..CompareOptions::default()
+        };
+
+        // When: Comparing their metadata with mtime enabled
+        let result = compare_metadata(&left, &right, &options).unwrap();
+
+        // Then: The mtime difference is reported
+        assert_eq!(result.unwrap().mtime.unwrap().0, 1_000_000);
+    }
 }
@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::path::Path;
+
+use super::glob::{path_to_slash, GlobPattern};
+
+/// Include/exclude filters applied to both trees before any file is read
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Vec<GlobPattern>,
+    exclude: Vec<GlobPattern>,
+    exclude_regex_left: Option<Regex>,
+    exclude_regex_right: Option<Regex>,
+}
+
+impl PathFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only compare files matching this glob (may be given several times)
+    pub fn include(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(GlobPattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Skip paths matching this glob (may be given several times)
+    pub fn exclude(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(GlobPattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Skip paths matching any glob listed in a file (ignore-file syntax)
+    pub fn exclude_from(mut self, path: &Path) -> Result<Self> {
+        self.exclude.extend(GlobPattern::from_file(path)?);
+        Ok(self)
+    }
+
+    /// Skip left-side paths whose full relative path matches this regex
+    pub fn exclude_regex_left(mut self, pattern: &str) -> Result<Self> {
+        self.exclude_regex_left =
+            Some(Regex::new(pattern).context("Invalid regex pattern for left exclusion")?);
+        Ok(self)
+    }
+
+    /// Skip right-side paths whose full relative path matches this regex
+    pub fn exclude_regex_right(mut self, pattern: &str) -> Result<Self> {
+        self.exclude_regex_right =
+            Some(Regex::new(pattern).context("Invalid regex pattern for right exclusion")?);
+        Ok(self)
+    }
+
+    /// Whether include patterns were given (then only matching files are collected)
+    pub fn has_includes(&self) -> bool {
+        !self.include.is_empty()
+    }
+
+    /// Check whether a path is excluded by the glob filters
+    /// Like ignore files, the last matching pattern wins so `!` can re-include
+    pub fn is_excluded(&self, rel_path: &Path, is_dir: bool) -> bool {
+        let path = path_to_slash(rel_path);
+        self.exclude
+            .iter()
+            .rev()
+            .find(|p| p.matches(&path, is_dir))
+            .is_some_and(|p| !p.negated)
+    }
+
+    /// Check whether a file passes the include filters
+    pub fn is_included(&self, rel_path: &Path) -> bool {
+        if self.include.is_empty() {
+            return true;
+        }
+        let path = path_to_slash(rel_path);
+        self.include.iter().any(|p| p.matches(&path, false))
+    }
+
+    /// Check the per-side regexes for a path present in `in_left`/`in_right`
+    ///
+    /// A path present on both sides is excluded if either regex matches.
+    pub fn is_excluded_by_regex(&self, rel_path: &Path, in_left: bool, in_right: bool) -> bool {
+        let path = rel_path.to_string_lossy();
+        let left_match = in_left
+            && self
+                .exclude_regex_left
+                .as_ref()
+                .is_some_and(|re| re.is_match(&path));
+        let right_match = in_right
+            && self
+                .exclude_regex_right
+                .as_ref()
+                .is_some_and(|re| re.is_match(&path));
+        left_match || right_match
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_filter_accepts_everything() {
+        // Given: A filter with no patterns
+        let filter = PathFilter::new();
+
+        // When: Checking a path
+
+        // Then: It is neither excluded nor filtered out by includes
+        assert!(!filter.is_excluded(Path::new("a/b.txt"), false));
+        assert!(filter.is_included(Path::new("a/b.txt")));
+    }
+
+    #[test]
+    fn test_multiple_excludes() {
+        // Given: Two exclude patterns
+        let filter = PathFilter::new()
+            .exclude("*.bak")
+            .unwrap()
+            .exclude("node_modules/")
+            .unwrap();
+
+        // When: Checking matching and non-matching paths
+
+        // Then: Either pattern excludes
+        assert!(filter.is_excluded(Path::new("src/main.rs.bak"), false));
+        assert!(filter.is_excluded(Path::new("web/node_modules"), true));
+        assert!(!filter.is_excluded(Path::new("src/main.rs"), false));
+    }
+
+    #[test]
+    fn test_include_limits_files() {
+        // Given: An include pattern for Terraform files
+        let filter = PathFilter::new().include("*.tf").unwrap();
+
+        // When: Checking files
+
+        // Then: Only matching files are included
+        assert!(filter.is_included(Path::new("modules/a/main.tf")));
+        assert!(!filter.is_included(Path::new("README.md")));
+    }
+
+    #[test]
+    fn test_regex_semantics_per_side() {
+        // Given: A left-only exclusion regex
+        let filter = PathFilter::new().exclude_regex_left(r"\.bak$").unwrap();
+
+        // When: Checking paths present on different sides
+
+        // Then: Only paths present on the left are excluded
+        assert!(filter.is_excluded_by_regex(Path::new("a.bak"), true, false));
+        assert!(filter.is_excluded_by_regex(Path::new("a.bak"), true, true));
+        assert!(!filter.is_excluded_by_regex(Path::new("a.bak"), false, true));
+    }
+}
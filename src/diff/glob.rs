@@ -0,0 +1,254 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::path::Path;
+
+/// A single glob pattern with gitignore semantics
+#[derive(Debug, Clone)]
+pub struct GlobPattern {
+    regex: Regex,
+    /// Pattern started with `!` (re-includes a previously ignored path)
+    pub negated: bool,
+    /// Pattern ended with `/` (matches directories only)
+    pub dir_only: bool,
+}
+
+impl GlobPattern {
+    /// Compile a single glob pattern given on the command line
+    pub fn new(pattern: &str) -> Result<Self> {
+        match Self::compile(pattern)? {
+            Some(glob) => Ok(glob),
+            None => bail!("Empty glob pattern: {:?}", pattern),
+        }
+    }
+
+    /// Parse one line of an ignore file
+    /// Returns None for blank lines, comments and invalid patterns
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        // Trailing spaces are ignored unless escaped with a backslash
+        let line = if line.ends_with("\\ ") {
+            line
+        } else {
+            line.trim_end_matches(' ')
+        };
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        Self::compile(line).ok().flatten()
+    }
+
+    fn compile(pattern: &str) -> Result<Option<Self>> {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+
+        // A slash anywhere but at the end anchors the pattern to its directory
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+        let regex = Regex::new(&format!("{}{}$", prefix, glob_to_regex(pattern)))
+            .with_context(|| format!("Invalid glob pattern: {:?}", pattern))?;
+
+        Ok(Some(Self {
+            regex,
+            negated,
+            dir_only,
+        }))
+    }
+
+    /// Read glob patterns from a file in ignore-file syntax
+    pub fn from_file(path: &Path) -> Result<Vec<Self>> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pattern file {}", path.display()))?;
+        Ok(content.lines().filter_map(Self::parse).collect())
+    }
+
+    /// Check whether a relative path (using `/` separators) matches
+    pub fn matches(&self, rel_path: &str, is_dir: bool) -> bool {
+        (!self.dir_only || is_dir) && self.regex.is_match(rel_path)
+    }
+}
+
+/// Translate a gitignore glob (without the leading `/` or `!`) to a regex body
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                let before_slash = chars.get(i + 2) == Some(&'/');
+                if at_start && before_slash {
+                    // "**/" matches zero or more leading directories
+                    regex.push_str("(?:.*/)?");
+                    i += 3;
+                } else if at_start && i + 2 == chars.len() {
+                    // trailing "/**" matches everything inside
+                    regex.push_str(".*");
+                    i += 2;
+                } else {
+                    regex.push_str("[^/]*");
+                    i += 2;
+                }
+            }
+            '*' => {
+                regex.push_str("[^/]*");
+                i += 1;
+            }
+            '?' => {
+                regex.push_str("[^/]");
+                i += 1;
+            }
+            '[' => match chars[i + 1..].iter().position(|&c| c == ']') {
+                Some(len) if len > 0 => {
+                    let class: String = chars[i + 1..i + 1 + len].iter().collect();
+                    let class = match class.strip_prefix('!') {
+                        Some(rest) => format!("^{}", rest),
+                        None => class,
+                    };
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\"));
+                    regex.push(']');
+                    i += len + 2;
+                }
+                _ => {
+                    regex.push_str("\\[");
+                    i += 1;
+                }
+            },
+            '\\' if i + 1 < chars.len() => {
+                regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 2;
+            }
+            c => {
+                regex.push_str(&regex::escape(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+
+    regex
+}
+
+/// Convert a relative path to a `/`-separated string for matching
+pub fn path_to_slash(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(line: &str) -> GlobPattern {
+        GlobPattern::parse(line).unwrap()
+    }
+
+    // ========================================
+    // GlobPattern tests
+    // ========================================
+
+    #[test]
+    fn test_parse_skips_blank_and_comment_lines() {
+        // Given: A blank line and a comment line
+
+        // When: Parsing them as patterns
+
+        // Then: No pattern is produced
+        assert!(GlobPattern::parse("").is_none());
+        assert!(GlobPattern::parse("# comment").is_none());
+    }
+
+    #[test]
+    fn test_unanchored_pattern_matches_any_level() {
+        // Given: A pattern without a slash
+        let p = pattern("*.log");
+
+        // When: Matching paths at several depths
+
+        // Then: The basename is matched at any level
+        assert!(p.matches("debug.log", false));
+        assert!(p.matches("a/b/debug.log", false));
+        assert!(!p.matches("debug.log.txt", false));
+    }
+
+    #[test]
+    fn test_anchored_pattern_matches_from_root() {
+        // Given: A pattern with a leading slash
+        let p = pattern("/build");
+
+        // When: Matching paths at several depths
+
+        // Then: Only the top-level entry matches
+        assert!(p.matches("build", true));
+        assert!(!p.matches("src/build", true));
+    }
+
+    #[test]
+    fn test_dir_only_pattern() {
+        // Given: A pattern with a trailing slash
+        let p = pattern("target/");
+
+        // When: Matching a directory and a file with the same name
+
+        // Then: Only the directory matches
+        assert!(p.matches("target", true));
+        assert!(!p.matches("target", false));
+    }
+
+    #[test]
+    fn test_double_star_patterns() {
+        // Given: Patterns using "**"
+        let leading = pattern("**/generated");
+        let middle = pattern("a/**/z.txt");
+        let trailing = pattern("vendor/**");
+
+        // When: Matching nested paths
+
+        // Then: "**" spans any number of directories
+        assert!(leading.matches("generated", true));
+        assert!(leading.matches("x/y/generated", true));
+        assert!(middle.matches("a/z.txt", false));
+        assert!(middle.matches("a/b/c/z.txt", false));
+        assert!(trailing.matches("vendor/x/y", false));
+        assert!(!trailing.matches("vendor", true));
+    }
+
+    #[test]
+    fn test_character_class_and_negation() {
+        // Given: A character class pattern and a negated pattern
+        let class = pattern("file[0-9].txt");
+        let negated = pattern("!keep.log");
+
+        // When: Parsing and matching
+
+        // Then: Classes match single characters and negation is recorded
+        assert!(class.matches("file1.txt", false));
+        assert!(!class.matches("fileA.txt", false));
+        assert!(negated.negated);
+        assert!(negated.matches("keep.log", false));
+    }
+
+    #[test]
+    fn test_new_rejects_empty_pattern() {
+        // Given: An empty command-line pattern
+
+        // When: Compiling it
+        let result = GlobPattern::new("");
+
+        // Then: An error is returned
+        assert!(result.is_err());
+    }
+}
@@ -1,4 +1,103 @@
-use similar::TextDiff;
+use similar::{capture_diff_slices, Algorithm, DiffOp, DiffTag, TextDiff};
+use std::str::FromStr;
+
+use super::whitespace::WhitespaceOptions;
+
+/// How lines of two files are matched up when looking for changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffAlgorithm {
+    #[default]
+    Myers,
+    /// Matches unique lines first, which keeps moved blocks apart
+    Patience,
+    Lcs,
+}
+
+impl FromStr for DiffAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "myers" => Ok(DiffAlgorithm::Myers),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            "lcs" => Ok(DiffAlgorithm::Lcs),
+            _ => Err(format!(
+                "invalid value '{}' (expected myers, patience or lcs)",
+                s
+            )),
+        }
+    }
+}
+
+/// How two texts are split into hunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HunkOptions {
+    /// Unchanged lines shown around each change
+    pub context_lines: usize,
+    pub algorithm: DiffAlgorithm,
+    /// Differences that never make up a hunk
+    pub whitespace: WhitespaceOptions,
+}
+
+impl Default for HunkOptions {
+    fn default() -> Self {
+        Self {
+            context_lines: 3,
+            algorithm: DiffAlgorithm::Myers,
+            whitespace: WhitespaceOptions::default(),
+        }
+    }
+}
+
+impl HunkOptions {
+    fn similar_algorithm(&self) -> Algorithm {
+        match self.algorithm {
+            DiffAlgorithm::Myers => Algorithm::Myers,
+            DiffAlgorithm::Patience => Algorithm::Patience,
+            DiffAlgorithm::Lcs => Algorithm::Lcs,
+        }
+    }
+
+    /// Line diff of two texts with the chosen algorithm
+    pub fn diff_lines<'a>(&self, left: &'a str, right: &'a str) -> TextDiff<'a, 'a, 'a, str> {
+        TextDiff::configure()
+            .algorithm(self.similar_algorithm())
+            .diff_lines(left, right)
+    }
+
+    /// Operations turning the lines of `left` into those of `right`
+    ///
+    /// Lines are matched by their whitespace keys, and changes made only of
+    /// ignored blank lines are dropped, so ignored differences end up in the
+    /// equal regions between changes.
+    pub fn line_ops(&self, left: &str, right: &str) -> Vec<DiffOp> {
+        let whitespace = &self.whitespace;
+        if !whitespace.is_active() {
+            return self.diff_lines(left, right).ops().to_vec();
+        }
+        let left_lines: Vec<&str> = left.split_inclusive('\n').collect();
+        let right_lines: Vec<&str> = right.split_inclusive('\n').collect();
+        let keys = |lines: &[&str]| -> Vec<String> {
+            lines.iter().map(|line| whitespace.line_key(line)).collect()
+        };
+        let ignored = |lines: &[&str], range: std::ops::Range<usize>| {
+            lines[range]
+                .iter()
+                .all(|line| whitespace.is_ignored_line(line))
+        };
+        capture_diff_slices(
+            self.similar_algorithm(),
+            &keys(&left_lines),
+            &keys(&right_lines),
+        )
+        .into_iter()
+        .filter(|op| {
+            op.tag() == DiffTag::Equal
+                || !(ignored(&left_lines, op.old_range()) && ignored(&right_lines, op.new_range()))
+        })
+        .collect()
+    }
+}
 
 /// A single hunk (contiguous block of changes)
 #[derive(Debug, Clone)]
@@ -21,6 +120,23 @@ pub struct Hunk {
     pub context_after: Vec<String>,
 }
 
+#[cfg(test)]
+impl Hunk {
+    /// Hunk at the start of both files replacing `left_lines` with `right_lines`
+    pub(crate) fn for_test(left_lines: &[&str], right_lines: &[&str]) -> Self {
+        Self {
+            left_start: 0,
+            left_count: left_lines.len(),
+            right_start: 0,
+            right_count: right_lines.len(),
+            left_lines: left_lines.iter().map(|line| line.to_string()).collect(),
+            right_lines: right_lines.iter().map(|line| line.to_string()).collect(),
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+        }
+    }
+}
+
 /// Choice for a hunk
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HunkChoice {
@@ -30,6 +146,100 @@ pub enum HunkChoice {
     Right,
     /// Skip this hunk (leave both files unchanged for this hunk)
     Skip,
+    /// Keep both versions, one after the other, in both files
+    Both(BothOrder),
+}
+
+/// Which side comes first when both versions of a hunk are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BothOrder {
+    LeftFirst,
+    RightFirst,
+}
+
+/// How a hunk is resolved, beyond picking a side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkResolution {
+    /// Use one side or skip
+    Choice(HunkChoice),
+    /// Hand-written lines (without newlines) that both files receive
+    Edited(Vec<String>),
+    /// One resolution per piece returned by `split_hunk`; missing ones skip
+    Split(Vec<HunkResolution>),
+}
+
+impl From<HunkChoice> for HunkResolution {
+    fn from(choice: HunkChoice) -> Self {
+        HunkResolution::Choice(choice)
+    }
+}
+
+impl HunkResolution {
+    /// Side whose trailing newline both files take, if any
+    fn trailing_side(&self) -> Option<HunkChoice> {
+        match self {
+            HunkResolution::Choice(HunkChoice::Skip) | HunkResolution::Edited(_) => None,
+            // The side written last ends the region
+            HunkResolution::Choice(HunkChoice::Both(BothOrder::LeftFirst)) => {
+                Some(HunkChoice::Right)
+            }
+            HunkResolution::Choice(HunkChoice::Both(BothOrder::RightFirst)) => {
+                Some(HunkChoice::Left)
+            }
+            HunkResolution::Choice(choice) => Some(*choice),
+            HunkResolution::Split(parts) => parts.iter().rev().find_map(Self::trailing_side),
+        }
+    }
+
+    /// Add the lines each file gets for a region to the merged files
+    fn resolve(
+        &self,
+        left: &[&str],
+        right: &[&str],
+        merged_left: &mut Vec<String>,
+        merged_right: &mut Vec<String>,
+    ) {
+        let to_strings = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        match self {
+            HunkResolution::Choice(HunkChoice::Left) => {
+                merged_left.extend(to_strings(left));
+                merged_right.extend(to_strings(left));
+            }
+            HunkResolution::Choice(HunkChoice::Right) => {
+                merged_left.extend(to_strings(right));
+                merged_right.extend(to_strings(right));
+            }
+            HunkResolution::Choice(HunkChoice::Skip) => {
+                merged_left.extend(to_strings(left));
+                merged_right.extend(to_strings(right));
+            }
+            HunkResolution::Choice(HunkChoice::Both(order)) => {
+                let (first, second) = match order {
+                    BothOrder::LeftFirst => (left, right),
+                    BothOrder::RightFirst => (right, left),
+                };
+                let both = [to_strings(first), to_strings(second)].concat();
+                merged_left.extend(both.iter().cloned());
+                merged_right.extend(both);
+            }
+            HunkResolution::Edited(lines) => {
+                merged_left.extend(lines.iter().cloned());
+                merged_right.extend(lines.iter().cloned());
+            }
+            HunkResolution::Split(parts) => {
+                // Pieces pair the lines of both sides by position, see `split_hunk`
+                let skip = HunkResolution::Choice(HunkChoice::Skip);
+                for i in 0..left.len().max(right.len()) {
+                    parts.get(i).unwrap_or(&skip).resolve(
+                        left.get(i..=i).unwrap_or_default(),
+                        right.get(i..=i).unwrap_or_default(),
+                        merged_left,
+                        merged_right,
+                    );
+                }
+            }
+        }
+    }
 }
 
 /// Format a line with correct newline handling
@@ -49,16 +259,18 @@ fn format_line_with_newline(
 }
 
 /// Extract hunks from two text contents
-pub fn extract_hunks(left_content: &str, right_content: &str, context_lines: usize) -> Vec<Hunk> {
+///
+/// Each change found by the diff becomes one hunk, whatever the context size.
+pub fn extract_hunks(left_content: &str, right_content: &str, options: &HunkOptions) -> Vec<Hunk> {
+    let context_lines = options.context_lines;
     let left_lines_vec: Vec<&str> = left_content.lines().collect();
     let right_lines_vec: Vec<&str> = right_content.lines().collect();
     let left_ends_with_newline = left_content.ends_with('\n');
     let right_ends_with_newline = right_content.ends_with('\n');
-    let diff = TextDiff::from_lines(left_content, right_content);
     let mut hunks = Vec::new();
 
-    // Process each operation individually to match apply_hunk_choices
-    for op in diff.ops() {
+    // Process each operation individually
+    for op in &options.line_ops(left_content, right_content) {
         match op {
             similar::DiffOp::Equal { .. } => {
                 // Skip equal sections, they don't create hunks
@@ -250,142 +462,139 @@ pub fn extract_hunks(left_content: &str, right_content: &str, context_lines: usi
     hunks
 }
 
+/// Lines `start..start + len`, clamped to the available lines
+fn line_range<'a, 'b>(lines: &'b [&'a str], start: usize, len: usize) -> &'b [&'a str] {
+    let end = (start + len).min(lines.len());
+    &lines[start.min(end)..end]
+}
+
+/// Split a hunk into one-line pieces, pairing the lines of both sides by
+/// position (like `git add -p` does with `s`)
+///
+/// Extra lines on the longer side become one-line deletions or insertions.
+/// A hunk with a single line on each side cannot be split and is returned
+/// as the only piece.
+pub fn split_hunk(hunk: &Hunk) -> Vec<Hunk> {
+    let count = hunk.left_lines.len().max(hunk.right_lines.len());
+    if count < 2 {
+        return vec![hunk.clone()];
+    }
+    (0..count)
+        .map(|i| {
+            let left_lines: Vec<String> = hunk.left_lines.get(i).cloned().into_iter().collect();
+            let right_lines: Vec<String> = hunk.right_lines.get(i).cloned().into_iter().collect();
+            Hunk {
+                left_start: hunk.left_start + i.min(hunk.left_lines.len()),
+                left_count: left_lines.len(),
+                right_start: hunk.right_start + i.min(hunk.right_lines.len()),
+                right_count: right_lines.len(),
+                left_lines,
+                right_lines,
+                context_before: if i == 0 {
+                    hunk.context_before.clone()
+                } else {
+                    Vec::new()
+                },
+                context_after: if i == count - 1 {
+                    hunk.context_after.clone()
+                } else {
+                    Vec::new()
+                },
+            }
+        })
+        .collect()
+}
+
 /// Apply hunk choices to create merged content
 /// Returns (new_left_content, new_right_content)
 /// - Left choice: both files get left content
 /// - Right choice: both files get right content
 /// - Skip choice: left file keeps left content, right file keeps right content
+/// - Both choice: both files get left and right content, in the chosen order
 pub fn apply_hunk_choices(
     left_content: &str,
     right_content: &str,
-    _hunks: &[Hunk],
+    hunks: &[Hunk],
     choices: &[HunkChoice],
 ) -> (String, String) {
-    let left_lines: Vec<&str> = left_content.lines().collect();
-    let right_lines: Vec<&str> = right_content.lines().collect();
+    let resolutions: Vec<HunkResolution> = choices.iter().map(|&c| c.into()).collect();
+    apply_hunk_resolutions(left_content, right_content, hunks, &resolutions)
+}
+
+/// Merge both original contents with the decisions made so far, leaving
+/// undecided hunks as they are
+///
+/// The merge always starts from the originals, so going back to a hunk and
+/// changing its decision replaces the earlier one instead of stacking on it.
+/// Returns (new_left_content, new_right_content)
+pub fn apply_hunk_decisions(
+    left_content: &str,
+    right_content: &str,
+    hunks: &[Hunk],
+    decisions: &[Option<HunkResolution>],
+) -> (String, String) {
+    let resolutions: Vec<HunkResolution> = decisions
+        .iter()
+        .map(|decision| decision.clone().unwrap_or(HunkChoice::Skip.into()))
+        .collect();
+    apply_hunk_resolutions(left_content, right_content, hunks, &resolutions)
+}
+
+/// Apply hunk resolutions, including split and hand-edited hunks, to create
+/// merged content
+/// Returns (new_left_content, new_right_content)
+pub fn apply_hunk_resolutions(
+    left_content: &str,
+    right_content: &str,
+    hunks: &[Hunk],
+    resolutions: &[HunkResolution],
+) -> (String, String) {
+    // Lines keep a carriage return, so CRLF lines stay CRLF when rejoined
+    let split = |content| -> Vec<&str> {
+        str::split_inclusive(content, '\n')
+            .map(|line| line.strip_suffix('\n').unwrap_or(line))
+            .collect()
+    };
+    let left_lines = split(left_content);
+    let right_lines = split(right_content);
     let mut merged_left_lines: Vec<String> = Vec::new();
     let mut merged_right_lines: Vec<String> = Vec::new();
 
-    // Build the merged content based on choices
-    let diff = TextDiff::from_lines(left_content, right_content);
-    let mut hunk_idx = 0;
-
-    // Process all operations, not just grouped ones
-    for op in diff.ops() {
-        match op {
-            similar::DiffOp::Equal { old_index, len, .. } => {
-                // Copy equal lines (they're the same in both)
-                for i in *old_index..(*old_index + *len) {
-                    if i < left_lines.len() {
-                        merged_left_lines.push(left_lines[i].to_string());
-                        merged_right_lines.push(left_lines[i].to_string());
-                    }
-                }
-            }
-            similar::DiffOp::Delete {
-                old_index, old_len, ..
-            } => {
-                // Lines only in left (deleted from left's perspective)
-                let choice = choices.get(hunk_idx).copied().unwrap_or(HunkChoice::Skip);
-                match choice {
-                    HunkChoice::Left => {
-                        // Keep left content in both files
-                        for i in *old_index..(*old_index + *old_len) {
-                            if i < left_lines.len() {
-                                merged_left_lines.push(left_lines[i].to_string());
-                                merged_right_lines.push(left_lines[i].to_string());
-                            }
-                        }
-                    }
-                    HunkChoice::Skip => {
-                        // Left keeps left content, right doesn't have these lines
-                        for i in *old_index..(*old_index + *old_len) {
-                            if i < left_lines.len() {
-                                merged_left_lines.push(left_lines[i].to_string());
-                            }
-                        }
-                        // Right file: don't include (original right doesn't have these)
-                    }
-                    HunkChoice::Right => {
-                        // Don't include left content in either (it's deleted)
-                    }
-                }
-                hunk_idx += 1;
-            }
-            similar::DiffOp::Insert {
-                new_index, new_len, ..
-            } => {
-                // Lines only in right (inserted from left's perspective)
-                let choice = choices.get(hunk_idx).copied().unwrap_or(HunkChoice::Skip);
-                match choice {
-                    HunkChoice::Right => {
-                        // Include right content in both files
-                        for i in *new_index..(*new_index + *new_len) {
-                            if i < right_lines.len() {
-                                merged_left_lines.push(right_lines[i].to_string());
-                                merged_right_lines.push(right_lines[i].to_string());
-                            }
-                        }
-                    }
-                    HunkChoice::Skip => {
-                        // Left doesn't have these lines, right keeps them
-                        // Left file: don't include (original left doesn't have these)
-                        for i in *new_index..(*new_index + *new_len) {
-                            if i < right_lines.len() {
-                                merged_right_lines.push(right_lines[i].to_string());
-                            }
-                        }
-                    }
-                    HunkChoice::Left => {
-                        // Don't include right content in either (not inserted)
-                    }
-                }
-                hunk_idx += 1;
-            }
-            similar::DiffOp::Replace {
-                old_index,
-                old_len,
-                new_index,
-                new_len,
-            } => {
-                // Lines changed between left and right
-                let choice = choices.get(hunk_idx).copied().unwrap_or(HunkChoice::Skip);
-                match choice {
-                    HunkChoice::Left => {
-                        // Use left content in both files
-                        for i in *old_index..(*old_index + *old_len) {
-                            if i < left_lines.len() {
-                                merged_left_lines.push(left_lines[i].to_string());
-                                merged_right_lines.push(left_lines[i].to_string());
-                            }
-                        }
-                    }
-                    HunkChoice::Skip => {
-                        // Each file keeps its own content
-                        for i in *old_index..(*old_index + *old_len) {
-                            if i < left_lines.len() {
-                                merged_left_lines.push(left_lines[i].to_string());
-                            }
-                        }
-                        for i in *new_index..(*new_index + *new_len) {
-                            if i < right_lines.len() {
-                                merged_right_lines.push(right_lines[i].to_string());
-                            }
-                        }
-                    }
-                    HunkChoice::Right => {
-                        // Use right content in both files
-                        for i in *new_index..(*new_index + *new_len) {
-                            if i < right_lines.len() {
-                                merged_left_lines.push(right_lines[i].to_string());
-                                merged_right_lines.push(right_lines[i].to_string());
-                            }
-                        }
-                    }
-                }
-                hunk_idx += 1;
-            }
+    let skip = HunkResolution::Choice(HunkChoice::Skip);
+
+    // The hunks give the changed regions, so the contents are not diffed
+    // again and the regions are the ones the user saw, whatever algorithm
+    // found them
+    let (mut left_position, mut right_position) = (0, 0);
+    for (hunk_idx, hunk) in hunks.iter().enumerate() {
+        // Lines between hunks match, but each file keeps its own in case
+        // they differ in ignored whitespace
+        let left_len = hunk.left_start.saturating_sub(left_position);
+        let right_len = hunk.right_start.saturating_sub(right_position);
+        for line in line_range(&left_lines, left_position, left_len) {
+            merged_left_lines.push(line.to_string());
+        }
+        for line in line_range(&right_lines, right_position, right_len) {
+            merged_right_lines.push(line.to_string());
         }
+        // Lines only in left are deleted, lines only in right inserted, from
+        // left's perspective; the resolution decides what each file keeps
+        resolutions.get(hunk_idx).unwrap_or(&skip).resolve(
+            line_range(&left_lines, hunk.left_start, hunk.left_count),
+            line_range(&right_lines, hunk.right_start, hunk.right_count),
+            &mut merged_left_lines,
+            &mut merged_right_lines,
+        );
+        left_position = left_position.max(hunk.left_start + hunk.left_count);
+        right_position = right_position.max(hunk.right_start + hunk.right_count);
+    }
+    let left_rest = left_lines.len().saturating_sub(left_position);
+    let right_rest = right_lines.len().saturating_sub(right_position);
+    for line in line_range(&left_lines, left_position, left_rest) {
+        merged_left_lines.push(line.to_string());
+    }
+    for line in line_range(&right_lines, right_position, right_rest) {
+        merged_right_lines.push(line.to_string());
     }
 
     // Determine trailing newline behavior based on choices
@@ -393,11 +602,10 @@ pub fn apply_hunk_choices(
     let right_has_newline = right_content.ends_with('\n');
 
     // Find the last non-skip choice to determine trailing newline behavior
-    let last_choice = choices
+    let last_choice = resolutions
         .iter()
         .rev()
-        .find(|c| **c != HunkChoice::Skip)
-        .copied();
+        .find_map(HunkResolution::trailing_side);
 
     let (left_trailing, right_trailing) = match last_choice {
         Some(HunkChoice::Left) => {
@@ -427,10 +635,293 @@ pub fn apply_hunk_choices(
     (merged_left, merged_right)
 }
 
+/// Which side(s) changed a region relative to the common ancestor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreeWayKind {
+    /// Only the left side changed this region
+    LeftChanged,
+    /// Only the right side changed this region
+    RightChanged,
+    /// Both sides made the identical change
+    BothSame,
+    /// Both sides changed the region differently
+    Conflict,
+}
+
+/// A region that differs from the base in at least one side
+#[derive(Debug, Clone)]
+pub struct ThreeWayHunk {
+    /// Starting line in base file (0-indexed)
+    pub base_start: usize,
+    /// Starting line in left file (0-indexed)
+    pub left_start: usize,
+    /// Starting line in right file (0-indexed)
+    pub right_start: usize,
+    /// Lines of the region in the base file
+    pub base_lines: Vec<String>,
+    /// Lines of the region in the left file
+    pub left_lines: Vec<String>,
+    /// Lines of the region in the right file
+    pub right_lines: Vec<String>,
+    /// Classification of the region
+    pub kind: ThreeWayKind,
+}
+
+impl ThreeWayHunk {
+    /// Whether this hunk needs a user decision
+    pub fn is_conflict(&self) -> bool {
+        self.kind == ThreeWayKind::Conflict
+    }
+
+    /// Convert to a two-way hunk for display (base lines are not included)
+    pub fn to_hunk(&self) -> Hunk {
+        Hunk {
+            left_start: self.left_start,
+            left_count: self.left_lines.len(),
+            right_start: self.right_start,
+            right_count: self.right_lines.len(),
+            left_lines: self.left_lines.iter().map(|l| format!("{}\n", l)).collect(),
+            right_lines: self
+                .right_lines
+                .iter()
+                .map(|l| format!("{}\n", l))
+                .collect(),
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+        }
+    }
+}
+
+/// A change made by one side, expressed as a replaced range of base lines
+#[derive(Debug, Clone)]
+struct SideChange {
+    base_start: usize,
+    base_end: usize,
+    lines: Vec<String>,
+}
+
+/// Collect the non-equal operations between base and one side
+fn side_changes(base_content: &str, side_content: &str, options: &HunkOptions) -> Vec<SideChange> {
+    let side_lines: Vec<&str> = side_content.lines().collect();
+    let diff = options.diff_lines(base_content, side_content);
+
+    diff.ops()
+        .iter()
+        .filter(|op| !matches!(op, similar::DiffOp::Equal { .. }))
+        .map(|op| {
+            let old = op.old_range();
+            let new = op.new_range();
+            SideChange {
+                base_start: old.start,
+                base_end: old.end,
+                lines: new
+                    .filter_map(|i| side_lines.get(i).map(|s| s.to_string()))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Apply one side's changes to the base lines in `start..end`
+fn apply_side_changes(
+    base_lines: &[&str],
+    start: usize,
+    end: usize,
+    changes: &[SideChange],
+) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut pos = start;
+    for change in changes {
+        result.extend(
+            base_lines[pos..change.base_start]
+                .iter()
+                .map(|s| s.to_string()),
+        );
+        result.extend(change.lines.iter().cloned());
+        pos = change.base_end;
+    }
+    result.extend(base_lines[pos..end].iter().map(|s| s.to_string()));
+    result
+}
+
+/// Extract three-way hunks from a base and two derived contents
+///
+/// Changes from both sides that overlap or touch the same base lines are
+/// grouped into one hunk, which is a conflict unless both sides agree.
+pub fn extract_three_way_hunks(
+    base_content: &str,
+    left_content: &str,
+    right_content: &str,
+    options: &HunkOptions,
+) -> Vec<ThreeWayHunk> {
+    let base_lines: Vec<&str> = base_content.lines().collect();
+    let left_changes = side_changes(base_content, left_content, options);
+    let right_changes = side_changes(base_content, right_content, options);
+
+    let mut hunks = Vec::new();
+    let (mut li, mut ri) = (0, 0);
+    // Line offset of each side relative to the base, for reporting positions
+    let (mut left_offset, mut right_offset) = (0isize, 0isize);
+
+    while li < left_changes.len() || ri < right_changes.len() {
+        // Start the region with whichever change comes first in the base
+        let take_left = match (left_changes.get(li), right_changes.get(ri)) {
+            (Some(l), Some(r)) => l.base_start <= r.base_start,
+            (Some(_), None) => true,
+            _ => false,
+        };
+        let first = if take_left {
+            &left_changes[li]
+        } else {
+            &right_changes[ri]
+        };
+        let start = first.base_start;
+        let mut end = first.base_end;
+        let (left_from, right_from) = (li, ri);
+
+        // Absorb every change from either side that overlaps or touches the region
+        loop {
+            if let Some(c) = left_changes.get(li).filter(|c| c.base_start <= end) {
+                end = end.max(c.base_end);
+                li += 1;
+            } else if let Some(c) = right_changes.get(ri).filter(|c| c.base_start <= end) {
+                end = end.max(c.base_end);
+                ri += 1;
+            } else {
+                break;
+            }
+        }
+
+        let left_region = &left_changes[left_from..li];
+        let right_region = &right_changes[right_from..ri];
+        let left_lines = apply_side_changes(&base_lines, start, end, left_region);
+        let right_lines = apply_side_changes(&base_lines, start, end, right_region);
+
+        let kind = if right_region.is_empty() {
+            ThreeWayKind::LeftChanged
+        } else if left_region.is_empty() {
+            ThreeWayKind::RightChanged
+        } else if left_lines == right_lines {
+            ThreeWayKind::BothSame
+        } else {
+            ThreeWayKind::Conflict
+        };
+
+        let base_len = (end - start) as isize;
+        hunks.push(ThreeWayHunk {
+            base_start: start,
+            left_start: (start as isize + left_offset) as usize,
+            right_start: (start as isize + right_offset) as usize,
+            base_lines: base_lines[start..end]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            kind,
+            left_lines,
+            right_lines,
+        });
+        let last = hunks.last().expect("hunk was just pushed");
+        left_offset += last.left_lines.len() as isize - base_len;
+        right_offset += last.right_lines.len() as isize - base_len;
+    }
+
+    hunks
+}
+
+/// Apply three-way hunks and the choices for their conflicts
+/// Returns (new_left_content, new_right_content)
+/// - Non-conflicting hunks: both files receive the changed side
+/// - Conflicts are resolved in order by `choices`, as in `apply_hunk_choices`
+///   (missing choices count as Skip, so each file keeps its own version)
+pub fn apply_three_way_choices(
+    base_content: &str,
+    left_content: &str,
+    right_content: &str,
+    hunks: &[ThreeWayHunk],
+    choices: &[HunkChoice],
+) -> (String, String) {
+    let base_lines: Vec<&str> = base_content.lines().collect();
+    let mut merged_left_lines: Vec<String> = Vec::new();
+    let mut merged_right_lines: Vec<String> = Vec::new();
+    let mut conflict_idx = 0;
+    let mut pos = 0;
+
+    for hunk in hunks {
+        for line in &base_lines[pos..hunk.base_start] {
+            merged_left_lines.push(line.to_string());
+            merged_right_lines.push(line.to_string());
+        }
+        pos = hunk.base_start + hunk.base_lines.len();
+
+        let (left_part, right_part) = match hunk.kind {
+            ThreeWayKind::LeftChanged | ThreeWayKind::BothSame => {
+                (&hunk.left_lines, &hunk.left_lines)
+            }
+            ThreeWayKind::RightChanged => (&hunk.right_lines, &hunk.right_lines),
+            ThreeWayKind::Conflict => {
+                let choice = choices
+                    .get(conflict_idx)
+                    .copied()
+                    .unwrap_or(HunkChoice::Skip);
+                conflict_idx += 1;
+                match choice {
+                    HunkChoice::Left => (&hunk.left_lines, &hunk.left_lines),
+                    HunkChoice::Right => (&hunk.right_lines, &hunk.right_lines),
+                    HunkChoice::Skip => (&hunk.left_lines, &hunk.right_lines),
+                    HunkChoice::Both(order) => {
+                        let (first, second) = match order {
+                            BothOrder::LeftFirst => (&hunk.left_lines, &hunk.right_lines),
+                            BothOrder::RightFirst => (&hunk.right_lines, &hunk.left_lines),
+                        };
+                        let both = [first.clone(), second.clone()].concat();
+                        merged_left_lines.extend(both.iter().cloned());
+                        merged_right_lines.extend(both);
+                        continue;
+                    }
+                }
+            }
+        };
+        merged_left_lines.extend(left_part.iter().cloned());
+        merged_right_lines.extend(right_part.iter().cloned());
+    }
+    for line in &base_lines[pos.min(base_lines.len())..] {
+        merged_left_lines.push(line.to_string());
+        merged_right_lines.push(line.to_string());
+    }
+
+    // A side that changed the trailing newline relative to the base wins
+    let base_has_newline = base_content.ends_with('\n');
+    let left_has_newline = left_content.ends_with('\n');
+    let right_has_newline = right_content.ends_with('\n');
+    let trailing = if left_has_newline != base_has_newline {
+        left_has_newline
+    } else {
+        right_has_newline
+    };
+
+    let mut merged_left = merged_left_lines.join("\n");
+    let mut merged_right = merged_right_lines.join("\n");
+    if trailing && !merged_left.is_empty() {
+        merged_left.push('\n');
+    }
+    if trailing && !merged_right.is_empty() {
+        merged_right.push('\n');
+    }
+
+    (merged_left, merged_right)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn context(context_lines: usize) -> HunkOptions {
+        HunkOptions {
+            context_lines,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_extract_hunks_simple() {
         // Given: Two files with one line different
@@ -438,7 +929,7 @@ mod tests {
         let right = "line1\nmodified\nline3\n";
 
         // When: Extracting hunks with context
-        let hunks = extract_hunks(left, right, 1);
+        let hunks = extract_hunks(left, right, &context(1));
 
         // Then: One hunk is found with the changed lines
         assert_eq!(hunks.len(), 1);
@@ -453,18 +944,171 @@ mod tests {
         let right = "a\nB\nc\nD\ne\n";
 
         // When: Extracting hunks without context
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
 
         // Then: At least one hunk is found
         assert!(!hunks.is_empty());
     }
 
+    #[test]
+    fn test_extract_hunks_context_size() {
+        // Given: A change in the middle of five lines
+        let left = "a\nb\nc\nd\ne\n";
+        let right = "a\nb\nC\nd\ne\n";
+
+        // When: Extracting hunks with one and with no context line
+        let one = extract_hunks(left, right, &context(1));
+        let none = extract_hunks(left, right, &context(0));
+
+        // Then: Only the context differs, not the hunk
+        assert_eq!(one[0].context_before, vec!["b\n"]);
+        assert_eq!(one[0].context_after, vec!["d\n"]);
+        assert!(none[0].context_before.is_empty() && none[0].context_after.is_empty());
+        assert_eq!((one[0].left_start, none[0].left_start), (2, 2));
+    }
+
+    #[test]
+    fn test_patience_keeps_moved_block_together() {
+        // Given: A block ending in a closing brace moved to the top
+        let left = "a\nb\n}\nx\ny\n}\n";
+        let right = "x\ny\n}\na\nb\n}\n";
+        let patience = HunkOptions {
+            algorithm: DiffAlgorithm::Patience,
+            ..Default::default()
+        };
+
+        // When: Extracting hunks with Myers and with Patience
+        let myers = extract_hunks(left, right, &HunkOptions::default());
+        let patience = extract_hunks(left, right, &patience);
+
+        // Then: Patience removes the moved block as it was written
+        assert_eq!(myers[1].left_lines, vec!["}\n", "x\n", "y\n"]);
+        assert_eq!(patience[1].left_lines, vec!["x\n", "y\n", "}\n"]);
+    }
+
+    #[test]
+    fn test_apply_follows_hunks_of_any_algorithm() {
+        // Given: Hunks of a moved block found by each algorithm
+        let left = "a\nb\n}\nx\ny\n}\n";
+        let right = "x\ny\n}\na\nb\n}\n";
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Lcs,
+        ] {
+            let options = HunkOptions {
+                algorithm,
+                ..Default::default()
+            };
+            let hunks = extract_hunks(left, right, &options);
+
+            // When: Taking every hunk from one side
+            let left_choices = vec![HunkChoice::Left; hunks.len()];
+            let right_choices = vec![HunkChoice::Right; hunks.len()];
+            let all_left = apply_hunk_choices(left, right, &hunks, &left_choices);
+            let all_right = apply_hunk_choices(left, right, &hunks, &right_choices);
+
+            // Then: The regions line up with the files, giving back that side
+            assert_eq!(
+                all_left,
+                (left.to_string(), left.to_string()),
+                "{:?}",
+                algorithm
+            );
+            assert_eq!(
+                all_right,
+                (right.to_string(), right.to_string()),
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn test_diff_algorithm_parses_option_values() {
+        // Given/When/Then: The values accepted by --diff-algorithm
+        assert_eq!("myers".parse(), Ok(DiffAlgorithm::Myers));
+        assert_eq!("patience".parse(), Ok(DiffAlgorithm::Patience));
+        assert_eq!("lcs".parse(), Ok(DiffAlgorithm::Lcs));
+        assert!("histogram".parse::<DiffAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn test_ignored_whitespace_makes_no_hunk() {
+        // Given: Files differing in indentation, a blank line and one word
+        let left = "a\n  b\nc\nd\n";
+        let right = "a\nb\n\nc\nD\n";
+        let ignoring = HunkOptions {
+            whitespace: WhitespaceOptions {
+                ignore_all_space: true,
+                ignore_blank_lines: true,
+                ..Default::default()
+            },
+            ..context(0)
+        };
+
+        // When: Extracting hunks with and without ignoring whitespace
+        let exact = extract_hunks(left, right, &context(0));
+        let hunks = extract_hunks(left, right, &ignoring);
+
+        // Then: Only the changed word is a hunk when whitespace is ignored
+        assert_eq!(exact.len(), 2);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].left_lines, vec!["d\n"]);
+        assert_eq!(hunks[0].right_lines, vec!["D\n"]);
+    }
+
+    #[test]
+    fn test_apply_keeps_ignored_whitespace_of_each_side() {
+        // Given: Files differing in indentation and in one word
+        let left = "a\n  b\nc\n";
+        let right = "a\nb\nC\n";
+        let options = HunkOptions {
+            whitespace: WhitespaceOptions {
+                ignore_all_space: true,
+                ..Default::default()
+            },
+            ..context(0)
+        };
+        let hunks = extract_hunks(left, right, &options);
+
+        // When: Taking the right side of the only hunk
+        let merged = apply_hunk_choices(left, right, &hunks, &[HunkChoice::Right]);
+
+        // Then: The change is merged but left keeps its indentation
+        assert_eq!(merged.0, "a\n  b\nC\n");
+        assert_eq!(merged.1, "a\nb\nC\n");
+    }
+
+    #[test]
+    fn test_apply_keeps_crlf_lines() {
+        // Given: A CRLF file and an LF file with one real change
+        let left = "a\r\nb\r\nc\r\n";
+        let right = "a\nb\nC\n";
+        let options = HunkOptions {
+            whitespace: WhitespaceOptions {
+                ignore_eol: true,
+                ..Default::default()
+            },
+            ..context(0)
+        };
+        let hunks = extract_hunks(left, right, &options);
+
+        // When: Taking the left side of the only hunk
+        let merged = apply_hunk_choices(left, right, &hunks, &[HunkChoice::Left]);
+
+        // Then: Each file keeps its line endings outside the hunk
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(merged.0, left);
+        assert_eq!(merged.1, "a\nb\nc\r\n");
+    }
+
     #[test]
     fn test_apply_hunk_choices_left() {
         // Given: Two files with different content and Left choice
         let left = "line1\nold\nline3\n";
         let right = "line1\nnew\nline3\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Left];
 
         // When: Applying hunk choices
@@ -480,7 +1124,7 @@ mod tests {
         // Given: Two files with different content and Right choice
         let left = "line1\nold\nline3\n";
         let right = "line1\nnew\nline3\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Right];
 
         // When: Applying hunk choices
@@ -496,7 +1140,7 @@ mod tests {
         // Given: Two files with different content and Skip choice
         let left = "line1\nold\nline3\n";
         let right = "line1\nnew\nline3\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Skip];
 
         // When: Applying hunk choices
@@ -512,7 +1156,7 @@ mod tests {
         // Given: Left has no trailing newline, right has trailing newline
         let left = "hello";
         let right = "hello\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Left];
 
         // When: Applying hunk choices with Left
@@ -528,7 +1172,7 @@ mod tests {
         // Given: Left has no trailing newline, right has trailing newline
         let left = "hello";
         let right = "hello\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Right];
 
         // When: Applying hunk choices with Right
@@ -544,7 +1188,7 @@ mod tests {
         // Given: Left has no trailing newline, right has trailing newline
         let left = "hello";
         let right = "hello\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Skip];
 
         // When: Applying hunk choices with Skip
@@ -562,7 +1206,7 @@ mod tests {
         let right = "line1\nline3\n";
 
         // When: Extracting hunks
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
 
         // Then: One hunk is found with delete operation (left has line, right is empty)
         assert_eq!(hunks.len(), 1);
@@ -577,7 +1221,7 @@ mod tests {
         let right = "line1\nline2\nline3\n";
 
         // When: Extracting hunks
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
 
         // Then: One hunk is found with insert operation (left is empty, right has line)
         assert_eq!(hunks.len(), 1);
@@ -590,7 +1234,7 @@ mod tests {
         // Given: Right has a line deleted, and Left choice is made
         let left = "line1\nline2\nline3\n";
         let right = "line1\nline3\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Left];
 
         // When: Applying hunk choices
@@ -606,7 +1250,7 @@ mod tests {
         // Given: Right has a line deleted, and Right choice is made
         let left = "line1\nline2\nline3\n";
         let right = "line1\nline3\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Right];
 
         // When: Applying hunk choices
@@ -622,7 +1266,7 @@ mod tests {
         // Given: Right has a line inserted, and Left choice is made
         let left = "line1\nline3\n";
         let right = "line1\nline2\nline3\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Left];
 
         // When: Applying hunk choices
@@ -638,7 +1282,7 @@ mod tests {
         // Given: Right has a line inserted, and Right choice is made
         let left = "line1\nline3\n";
         let right = "line1\nline2\nline3\n";
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
         let choices = vec![HunkChoice::Right];
 
         // When: Applying hunk choices
@@ -649,13 +1293,175 @@ mod tests {
         assert_eq!(merged_right, "line1\nline2\nline3\n");
     }
 
+    #[test]
+    fn test_split_hunk_pairs_lines_by_position() {
+        // Given: A hunk replacing two lines with three
+        let hunks = extract_hunks("a\nb\nz\n", "A\nB\nC\nz\n", &context(3));
+
+        // When: Splitting it
+        let pieces = split_hunk(&hunks[0]);
+
+        // Then: Each line pair is a piece, and the extra line an insertion
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[0].left_lines, vec!["a\n"]);
+        assert_eq!(pieces[0].right_lines, vec!["A\n"]);
+        assert_eq!(pieces[2].left_lines, Vec::<String>::new());
+        assert_eq!(pieces[2].right_lines, vec!["C\n"]);
+        assert_eq!(pieces[2].right_start, 2);
+        assert_eq!(pieces[2].context_after, vec!["z\n"]);
+    }
+
+    #[test]
+    fn test_apply_split_resolution() {
+        // Given: A replace hunk split into pieces with different choices
+        let left = "a\nb\nz\n";
+        let right = "A\nB\nC\nz\n";
+        let hunks = extract_hunks(left, right, &context(3));
+        let resolution = HunkResolution::Split(vec![
+            HunkChoice::Left.into(),
+            HunkChoice::Right.into(),
+            HunkChoice::Skip.into(),
+        ]);
+
+        // When: Applying it
+        let (new_left, new_right) = apply_hunk_resolutions(left, right, &hunks, &[resolution]);
+
+        // Then: Each piece is resolved on its own
+        assert_eq!(new_left, "a\nB\nz\n");
+        assert_eq!(new_right, "a\nB\nC\nz\n");
+    }
+
+    #[test]
+    fn test_apply_hunk_choices_both() {
+        // Given: Each side appended a different line
+        let left = "a\nleft\n";
+        let right = "a\nright\n";
+        let hunks = extract_hunks(left, right, &context(3));
+
+        // When: Keeping both, in each order
+        let left_first = apply_hunk_choices(
+            left,
+            right,
+            &hunks,
+            &[HunkChoice::Both(BothOrder::LeftFirst)],
+        );
+        let right_first = apply_hunk_choices(
+            left,
+            right,
+            &hunks,
+            &[HunkChoice::Both(BothOrder::RightFirst)],
+        );
+
+        // Then: Both files get the concatenation in the chosen order
+        assert_eq!(left_first.0, "a\nleft\nright\n");
+        assert_eq!(left_first.1, left_first.0);
+        assert_eq!(right_first.0, "a\nright\nleft\n");
+        assert_eq!(right_first.1, right_first.0);
+    }
+
+    #[test]
+    fn test_both_takes_trailing_newline_of_last_side() {
+        // Given: Only the right side ends with a newline
+        let left = "a\nleft";
+        let right = "a\nright\n";
+        let hunks = extract_hunks(left, right, &context(3));
+
+        // When: Keeping both, left first
+        let (new_left, new_right) = apply_hunk_choices(
+            left,
+            right,
+            &hunks,
+            &[HunkChoice::Both(BothOrder::LeftFirst)],
+        );
+
+        // Then: The result ends like the right side, which comes last
+        assert_eq!(new_left, "a\nleft\nright\n");
+        assert_eq!(new_right, new_left);
+    }
+
+    #[test]
+    fn test_apply_edited_resolution() {
+        // Given: A hunk resolved by hand-written lines
+        let left = "a\nold\nz\n";
+        let right = "a\nnew\nz\n";
+        let hunks = extract_hunks(left, right, &context(3));
+        let resolution = HunkResolution::Edited(vec!["mixed".into(), "lines".into()]);
+
+        // When: Applying it
+        let (new_left, new_right) = apply_hunk_resolutions(left, right, &hunks, &[resolution]);
+
+        // Then: Both files get the edited lines
+        assert_eq!(new_left, "a\nmixed\nlines\nz\n");
+        assert_eq!(new_right, new_left);
+    }
+
+    #[test]
+    fn test_apply_hunk_decisions_revised_decision_replaces_earlier() {
+        // Given: Two hunks, first decided left and right
+        let left = "a\nb\nc\nd\n";
+        let right = "a\nB\nc\nD\n";
+        let hunks = extract_hunks(left, right, &context(0));
+        let first = [
+            Some(HunkChoice::Left.into()),
+            Some(HunkChoice::Right.into()),
+        ];
+        let (first_left, _) = apply_hunk_decisions(left, right, &hunks, &first);
+
+        // When: Going back to the first hunk and taking right instead
+        let revised = [
+            Some(HunkChoice::Right.into()),
+            Some(HunkChoice::Right.into()),
+        ];
+        let (new_left, new_right) = apply_hunk_decisions(left, right, &hunks, &revised);
+
+        // Then: The files are merged from the originals with the new decision only
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(first_left, "a\nb\nc\nD\n");
+        assert_eq!(new_left, right);
+        assert_eq!(new_right, right);
+    }
+
+    #[test]
+    fn test_apply_hunk_decisions_leaves_undecided_hunks() {
+        // Given: Two hunks, the first passed over and the second decided left
+        let left = "a\nb\nc\nd\n";
+        let right = "a\nB\nc\nD\n";
+        let hunks = extract_hunks(left, right, &context(0));
+        let decisions = [None, Some(HunkChoice::Left.into())];
+
+        // When: Merging
+        let (new_left, new_right) = apply_hunk_decisions(left, right, &hunks, &decisions);
+
+        // Then: Each file keeps its own first hunk
+        assert_eq!(new_left, left);
+        assert_eq!(new_right, "a\nB\nc\nd\n");
+    }
+
+    #[test]
+    fn test_apply_hunk_decisions_withdrawn_edit_restores_original() {
+        // Given: A hunk first resolved by hand-written lines
+        let left = "a\nold\nz\n";
+        let right = "a\nnew\nz\n";
+        let hunks = extract_hunks(left, right, &context(3));
+        let edited = [Some(HunkResolution::Edited(vec!["mixed".into()]))];
+        let (edited_left, _) = apply_hunk_decisions(left, right, &hunks, &edited);
+
+        // When: The decision is undone, leaving the hunk undecided again
+        let (new_left, new_right) = apply_hunk_decisions(left, right, &hunks, &[None]);
+
+        // Then: Both files are back to their original contents
+        assert_eq!(edited_left, "a\nmixed\nz\n");
+        assert_eq!(new_left, left);
+        assert_eq!(new_right, right);
+    }
+
     #[test]
     fn test_no_difference() {
         // Given: Two identical files
         let content = "line1\nline2\nline3\n";
 
         // When: Extracting hunks
-        let hunks = extract_hunks(content, content, 0);
+        let hunks = extract_hunks(content, content, &context(0));
 
         // Then: No hunks are found
         assert!(hunks.is_empty());
@@ -668,7 +1474,7 @@ mod tests {
         let right = "";
 
         // When: Extracting hunks
-        let hunks = extract_hunks(left, right, 0);
+        let hunks = extract_hunks(left, right, &context(0));
 
         // Then: No hunks are found
         assert!(hunks.is_empty());
@@ -706,4 +1512,112 @@ mod tests {
         // Then: Newline is always added for non-last lines
         assert_eq!(result, "hello\n");
     }
+
+    #[test]
+    fn test_three_way_left_only_change() {
+        // Given: Only the left side changed a line relative to base
+        let base = "a\nb\nc\n";
+        let left = "a\nB\nc\n";
+        let right = "a\nb\nc\n";
+
+        // When: Extracting three-way hunks
+        let hunks = extract_three_way_hunks(base, left, right, &HunkOptions::default());
+
+        // Then: One non-conflicting left change is found
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].kind, ThreeWayKind::LeftChanged);
+        assert_eq!(hunks[0].left_lines, vec!["B"]);
+    }
+
+    #[test]
+    fn test_three_way_non_overlapping_changes_merge() {
+        // Given: Left and right changed different lines
+        let base = "a\nb\nc\nd\ne\n";
+        let left = "A\nb\nc\nd\ne\n";
+        let right = "a\nb\nc\nd\nE\n";
+        let hunks = extract_three_way_hunks(base, left, right, &HunkOptions::default());
+
+        // When: Applying with no conflict choices
+        let (merged_left, merged_right) = apply_three_way_choices(base, left, right, &hunks, &[]);
+
+        // Then: Both changes are taken by both files
+        assert_eq!(hunks.len(), 2);
+        assert!(hunks.iter().all(|h| !h.is_conflict()));
+        assert_eq!(merged_left, "A\nb\nc\nd\nE\n");
+        assert_eq!(merged_right, "A\nb\nc\nd\nE\n");
+    }
+
+    #[test]
+    fn test_three_way_conflict_choices() {
+        // Given: Both sides changed the same line differently
+        let base = "a\nb\nc\n";
+        let left = "a\nleft\nc\n";
+        let right = "a\nright\nc\n";
+        let hunks = extract_three_way_hunks(base, left, right, &HunkOptions::default());
+
+        // When: Resolving the conflict with Right, and separately with Skip
+        let (right_left, right_right) =
+            apply_three_way_choices(base, left, right, &hunks, &[HunkChoice::Right]);
+        let (skip_left, skip_right) =
+            apply_three_way_choices(base, left, right, &hunks, &[HunkChoice::Skip]);
+
+        // Then: The conflict is detected and resolved as chosen
+        assert_eq!(hunks.len(), 1);
+        assert!(hunks[0].is_conflict());
+        assert_eq!(hunks[0].base_lines, vec!["b"]);
+        assert_eq!(right_left, "a\nright\nc\n");
+        assert_eq!(right_right, "a\nright\nc\n");
+        assert_eq!(skip_left, left);
+        assert_eq!(skip_right, right);
+    }
+
+    #[test]
+    fn test_three_way_conflict_keeps_both() {
+        // Given: Both sides appended different lines after the same base
+        let base = "a\n";
+        let left = "a\nleft\n";
+        let right = "a\nright\n";
+        let hunks = extract_three_way_hunks(base, left, right, &HunkOptions::default());
+
+        // When: Keeping both, right first
+        let choice = HunkChoice::Both(BothOrder::RightFirst);
+        let (new_left, new_right) = apply_three_way_choices(base, left, right, &hunks, &[choice]);
+
+        // Then: Both files get both additions
+        assert_eq!(new_left, "a\nright\nleft\n");
+        assert_eq!(new_right, new_left);
+    }
+
+    #[test]
+    fn test_three_way_identical_change_is_not_conflict() {
+        // Given: Both sides made the same change
+        let base = "a\nb\n";
+        let left = "a\nx\n";
+        let right = "a\nx\n";
+
+        // When: Extracting three-way hunks
+        let hunks = extract_three_way_hunks(base, left, right, &HunkOptions::default());
+
+        // Then: The change is classified as BothSame
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].kind, ThreeWayKind::BothSame);
+    }
+
+    #[test]
+    fn test_three_way_positions_track_side_offsets() {
+        // Given: Left inserted lines before a right-only change
+        let base = "a\nb\nc\nd\n";
+        let left = "a\nnew1\nnew2\nb\nc\nd\n";
+        let right = "a\nb\nc\nD\n";
+
+        // When: Extracting three-way hunks
+        let hunks = extract_three_way_hunks(base, left, right, &HunkOptions::default());
+
+        // Then: The right change is reported at its position in each file
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[1].kind, ThreeWayKind::RightChanged);
+        assert_eq!(hunks[1].base_start, 3);
+        assert_eq!(hunks[1].left_start, 5);
+        assert_eq!(hunks[1].right_start, 3);
+    }
 }
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::glob::{path_to_slash, GlobPattern};
+
+/// Ignore files honoured in every directory of both trees
+pub const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".ddmergeignore"];
+
+/// Directory names that are always ignored when ignore files are honoured
+const ALWAYS_IGNORED: &[&str] = &[".git"];
+
+/// Ignore rules collected lazily from the ignore files of one or more trees
+///
+/// Rules found in a directory of any tree apply to the same relative
+/// directory of every tree, so both sides are filtered symmetrically.
+#[derive(Debug)]
+pub struct IgnoreRules {
+    roots: Vec<PathBuf>,
+    rules: HashMap<PathBuf, Vec<GlobPattern>>,
+}
+
+impl IgnoreRules {
+    pub fn new(roots: &[&Path]) -> Self {
+        Self {
+            roots: roots.iter().map(|r| r.to_path_buf()).collect(),
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Rules declared in one relative directory, loaded on first use
+    fn rules_for(&mut self, rel_dir: &Path) -> &[GlobPattern] {
+        if !self.rules.contains_key(rel_dir) {
+            let mut patterns = Vec::new();
+            for root in &self.roots {
+                for name in IGNORE_FILES {
+                    if let Ok(content) = fs::read_to_string(root.join(rel_dir).join(name)) {
+                        patterns.extend(content.lines().filter_map(GlobPattern::parse));
+                    }
+                }
+            }
+            self.rules.insert(rel_dir.to_path_buf(), patterns);
+        }
+        &self.rules[rel_dir]
+    }
+
+    /// Check whether a path relative to the roots is ignored
+    ///
+    /// Rules from deeper directories take precedence, and within a directory
+    /// the last matching rule wins, as in git.
+    pub fn is_ignored(&mut self, rel_path: &Path, is_dir: bool) -> bool {
+        let is_always_ignored = rel_path
+            .file_name()
+            .is_some_and(|name| ALWAYS_IGNORED.iter().any(|n| name == *n));
+        if is_dir && is_always_ignored {
+            return true;
+        }
+
+        let mut ignored = false;
+        let ancestors: Vec<&Path> = rel_path.ancestors().skip(1).collect();
+        for dir in ancestors.into_iter().rev() {
+            let rel_to_dir = path_to_slash(rel_path.strip_prefix(dir).unwrap_or(rel_path));
+            for pattern in self.rules_for(dir) {
+                if pattern.matches(&rel_to_dir, is_dir) {
+                    ignored = !pattern.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // ========================================
+    // IgnoreRules tests
+    // ========================================
+
+    #[test]
+    fn test_rules_from_either_tree_apply() {
+        // Given: An ignore file only in the right tree
+        let left = TempDir::new().unwrap();
+        let right = TempDir::new().unwrap();
+        fs::write(right.path().join(".ddmergeignore"), "*.tmp\n").unwrap();
+        let mut rules = IgnoreRules::new(&[left.path(), right.path()]);
+
+        // When: Checking a path
+
+        // Then: The rule applies regardless of which tree declared it
+        assert!(rules.is_ignored(Path::new("a/file.tmp"), false));
+        assert!(!rules.is_ignored(Path::new("a/file.txt"), false));
+    }
+
+    #[test]
+    fn test_nested_rules_override_parent() {
+        // Given: A root rule ignoring logs and a nested rule re-including one
+        let root = TempDir::new().unwrap();
+        fs::create_dir(root.path().join("sub")).unwrap();
+        fs::write(root.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.path().join("sub/.gitignore"), "!keep.log\n").unwrap();
+        let mut rules = IgnoreRules::new(&[root.path()]);
+
+        // When: Checking paths under both directories
+
+        // Then: The deeper rule wins
+        assert!(rules.is_ignored(Path::new("other.log"), false));
+        assert!(rules.is_ignored(Path::new("sub/other.log"), false));
+        assert!(!rules.is_ignored(Path::new("sub/keep.log"), false));
+    }
+
+    #[test]
+    fn test_git_directory_always_ignored() {
+        // Given: No ignore files at all
+        let root = TempDir::new().unwrap();
+        let mut rules = IgnoreRules::new(&[root.path()]);
+
+        // When: Checking a .git directory
+
+        // Then: It is ignored
+        assert!(rules.is_ignored(Path::new(".git"), true));
+        assert!(rules.is_ignored(Path::new("sub/.git"), true));
+    }
+}
@@ -1,7 +1,28 @@
+mod cache;
 mod directory;
 pub mod file;
+mod filter;
+pub mod glob;
 pub mod hunk;
+pub mod ignore;
+mod parallel;
+mod rename;
+mod whitespace;
 
-pub use directory::{compare_directories, DiffEntry, DiffType};
-pub use file::{compare_files, read_text_file};
-pub use hunk::{apply_hunk_choices, extract_hunks, Hunk, HunkChoice};
+pub use cache::{cache_dir, default_cache_path, HashCache};
+pub use directory::{
+    classify_change, compare_directories, compare_directories_with_options, compare_path,
+    ChangeOrigin, CompareOptions, DiffEntry, DiffType,
+};
+pub use file::{
+    compare_files, compare_files_with_options, compare_metadata, compare_symlinks, read_text_file,
+    MetadataDiff,
+};
+pub use filter::PathFilter;
+pub use hunk::{
+    apply_hunk_choices, apply_hunk_decisions, apply_hunk_resolutions, apply_three_way_choices,
+    extract_hunks, extract_three_way_hunks, split_hunk, BothOrder, DiffAlgorithm, Hunk, HunkChoice,
+    HunkOptions, HunkResolution, ThreeWayHunk, ThreeWayKind,
+};
+pub use rename::{detect_renames, RenameOptions};
+pub use whitespace::WhitespaceOptions;
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Resolve a requested thread count, where 0 means one per available CPU
+pub fn resolve_jobs(jobs: usize) -> usize {
+    if jobs > 0 {
+        return jobs;
+    }
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Apply `f` to every item on up to `jobs` threads, keeping the input order
+///
+/// Items are handed out one at a time from a shared counter, so slow items
+/// (large files) do not hold up a whole chunk of fast ones.
+pub fn map_ordered<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = resolve_jobs(jobs).min(items.len());
+    if jobs <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                let mut local = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    local.push((index, f(item)));
+                }
+                results.lock().unwrap().extend(local);
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_ordered_keeps_input_order() {
+        // Given: More items than threads
+        let items: Vec<usize> = (0..1000).collect();
+
+        // When: Mapping on several threads
+        let results = map_ordered(&items, 4, |n| n * 2);
+
+        // Then: Results are in input order
+        assert_eq!(results, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_map_ordered_single_thread() {
+        // Given: A single job
+        let items = vec!["a", "b"];
+
+        // When: Mapping
+        let results = map_ordered(&items, 1, |s| s.to_uppercase());
+
+        // Then: Every item is mapped
+        assert_eq!(results, vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_resolve_jobs() {
+        // Given/When/Then: An explicit count is kept and 0 means at least one thread
+        assert_eq!(resolve_jobs(3), 3);
+        assert!(resolve_jobs(0) >= 1);
+    }
+}
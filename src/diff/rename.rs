@@ -0,0 +1,469 @@
+use anyhow::Result;
+use similar::TextDiff;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::directory::{compare_moved_directory, CompareOptions, DiffEntry, DiffType, DirListing};
+use super::file::{compare_files, hash_file, read_text_file};
+
+/// Files larger than this are only paired by exact content
+const MAX_SIMILARITY_SIZE: u64 = 1024 * 1024;
+
+/// Options for the rename detection pass
+#[derive(Debug, Clone)]
+pub struct RenameOptions {
+    /// Minimum similarity ratio (0.0 to 1.0) for pairing non-identical files
+    pub min_similarity: f32,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        Self {
+            min_similarity: 0.5,
+        }
+    }
+}
+
+/// Kind of one-sided entry that may take part in a rename (None for symlinks)
+fn rename_candidate(entry: &DiffEntry) -> Option<(DiffType, bool)> {
+    let (is_dir, is_symlink) = match entry.diff_type {
+        DiffType::LeftOnly => (entry.left_is_dir, entry.left_is_symlink),
+        DiffType::RightOnly => (entry.right_is_dir, entry.right_is_symlink),
+        _ => return None,
+    };
+    (!is_symlink).then(|| (entry.diff_type.clone(), is_dir.unwrap_or(false)))
+}
+
+/// Paths of one-sided entries of the given type and kind
+fn candidates(diffs: &[DiffEntry], diff_type: DiffType, is_dir: bool) -> Vec<PathBuf> {
+    diffs
+        .iter()
+        .filter(|d| rename_candidate(d) == Some((diff_type.clone(), is_dir)))
+        .map(|d| d.path.clone())
+        .collect()
+}
+
+/// Pair left-only and right-only files into renames
+///
+/// Directories are paired when their trees are identical under the
+/// comparison options of the scan that found them. Directories left
+/// unpaired are descended into, so files of a moved and edited directory can
+/// still be paired one by one. Files with identical content are paired
+/// next, then the remaining text files are paired greedily by similarity
+/// ratio. Paired entries are replaced by a single `Renamed` entry, and a
+/// directory holding one is split into one-sided entries for the rest;
+/// everything else is returned unchanged.
+pub fn detect_renames(
+    diffs: Vec<DiffEntry>,
+    left: &Path,
+    right: &Path,
+    options: &RenameOptions,
+    compare_options: &CompareOptions,
+) -> Result<Vec<DiffEntry>> {
+    let mut left_only = candidates(&diffs, DiffType::LeftOnly, false);
+    let mut right_only = candidates(&diffs, DiffType::RightOnly, false);
+    let left_dirs = candidates(&diffs, DiffType::LeftOnly, true);
+    let right_dirs = candidates(&diffs, DiffType::RightOnly, true);
+
+    // A side's directories are only read when the other side has something to pair
+    let list = |dirs: &[PathBuf], in_left: bool, others: bool| -> Result<Vec<DirListing>> {
+        if !others {
+            return Ok(Vec::new());
+        }
+        dirs.iter()
+            .map(|dir| DirListing::read(left, right, dir, in_left, compare_options))
+            .collect()
+    };
+    let left_listings = list(
+        &left_dirs,
+        true,
+        !right_only.is_empty() || !right_dirs.is_empty(),
+    )?;
+    let right_listings = list(
+        &right_dirs,
+        false,
+        !left_only.is_empty() || !left_dirs.is_empty(),
+    )?;
+
+    // (from, to, similarity in percent, is_dir)
+    let mut pairs: Vec<(PathBuf, PathBuf, u8, bool)> = Vec::new();
+    let mut paired_left = BTreeSet::new();
+    let mut paired_right = BTreeSet::new();
+
+    // Identical directory trees; only those of the same shape are compared
+    let mut right_by_shape: HashMap<(usize, u64), Vec<&DirListing>> = HashMap::new();
+    for listing in &right_listings {
+        right_by_shape
+            .entry(listing.shape())
+            .or_default()
+            .push(listing);
+    }
+    for from in &left_listings {
+        let Some(candidates) = right_by_shape.get(&from.shape()) else {
+            continue;
+        };
+        for to in candidates {
+            if !paired_right.contains(to.start())
+                && compare_moved_directory(left, right, from, to, compare_options)?
+            {
+                pairs.push((
+                    from.start().to_path_buf(),
+                    to.start().to_path_buf(),
+                    100,
+                    true,
+                ));
+                paired_left.insert(from.start().to_path_buf());
+                paired_right.insert(to.start().to_path_buf());
+                break;
+            }
+        }
+    }
+
+    // Files of the directories left unpaired
+    for listing in left_listings
+        .iter()
+        .filter(|l| !paired_left.contains(l.start()))
+    {
+        left_only.extend(listing.files());
+    }
+    for listing in right_listings
+        .iter()
+        .filter(|l| !paired_right.contains(l.start()))
+    {
+        right_only.extend(listing.files());
+    }
+
+    // Exact content matches; only files of a size found on both sides are hashed
+    let size =
+        |root: &Path, path: &PathBuf| -> Result<u64> { Ok(fs::metadata(root.join(path))?.len()) };
+    let mut left_sizes = BTreeSet::new();
+    for path in &left_only {
+        left_sizes.insert(size(left, path)?);
+    }
+    let mut right_by_hash: HashMap<(u64, u128), Vec<&PathBuf>> = HashMap::new();
+    for path in &right_only {
+        let len = size(right, path)?;
+        if left_sizes.contains(&len) {
+            right_by_hash
+                .entry((len, hash_file(&right.join(path))?))
+                .or_default()
+                .push(path);
+        }
+    }
+    let right_sizes: BTreeSet<u64> = right_by_hash.keys().map(|(len, _)| *len).collect();
+    for from in &left_only {
+        let full = left.join(from);
+        let len = size(left, from)?;
+        if !right_sizes.contains(&len) {
+            continue;
+        }
+        let Some(candidates) = right_by_hash.get(&(len, hash_file(&full)?)) else {
+            continue;
+        };
+        for to in candidates {
+            if !paired_right.contains(*to) && compare_files(&full, &right.join(to))? {
+                pairs.push((from.clone(), (*to).clone(), 100, false));
+                paired_left.insert(from.clone());
+                paired_right.insert((*to).clone());
+                break;
+            }
+        }
+    }
+
+    // Similar text files, best ratio first
+    let read_candidates = |root: &Path, paths: &[PathBuf], paired: &BTreeSet<PathBuf>| {
+        paths
+            .iter()
+            .filter(|p| !paired.contains(*p))
+            .filter(|p| fs::metadata(root.join(p)).is_ok_and(|m| m.len() <= MAX_SIMILARITY_SIZE))
+            .filter_map(|p| match read_text_file(&root.join(p)) {
+                Ok(Some(content)) => Some((p.clone(), content)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let left_texts = read_candidates(left, &left_only, &paired_left);
+    let right_texts = read_candidates(right, &right_only, &paired_right);
+
+    let mut scored = Vec::new();
+    for (from, left_content) in &left_texts {
+        for (to, right_content) in &right_texts {
+            // The ratio cannot exceed the relative size of the smaller file
+            let (small, large) = if left_content.len() < right_content.len() {
+                (left_content.len(), right_content.len())
+            } else {
+                (right_content.len(), left_content.len())
+            };
+            if large > 0 && (2 * small) as f32 / ((small + large) as f32) < options.min_similarity {
+                continue;
+            }
+            let ratio = TextDiff::from_lines(left_content, right_content).ratio();
+            if ratio >= options.min_similarity {
+                scored.push((ratio, from, to));
+            }
+        }
+    }
+    // Stable sort keeps path order for equal ratios, so results are deterministic
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (ratio, from, to) in scored {
+        if paired_left.contains(from) || paired_right.contains(to) {
+            continue;
+        }
+        paired_left.insert(from.clone());
+        paired_right.insert(to.clone());
+        pairs.push((
+            from.clone(),
+            to.clone(),
+            (ratio * 100.0).floor() as u8,
+            false,
+        ));
+    }
+
+    if pairs.is_empty() {
+        return Ok(diffs);
+    }
+
+    // Directories holding paired files give way to entries for the rest
+    let split: HashMap<&Path, &DirListing> = left_listings
+        .iter()
+        .chain(&right_listings)
+        .map(|listing| (listing.start(), listing))
+        .collect();
+    let mut result = Vec::new();
+    for d in diffs {
+        let paired = match d.diff_type {
+            DiffType::LeftOnly => &paired_left,
+            DiffType::RightOnly => &paired_right,
+            _ => {
+                result.push(d);
+                continue;
+            }
+        };
+        if paired.contains(&d.path) {
+            continue;
+        }
+        match split.get(d.path.as_path()) {
+            Some(listing) if paired.iter().any(|p| p.starts_with(&d.path)) => {
+                result.extend(listing.unpaired_entries(&d.diff_type, paired));
+            }
+            _ => result.push(d),
+        }
+    }
+    result.extend(
+        pairs
+            .into_iter()
+            .map(|(from, to, similarity, is_dir)| DiffEntry::renamed(from, to, similarity, is_dir)),
+    );
+    result.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::{compare_directories_with_options, PathFilter};
+    use tempfile::TempDir;
+
+    fn detect(left: &Path, right: &Path) -> Vec<DiffEntry> {
+        detect_with(left, right, &CompareOptions::default())
+    }
+
+    fn detect_with(left: &Path, right: &Path, options: &CompareOptions) -> Vec<DiffEntry> {
+        let diffs = compare_directories_with_options(left, right, options).unwrap();
+        detect_renames(diffs, left, right, &RenameOptions::default(), options).unwrap()
+    }
+
+    #[test]
+    fn test_detect_exact_rename() {
+        // Given: A module directory renamed with identical content
+        let left = TempDir::new().unwrap();
+        let right = TempDir::new().unwrap();
+        fs::create_dir_all(left.path().join("modules/a")).unwrap();
+        fs::create_dir_all(right.path().join("modules/b")).unwrap();
+        fs::write(left.path().join("modules/a/main.tf"), "resource {}\n").unwrap();
+        fs::write(right.path().join("modules/b/main.tf"), "resource {}\n").unwrap();
+
+        // When: Detecting renames
+        let diffs = detect(left.path(), right.path());
+
+        // Then: One Renamed entry replaces the LeftOnly/RightOnly pair
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].diff_type,
+            DiffType::Renamed {
+                from: PathBuf::from("modules/a"),
+                to: PathBuf::from("modules/b"),
+                similarity: 100,
+            }
+        );
+    }
+
+    #[test]
+    fn test_moved_directory_follows_compare_options() {
+        // Given: A moved directory that only differs in an excluded backup file
+        let left = TempDir::new().unwrap();
+        let right = TempDir::new().unwrap();
+        fs::create_dir_all(left.path().join("modules/a")).unwrap();
+        fs::create_dir_all(right.path().join("modules/b")).unwrap();
+        fs::write(left.path().join("modules/a/main.tf"), "resource {}\n").unwrap();
+        fs::write(right.path().join("modules/b/main.tf"), "resource {}\n").unwrap();
+        fs::write(left.path().join("modules/a/main.tf.bak"), "old\n").unwrap();
+        let options = CompareOptions {
+            filter: PathFilter::new().exclude("*.bak").unwrap(),
+            ..CompareOptions::default()
+        };
+
+        // When: Detecting renames with and without the exclude
+        let filtered = detect_with(left.path(), right.path(), &options);
+        let unfiltered = detect(left.path(), right.path());
+
+        // Then: Only the scan excluding the backup pairs the directories,
+        // while the other pairs their files and leaves the backup one-sided
+        assert_eq!(filtered.len(), 1);
+        assert!(matches!(filtered[0].diff_type, DiffType::Renamed { .. }));
+        assert_eq!(filtered[0].left_is_dir, Some(true));
+        assert_eq!(unfiltered.len(), 2);
+        assert_eq!(unfiltered[0].path, PathBuf::from("modules/a/main.tf"));
+        assert_eq!(unfiltered[0].left_is_dir, Some(false));
+        assert_eq!(unfiltered[1].path, PathBuf::from("modules/a/main.tf.bak"));
+        assert_eq!(unfiltered[1].diff_type, DiffType::LeftOnly);
+    }
+
+    #[test]
+    fn test_moved_and_edited_directory_pairs_its_files() {
+        // Given: A module moved from modules/a to modules/b with one value changed
+        let left = TempDir::new().unwrap();
+        let right = TempDir::new().unwrap();
+        fs::create_dir_all(left.path().join("modules/a")).unwrap();
+        fs::create_dir_all(right.path().join("modules/b")).unwrap();
+        let main = "resource \"aws_s3_bucket\" \"this\" {\n  bucket = var.name\n  acl    = \"private\"\n}\n";
+        fs::write(left.path().join("modules/a/main.tf"), main).unwrap();
+        fs::write(
+            right.path().join("modules/b/main.tf"),
+            main.replace("private", "public-read"),
+        )
+        .unwrap();
+        fs::write(
+            left.path().join("modules/a/vars.tf"),
+            "variable \"name\" {}\n",
+        )
+        .unwrap();
+        fs::write(
+            right.path().join("modules/b/vars.tf"),
+            "variable \"name\" {}\n",
+        )
+        .unwrap();
+
+        // When: Detecting renames
+        let diffs = detect(left.path(), right.path());
+
+        // Then: Each file is paired with its moved counterpart and nothing is one-sided
+        assert_eq!(diffs.len(), 2);
+        let renames: Vec<_> = diffs
+            .iter()
+            .map(|d| match &d.diff_type {
+                DiffType::Renamed {
+                    from,
+                    to,
+                    similarity,
+                } => (from.clone(), to.clone(), *similarity),
+                other => panic!("unexpected diff type: {:?}", other),
+            })
+            .collect();
+        assert_eq!(renames[0].0, PathBuf::from("modules/a/main.tf"));
+        assert_eq!(renames[0].1, PathBuf::from("modules/b/main.tf"));
+        assert!(renames[0].2 >= 50 && renames[0].2 < 100);
+        assert_eq!(
+            renames[1],
+            (
+                PathBuf::from("modules/a/vars.tf"),
+                PathBuf::from("modules/b/vars.tf"),
+                100
+            )
+        );
+    }
+
+    #[test]
+    fn test_directory_with_paired_file_is_split() {
+        // Given: A left-only directory where only one nested file moved
+        let left = TempDir::new().unwrap();
+        let right = TempDir::new().unwrap();
+        fs::create_dir_all(left.path().join("old/sub")).unwrap();
+        fs::create_dir_all(left.path().join("old/keep")).unwrap();
+        fs::write(left.path().join("old/sub/moved.txt"), "moved\n").unwrap();
+        fs::write(left.path().join("old/sub/other.txt"), "other\n").unwrap();
+        fs::write(left.path().join("old/keep/file.txt"), "keep\n").unwrap();
+        fs::write(right.path().join("moved.txt"), "moved\n").unwrap();
+
+        // When: Detecting renames
+        let diffs = detect(left.path(), right.path());
+
+        // Then: The rest of the directory is reported by its largest untouched subtrees
+        let entries: Vec<_> = diffs
+            .iter()
+            .map(|d| (d.path.clone(), d.diff_type.clone()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("old/keep"), DiffType::LeftOnly),
+                (
+                    PathBuf::from("old/sub/moved.txt"),
+                    DiffType::Renamed {
+                        from: PathBuf::from("old/sub/moved.txt"),
+                        to: PathBuf::from("moved.txt"),
+                        similarity: 100,
+                    }
+                ),
+                (PathBuf::from("old/sub/other.txt"), DiffType::LeftOnly),
+            ]
+        );
+        assert_eq!(diffs[0].left_is_dir, Some(true));
+    }
+
+    #[test]
+    fn test_detect_similar_rename() {
+        // Given: A renamed file with one line changed
+        let left = TempDir::new().unwrap();
+        let right = TempDir::new().unwrap();
+        fs::write(left.path().join("old.txt"), "a\nb\nc\nd\n").unwrap();
+        fs::write(right.path().join("new.txt"), "a\nb\nc\nD\n").unwrap();
+
+        // When: Detecting renames
+        let diffs = detect(left.path(), right.path());
+
+        // Then: The pair is reported with its similarity
+        assert_eq!(diffs.len(), 1);
+        match &diffs[0].diff_type {
+            DiffType::Renamed {
+                from,
+                to,
+                similarity,
+            } => {
+                assert_eq!(from, &PathBuf::from("old.txt"));
+                assert_eq!(to, &PathBuf::from("new.txt"));
+                assert!(*similarity >= 50 && *similarity < 100);
+            }
+            other => panic!("unexpected diff type: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unrelated_files_are_not_paired() {
+        // Given: A left-only and a right-only file with unrelated content
+        let left = TempDir::new().unwrap();
+        let right = TempDir::new().unwrap();
+        fs::write(left.path().join("a.txt"), "one\ntwo\n").unwrap();
+        fs::write(right.path().join("b.txt"), "three\nfour\n").unwrap();
+
+        // When: Detecting renames
+        let diffs = detect(left.path(), right.path());
+
+        // Then: Both entries are kept as they are
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].diff_type, DiffType::LeftOnly);
+        assert_eq!(diffs[1].diff_type, DiffType::RightOnly);
+    }
+}
@@ -0,0 +1,148 @@
+/// Differences in whitespace and line endings that comparisons ignore
+///
+/// Lines are compared through `line_key`, so two lines with the same key
+/// count as equal. Files that differ only in ignored ways are not reported,
+/// and hunks never contain such lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WhitespaceOptions {
+    /// Treat runs of whitespace as one space and ignore trailing whitespace
+    pub ignore_space_change: bool,
+    /// Ignore all whitespace within lines
+    pub ignore_all_space: bool,
+    /// Ignore lines that are empty or hold only whitespace
+    pub ignore_blank_lines: bool,
+    /// Treat CRLF line endings as LF
+    pub ignore_eol: bool,
+}
+
+impl WhitespaceOptions {
+    /// Whether any difference is ignored
+    pub fn is_active(&self) -> bool {
+        self.ignore_space_change
+            || self.ignore_all_space
+            || self.ignore_blank_lines
+            || self.ignore_eol
+    }
+
+    /// What a line (with its newline, if any) is compared by
+    ///
+    /// A missing newline at the end of a file still counts as a difference.
+    pub fn line_key(&self, line: &str) -> String {
+        let (mut body, newline) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (line, ""),
+        };
+        if self.ignore_eol {
+            body = body.strip_suffix('\r').unwrap_or(body);
+        }
+        let body = if self.ignore_all_space {
+            body.chars().filter(|c| !c.is_whitespace()).collect()
+        } else if self.ignore_space_change {
+            // Like `diff -b`: indentation still differs from none at all
+            let mut collapsed = String::new();
+            for c in body.trim_end().chars() {
+                if !c.is_whitespace() {
+                    collapsed.push(c);
+                } else if !collapsed.ends_with(' ') {
+                    collapsed.push(' ');
+                }
+            }
+            collapsed
+        } else {
+            body.to_string()
+        };
+        format!("{}{}", body, newline)
+    }
+
+    /// Whether a line is left out when blank lines are ignored
+    pub fn is_ignored_line(&self, line: &str) -> bool {
+        self.ignore_blank_lines && line.trim().is_empty()
+    }
+
+    /// Keys of the lines of a text that take part in a comparison
+    pub fn compared_lines(&self, content: &str) -> Vec<String> {
+        content
+            .split_inclusive('\n')
+            .filter(|line| !self.is_ignored_line(line))
+            .map(|line| self.line_key(line))
+            .collect()
+    }
+
+    /// Whether two texts are equal once ignored differences are left out
+    pub fn texts_equal(&self, left: &str, right: &str) -> bool {
+        self.compared_lines(left) == self.compared_lines(right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inactive_options_keep_lines() {
+        // Given/When/Then: Without options a line is its own key
+        let options = WhitespaceOptions::default();
+        assert!(!options.is_active());
+        assert_eq!(options.line_key("  a \tb\r\n"), "  a \tb\r\n");
+        assert!(!options.texts_equal("a\n", "a \n"));
+    }
+
+    #[test]
+    fn test_ignore_space_change() {
+        // Given: Space changes ignored
+        let options = WhitespaceOptions {
+            ignore_space_change: true,
+            ..Default::default()
+        };
+
+        // When/Then: Runs of whitespace and trailing whitespace do not count,
+        // but whitespace where there was none does
+        assert_eq!(options.line_key("  a \t b  \n"), " a b\n");
+        assert!(options.texts_equal("x  =  1\n", "x = 1   \n"));
+        assert!(!options.texts_equal("x=1\n", "x = 1\n"));
+        assert!(!options.texts_equal("a\n", "  a\n"));
+    }
+
+    #[test]
+    fn test_ignore_all_space() {
+        // Given: All whitespace ignored
+        let options = WhitespaceOptions {
+            ignore_all_space: true,
+            ..Default::default()
+        };
+
+        // When/Then: Lines equal without their whitespace match
+        assert_eq!(options.line_key("\tx = 1 \n"), "x=1\n");
+        assert!(options.texts_equal("x=1\n", "  x = 1\n"));
+        assert!(!options.texts_equal("x=1\n", "x=2\n"));
+    }
+
+    #[test]
+    fn test_ignore_blank_lines() {
+        // Given: Blank lines ignored
+        let options = WhitespaceOptions {
+            ignore_blank_lines: true,
+            ..Default::default()
+        };
+
+        // When/Then: Added or removed blank lines do not count
+        assert!(options.is_ignored_line("  \n"));
+        assert!(!options.is_ignored_line("a\n"));
+        assert!(options.texts_equal("a\n\nb\n", "a\nb\n\n\n"));
+        assert!(!options.texts_equal("a\nb\n", "a\nc\n"));
+    }
+
+    #[test]
+    fn test_ignore_eol() {
+        // Given: Line endings ignored
+        let options = WhitespaceOptions {
+            ignore_eol: true,
+            ..Default::default()
+        };
+
+        // When/Then: CRLF matches LF, but a missing final newline still differs
+        assert!(options.texts_equal("a\r\nb\r\n", "a\nb\n"));
+        assert!(!options.texts_equal("a\n", "a"));
+        assert!(!options.texts_equal("a \n", "a\n"));
+    }
+}
@@ -1,13 +1,33 @@
+mod tui;
+
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use colored::Colorize;
-use regex::Regex;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use ddmerge::diff::file::read_text_file;
-use ddmerge::diff::{compare_directories, extract_hunks, DiffType};
-use ddmerge::merge::{apply_file_action, apply_hunk_merge, FileAction};
-use ddmerge::ui::{display_hunk, prompt_for_hunk_choice, HunkUserChoice};
+use ddmerge::diff::{
+    apply_hunk_decisions, apply_three_way_choices, classify_change,
+    compare_directories_with_options, compare_path, default_cache_path, detect_renames,
+    extract_hunks, extract_three_way_hunks, split_hunk, BothOrder, ChangeOrigin, CompareOptions,
+    DiffAlgorithm, DiffEntry, DiffType, HashCache, Hunk, HunkChoice, HunkOptions, HunkResolution,
+    PathFilter, RenameOptions, WhitespaceOptions,
+};
+use ddmerge::merge::{
+    apply_entry_choice, apply_file_action, apply_hunk_merge, apply_metadata_choice, apply_rename,
+    check_unchanged, choose_side, default_journal_dir, default_session_path, entry_fingerprint,
+    hunk_fingerprint, three_way_fingerprint, undo_last_session, undo_operations, ChangedOnDisk,
+    Choice, Decision, DecisionKind, FileAction, FileState, Journal, Lookup, Policy, Prefer,
+    Recorder, Replay, RuleAction, RuleSet, SessionLog, SessionState,
+};
+use ddmerge::ui::{
+    display_hunk, display_three_way_hunk, edit_hunk, is_whitespace_only_diff,
+    prompt_for_hunk_choice, Highlighter, HunkUserChoice, Layout, Screen, Theme,
+};
+
+/// Exit code when differences were left unresolved by the policy flags
+const EXIT_UNRESOLVED: i32 = 3;
 
 /// Interactive directory diff and merge tool
 ///
@@ -16,12 +36,18 @@ use ddmerge::ui::{display_hunk, prompt_for_hunk_choice, HunkUserChoice};
 #[derive(Parser, Debug)]
 #[command(name = "ddmerge")]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Left directory to compare
-    left: PathBuf,
+    #[arg(required = true)]
+    left: Option<PathBuf>,
 
     /// Right directory to compare
-    right: PathBuf,
+    #[arg(required = true)]
+    right: Option<PathBuf>,
 
     /// Dry run mode (show what would be done without making changes)
     #[arg(long)]
@@ -31,13 +57,313 @@ struct Args {
     #[arg(long)]
     skip_binary: bool,
 
-    /// Skip files in left directory matching this regex pattern
-    #[arg(long)]
+    /// Only compare files matching this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Skip paths matching this glob on both sides (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Read exclude globs from a file (one per line, gitignore syntax)
+    #[arg(long, value_name = "FILE")]
+    exclude_from: Vec<PathBuf>,
+
+    /// Advanced: skip files in left directory whose path matches this regex
+    #[arg(long, value_name = "REGEX")]
     exclude_regex_left: Option<String>,
 
-    /// Skip files in right directory matching this regex pattern
-    #[arg(long)]
+    /// Advanced: skip files in right directory whose path matches this regex
+    #[arg(long, value_name = "REGEX")]
     exclude_regex_right: Option<String>,
+
+    /// Resolve modified files, hunks, symlinks, metadata and renames without prompting
+    /// by taking this side: left, right, newer or larger
+    #[arg(long, value_name = "SIDE")]
+    prefer: Option<Prefer>,
+
+    /// Action for entries only in the left directory without prompting: copy, delete or skip
+    #[arg(long, value_name = "ACTION")]
+    on_left_only: Option<FileAction>,
+
+    /// Action for entries only in the right directory without prompting: copy, delete or skip
+    #[arg(long, value_name = "ACTION")]
+    on_right_only: Option<FileAction>,
+
+    /// Side to take when entry types differ: left, right, newer or larger (default: --prefer)
+    #[arg(long, value_name = "SIDE")]
+    on_type_mismatch: Option<Prefer>,
+
+    /// Config file whose [[rule]] tables resolve matching paths without prompting
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Write every decision to this file so it can be re-applied with --replay
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Re-apply decisions written by --record; changed content is reported and prompted
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Session file recording progress for --resume (default: under the cache directory)
+    #[arg(long, value_name = "FILE")]
+    session: Option<PathBuf>,
+
+    /// Continue an interrupted merge from the next undecided entry
+    #[arg(long)]
+    resume: bool,
+
+    /// With --resume, ask again about entries and hunks skipped in the previous session
+    #[arg(long, requires = "resume")]
+    revisit_skipped: bool,
+
+    /// Common ancestor directory for a three-way merge
+    /// (changes made on only one side are applied without prompting)
+    #[arg(long, value_name = "DIR")]
+    base: Option<PathBuf>,
+
+    /// Follow symlinks, comparing and copying their targets' contents instead of the links themselves
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Do not report differences in permission bits
+    #[arg(long)]
+    ignore_mode: bool,
+
+    /// Also report differences in file owner (uid/gid)
+    #[arg(long)]
+    compare_owner: bool,
+
+    /// Also report differences in modification time
+    #[arg(long)]
+    compare_mtime: bool,
+
+    /// Do not honour .gitignore, .ignore and .ddmergeignore files
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// Treat files with equal size and modification time as identical (like rsync's quick check)
+    #[arg(long)]
+    trust_mtime: bool,
+
+    /// Directory keeping backups of changed files for undo (default: ~/.cache/ddmerge/journal)
+    #[arg(long, value_name = "DIR", global = true)]
+    journal: Option<PathBuf>,
+
+    /// Do not keep backups of changed files (the changes cannot be undone)
+    #[arg(long, conflicts_with = "journal")]
+    no_journal: bool,
+
+    /// Hash cache file reused across runs (default: ~/.cache/ddmerge/hashes)
+    #[arg(long, value_name = "PATH", global = true)]
+    cache: Option<PathBuf>,
+
+    /// Do not read or update the hash cache
+    #[arg(long, conflicts_with = "cache")]
+    no_cache: bool,
+
+    /// Number of threads for scanning and comparing (0 means one per CPU)
+    #[arg(short, long, value_name = "N", default_value_t = 0)]
+    jobs: usize,
+
+    /// Do not pair left-only and right-only files into renames
+    #[arg(long)]
+    no_renames: bool,
+
+    /// Minimum similarity in percent for pairing non-identical files as a rename
+    #[arg(long, value_name = "PERCENT", default_value_t = 50,
+          value_parser = clap::value_parser!(u8).range(0..=100))]
+    rename_threshold: u8,
+
+    /// How lines are matched when looking for hunks: myers, patience or lcs
+    #[arg(long, value_name = "ALGORITHM", default_value = "myers")]
+    diff_algorithm: DiffAlgorithm,
+
+    /// Number of unchanged lines shown around each hunk
+    #[arg(short = 'U', long, value_name = "N", default_value_t = 3)]
+    context: usize,
+
+    /// Treat runs of whitespace as one space and ignore trailing whitespace
+    #[arg(long)]
+    ignore_space_change: bool,
+
+    /// Ignore all whitespace within lines
+    #[arg(long)]
+    ignore_all_space: bool,
+
+    /// Ignore added or removed blank lines
+    #[arg(long)]
+    ignore_blank_lines: bool,
+
+    /// Treat CRLF line endings as LF
+    #[arg(long)]
+    ignore_eol: bool,
+
+    /// How hunks are shown: unified or side-by-side
+    #[arg(long, value_name = "LAYOUT", default_value = "unified")]
+    layout: Layout,
+
+    /// Show hunks without syntax highlighting
+    #[arg(long)]
+    no_syntax: bool,
+
+    /// Colours for syntax highlighting: dark or light
+    #[arg(
+        long,
+        value_name = "THEME",
+        default_value = "dark",
+        conflicts_with = "no_syntax"
+    )]
+    theme: Theme,
+
+    /// Full-screen interface with a file tree and hunk pane (prompts are used without a terminal)
+    #[arg(long, conflicts_with_all = ["prefer", "on_left_only", "on_right_only",
+          "on_type_mismatch", "record", "replay", "base"])]
+    tui: bool,
+}
+
+/// Maintenance commands run instead of a merge
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Remove cache entries for files that were deleted or changed
+    PruneCache,
+    /// Revert changes applied by previous merges, newest first
+    Undo {
+        /// Number of operations to revert
+        #[arg(default_value_t = 1)]
+        count: usize,
+
+        /// Revert every operation of the most recent merge session
+        #[arg(long, conflicts_with = "count")]
+        last_session: bool,
+    },
+}
+
+impl Args {
+    /// Left directory (always present when no subcommand is given)
+    fn left(&self) -> &Path {
+        self.left.as_deref().expect("left directory is required")
+    }
+
+    /// Right directory (always present when no subcommand is given)
+    fn right(&self) -> &Path {
+        self.right.as_deref().expect("right directory is required")
+    }
+
+    /// Decisions configured by the policy flags
+    fn policy(&self) -> Policy {
+        Policy {
+            prefer: self.prefer,
+            on_left_only: self.on_left_only,
+            on_right_only: self.on_right_only,
+            on_type_mismatch: self.on_type_mismatch,
+        }
+    }
+
+    /// How files are split into hunks
+    fn hunk_options(&self) -> HunkOptions {
+        HunkOptions {
+            context_lines: self.context,
+            algorithm: self.diff_algorithm,
+            whitespace: self.whitespace(),
+        }
+    }
+
+    /// Whitespace differences left out of comparisons and hunks
+    fn whitespace(&self) -> WhitespaceOptions {
+        WhitespaceOptions {
+            ignore_space_change: self.ignore_space_change,
+            ignore_all_space: self.ignore_all_space,
+            ignore_blank_lines: self.ignore_blank_lines,
+            ignore_eol: self.ignore_eol,
+        }
+    }
+
+    /// Syntax highlighting for a file, or None when disabled or not supported
+    fn highlighter(&self, path: &Path) -> Option<Highlighter> {
+        if self.no_syntax {
+            return None;
+        }
+        Highlighter::for_path(path, self.theme)
+    }
+
+    /// Location of the hash cache, or None when caching is disabled
+    fn cache_path(&self) -> Option<PathBuf> {
+        if self.no_cache {
+            return None;
+        }
+        self.cache.clone().or_else(default_cache_path)
+    }
+
+    /// Location of the undo journal, or None when journaling is disabled
+    fn journal_dir(&self) -> Option<PathBuf> {
+        if self.no_journal {
+            return None;
+        }
+        self.journal.clone().or_else(default_journal_dir)
+    }
+
+    /// Location of the session file, or None when there is nowhere to keep it
+    fn session_path(&self) -> Result<Option<PathBuf>> {
+        match &self.session {
+            Some(path) => Ok(Some(path.clone())),
+            None => default_session_path(self.left(), self.right()),
+        }
+    }
+}
+
+/// Remove stale entries from the hash cache
+fn prune_cache(args: &Args) -> Result<()> {
+    let Some(path) = args.cache_path() else {
+        anyhow::bail!("No cache location; pass --cache <PATH>");
+    };
+    let cache = HashCache::load(&path)?;
+    let removed = cache.prune();
+    if cache.is_dirty() {
+        cache.save(&path)?;
+    }
+    println!(
+        "{}",
+        format!(
+            "Pruned {} stale entry(ies); {} remain in {}",
+            removed,
+            cache.len(),
+            path.display()
+        )
+        .green()
+    );
+    Ok(())
+}
+
+/// Revert journaled operations
+fn undo(args: &Args, count: usize, last_session: bool) -> Result<()> {
+    let Some(dir) = args.journal_dir() else {
+        anyhow::bail!("No journal location; pass --journal <DIR>");
+    };
+    let undone = if last_session {
+        undo_last_session(&dir)?
+    } else {
+        undo_operations(&dir, count)?
+    };
+    if undone.is_empty() {
+        println!("{}", "Nothing to undo.".yellow());
+    }
+    for description in &undone {
+        println!("{} {}", "Undid:".green(), description);
+    }
+    Ok(())
+}
+
+/// Load the hash cache, falling back to no cache if it cannot be read
+fn load_cache(path: &Path) -> Option<Arc<HashCache>> {
+    match HashCache::load(path) {
+        Ok(cache) => Some(Arc::new(cache)),
+        Err(e) => {
+            println!("{} {:#} (continuing without cache)", "Warning:".yellow(), e);
+            None
+        }
+    }
 }
 
 /// Check if a file is binary by reading the first few bytes
@@ -60,402 +386,1556 @@ fn is_binary_file(path: &Path) -> bool {
     buffer[..bytes_read].contains(&0)
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+/// Format a symlink's target for display
+fn read_link_display(path: &Path) -> String {
+    std::fs::read_link(path)
+        .map(|target| target.display().to_string())
+        .unwrap_or_else(|_| "?".to_string())
+}
 
-    // Validate input directories
-    if !args.left.is_dir() {
-        anyhow::bail!("Left path is not a directory: {}", args.left.display());
-    }
-    if !args.right.is_dir() {
-        anyhow::bail!("Right path is not a directory: {}", args.right.display());
-    }
-
-    // Compile regex patterns
-    let exclude_left_regex = args
-        .exclude_regex_left
-        .as_ref()
-        .map(|p| Regex::new(p))
-        .transpose()
-        .context("Invalid regex pattern for --exclude-regex-left")?;
-    let exclude_right_regex = args
-        .exclude_regex_right
-        .as_ref()
-        .map(|p| Regex::new(p))
-        .transpose()
-        .context("Invalid regex pattern for --exclude-regex-right")?;
+/// Counters reported in the summary
+#[derive(Debug, Default)]
+struct Stats {
+    total_hunks: usize,
+    left_choices: usize,
+    right_choices: usize,
+    skip_choices: usize,
+    both_choices: usize,
+    edited: usize,
+    auto_resolved: usize,
+    rule_resolved: usize,
+    replayed: usize,
+    replay_mismatches: usize,
+    previously_done: usize,
+    undone: usize,
+    unresolved: usize,
+}
 
-    println!("{}", "Comparing directories...".cyan());
-    let diffs =
-        compare_directories(&args.left, &args.right).context("Failed to compare directories")?;
+impl Stats {
+    fn record(&mut self, choice: HunkChoice) {
+        match choice {
+            HunkChoice::Left => self.left_choices += 1,
+            HunkChoice::Right => self.right_choices += 1,
+            HunkChoice::Skip => self.skip_choices += 1,
+            HunkChoice::Both(_) => self.both_choices += 1,
+        }
+    }
 
-    if diffs.is_empty() {
-        println!("{}", "Directories are identical!".green());
-        return Ok(());
+    /// Count a hunk resolution; each piece of a split hunk counts on its own
+    fn record_resolution(&mut self, resolution: &HunkResolution) {
+        match resolution {
+            HunkResolution::Choice(choice) => self.record(*choice),
+            HunkResolution::Edited(_) => self.edited += 1,
+            HunkResolution::Split(parts) => {
+                for part in parts {
+                    self.record_resolution(part);
+                }
+            }
+        }
     }
+}
 
-    println!(
-        "{}",
-        format!("Found {} file(s) with differences.", diffs.len()).yellow()
-    );
+/// Outcome of asking about one hunk of a two-way merge
+enum HunkAnswer {
+    Resolved(HunkResolution),
+    /// Resolve whitespace-only hunks to one side from now on
+    AllWhitespace(HunkChoice),
+    /// Go to the previous hunk of the file
+    Previous,
+    /// Go to the next hunk, leaving this one as it is
+    Next,
+    SkipFile,
+    Undo,
+    Quit,
+}
 
-    let mut total_hunks = 0;
-    let mut left_choices = 0;
-    let mut right_choices = 0;
-    let mut skip_choices = 0;
-    let mut quit = false;
+/// Decisions for every hunk of a file, undecided ones skipped
+fn resolved(choices: &[Option<HunkResolution>]) -> Vec<HunkResolution> {
+    choices
+        .iter()
+        .map(|choice| choice.clone().unwrap_or(HunkChoice::Skip.into()))
+        .collect()
+}
+
+/// Short description of a hunk decision, shown when it is revisited
+fn describe(resolution: &HunkResolution) -> &'static str {
+    match resolution {
+        HunkResolution::Choice(HunkChoice::Left) => "left",
+        HunkResolution::Choice(HunkChoice::Right) => "right",
+        HunkResolution::Choice(HunkChoice::Skip) => "skip",
+        HunkResolution::Choice(HunkChoice::Both(BothOrder::LeftFirst)) => "both, left first",
+        HunkResolution::Choice(HunkChoice::Both(BothOrder::RightFirst)) => "both, right first",
+        HunkResolution::Edited(_) => "edited",
+        HunkResolution::Split(_) => "split",
+    }
+}
+
+/// Whether the session goes on with the next entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// Read one answer from stdin, lowercased and trimmed
+fn read_answer() -> Result<String> {
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_lowercase())
+}
+
+/// State of one interactive merge run
+struct Session<'a> {
+    args: &'a Args,
+    compare_options: &'a CompareOptions,
+    policy: Policy,
+    rules: RuleSet,
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+    /// Progress of the current run, kept for --resume
+    log: Option<SessionLog>,
+    /// Progress of the interrupted run being resumed
+    resumed: Option<SessionState>,
+    /// Backups of everything changed in this run, for undo
+    journal: Option<Journal>,
+    /// Side taken for every whitespace-only hunk, once asked for
+    whitespace_choice: Option<HunkChoice>,
+    stats: Stats,
+}
 
-    for diff in &diffs {
-        if quit {
-            break;
-        }
-
-        let path_str = diff.path.to_string_lossy();
-
-        // Check regex exclusions based on diff type
-        let should_exclude = match &diff.diff_type {
-            DiffType::LeftOnly => exclude_left_regex
-                .as_ref()
-                .is_some_and(|re| re.is_match(&path_str)),
-            DiffType::RightOnly => exclude_right_regex
-                .as_ref()
-                .is_some_and(|re| re.is_match(&path_str)),
-            DiffType::Modified | DiffType::TypeMismatch => {
-                exclude_left_regex
-                    .as_ref()
-                    .is_some_and(|re| re.is_match(&path_str))
-                    || exclude_right_regex
-                        .as_ref()
-                        .is_some_and(|re| re.is_match(&path_str))
+impl<'a> Session<'a> {
+    /// Start a session; decision files and the journal are attached by the caller
+    fn new(args: &'a Args, compare_options: &'a CompareOptions, rules: RuleSet) -> Self {
+        Self {
+            args,
+            compare_options,
+            policy: args.policy(),
+            rules,
+            recorder: None,
+            replay: None,
+            log: None,
+            resumed: None,
+            journal: None,
+            whitespace_choice: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Look up a decision to replay, reporting ones recorded for other content
+    fn replayed(&mut self, kind: DecisionKind, path: &Path, fingerprint: u64) -> Option<Choice> {
+        let lookup = self.replay.as_mut()?.take(kind, path, fingerprint);
+        match lookup {
+            Lookup::Found(choice) => {
+                println!("{}", format!("  Replaying: {}", choice).green());
+                self.stats.replayed += 1;
+                Some(choice)
+            }
+            Lookup::Mismatch => {
+                println!(
+                    "{}",
+                    "  Recorded decision does not match the current content".yellow()
+                );
+                self.stats.replay_mismatches += 1;
+                None
             }
+            Lookup::Missing => None,
+        }
+    }
+
+    /// Append a decision to the --record file
+    fn record(
+        &mut self,
+        kind: DecisionKind,
+        path: &Path,
+        fingerprint: u64,
+        choice: impl Into<Choice>,
+    ) -> Result<()> {
+        let choice = choice.into();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&Decision::new(choice, kind, fingerprint, path))?;
+        }
+        if let Some(log) = &mut self.log {
+            if kind == DecisionKind::Hunk && choice == Choice::Skip {
+                log.skipped_hunk(path, fingerprint)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Back up the paths an operation is about to change, so it can be undone
+    fn journal(&mut self, description: String, paths: &[PathBuf]) -> Result<()> {
+        match &mut self.journal {
+            Some(journal) if !self.args.dry_run => journal.record(&description, paths),
+            _ => Ok(()),
+        }
+    }
+
+    /// Both sides of an entry, including both names of a rename
+    fn entry_paths(&self, diff: &DiffEntry) -> Vec<PathBuf> {
+        let names = match &diff.diff_type {
+            DiffType::Renamed { from, to, .. } => vec![from, to],
+            _ => vec![&diff.path],
         };
+        names
+            .into_iter()
+            .flat_map(|name| [self.args.left().join(name), self.args.right().join(name)])
+            .collect()
+    }
 
-        if should_exclude {
-            continue;
+    /// Revert the last change applied in this run (the `u` key)
+    ///
+    /// Returns whether there was anything to undo.
+    fn undo_last(&mut self) -> Result<bool> {
+        let undone = match &mut self.journal {
+            Some(journal) => journal.undo_last()?,
+            None => None,
+        };
+        match undone {
+            Some(description) => {
+                println!("{} {}", "  Undid:".green(), description);
+                self.stats.undone += 1;
+                Ok(true)
+            }
+            None => {
+                println!("{}", "  Nothing to undo".yellow());
+                Ok(false)
+            }
         }
+    }
 
-        let left_path = args.left.join(&diff.path);
-        let right_path = args.right.join(&diff.path);
+    /// Whether a hunk was skipped in the resumed session and is not asked again
+    fn skipped_before(&self, path: &Path, fingerprint: u64) -> bool {
+        let skipped = self
+            .resumed
+            .as_ref()
+            .is_some_and(|state| state.is_skipped_hunk(path, fingerprint));
+        if skipped {
+            println!("{}", "  Skipped in the previous session".yellow());
+        }
+        skipped
+    }
 
-        match &diff.diff_type {
-            DiffType::LeftOnly => {
-                // Check for binary file
-                if args.skip_binary && is_binary_file(&left_path) {
-                    continue;
+    /// Fingerprint of a whole entry, only computed when decisions are recorded or replayed
+    fn entry_fingerprint(&self, left_path: &Path, right_path: &Path) -> Result<u64> {
+        if self.recorder.is_none() && self.replay.is_none() {
+            return Ok(0);
+        }
+        entry_fingerprint(left_path, right_path)
+    }
+
+    /// Policy decision for the hunks of a file, asked for once per file
+    fn hunk_policy_choice(
+        &mut self,
+        decided: &mut Option<HunkChoice>,
+        left_path: &Path,
+        right_path: &Path,
+    ) -> Result<HunkChoice> {
+        if let Some(choice) = *decided {
+            return Ok(choice);
+        }
+        let choice = self
+            .decide(&DiffType::Modified, left_path, right_path)?
+            .unwrap_or(HunkChoice::Skip);
+        *decided = Some(choice);
+        Ok(choice)
+    }
+
+    /// Decide a two-sided entry from the policy, or None to prompt
+    ///
+    /// With an active policy nothing is prompted: an entry the policy
+    /// cannot decide is counted as unresolved and `Skip` is returned.
+    fn decide(
+        &mut self,
+        diff_type: &DiffType,
+        left_path: &Path,
+        right_path: &Path,
+    ) -> Result<Option<HunkChoice>> {
+        if !self.policy.is_active() {
+            return Ok(None);
+        }
+        let choice = match self.policy.preference(diff_type) {
+            Some(prefer) => choose_side(prefer, left_path, right_path)?,
+            None => HunkChoice::Skip,
+        };
+        match choice {
+            HunkChoice::Left => println!("{}", "  Using left by policy".green()),
+            HunkChoice::Right => println!("{}", "  Using right by policy".green()),
+            // Policies never keep both versions
+            HunkChoice::Skip | HunkChoice::Both(_) => self.mark_unresolved(),
+        }
+        Ok(Some(choice))
+    }
+
+    fn mark_unresolved(&mut self) {
+        println!("{}", "  Unresolved (no policy decision)".yellow());
+        self.stats.unresolved += 1;
+    }
+
+    /// Handle one diff entry, unless the resumed session already did
+    fn process(&mut self, diff: &DiffEntry) -> Result<Flow> {
+        if let Some(state) = &self.resumed {
+            if state.is_done(&diff.path) {
+                self.stats.previously_done += 1;
+                return Ok(Flow::Continue);
+            }
+        }
+        let mut diff = diff.clone();
+        let flow = loop {
+            match self.process_entry(&diff) {
+                Err(e) => match e.downcast_ref::<ChangedOnDisk>() {
+                    Some(changed) => match self.rediff(&diff, changed)? {
+                        Some(entry) => diff = entry,
+                        None => return Ok(Flow::Continue),
+                    },
+                    None => return Err(e),
+                },
+                result => break result?,
+            }
+        };
+        if flow == Flow::Continue {
+            if let Some(log) = &mut self.log {
+                log.done(&diff.path)?;
+            }
+        }
+        Ok(flow)
+    }
+
+    /// Offer to compare an entry again after a write was refused because
+    /// another program changed it
+    ///
+    /// Returns the entry as it is now, or None to leave it.
+    fn rediff(&mut self, diff: &DiffEntry, changed: &ChangedOnDisk) -> Result<Option<DiffEntry>> {
+        println!("{} {}", "  Error:".red().bold(), changed);
+        if self.policy.is_active() {
+            self.mark_unresolved();
+            return Ok(None);
+        }
+        print!(
+            "  Re-diff {}? {}es / {}o, leave it > ",
+            diff.path.display(),
+            "(y)".green().bold(),
+            "(n)".yellow().bold()
+        );
+        std::io::Write::flush(&mut std::io::stdout()).unwrap();
+        loop {
+            match read_answer()?.as_str() {
+                "y" => break,
+                "n" => return Ok(None),
+                _ => {}
+            }
+        }
+
+        // A rename is handled again as is, since its contents are read anew
+        if matches!(diff.diff_type, DiffType::Renamed { .. }) {
+            return Ok(Some(diff.clone()));
+        }
+        let args = self.args;
+        let entry = compare_path(args.left(), args.right(), &diff.path, self.compare_options)?;
+        if entry.is_none() {
+            println!("{}", "  No differences left".green());
+        }
+        Ok(entry)
+    }
+
+    fn process_entry(&mut self, diff: &DiffEntry) -> Result<Flow> {
+        if self.apply_rule(diff)? {
+            return Ok(Flow::Continue);
+        }
+
+        // With a base directory, one-sided changes are applied without prompting
+        if let Some(base) = &self.args.base {
+            if !matches!(diff.diff_type, DiffType::Renamed { .. }) {
+                if let Some(flow) = self.handle_three_way(diff, base)? {
+                    return Ok(flow);
                 }
+            }
+        }
 
-                println!();
-                println!(
-                    "{} {} (only in left)",
-                    "File:".cyan().bold(),
-                    diff.path.display()
-                );
-                print!(
-                    "  Choose: {}opy to right / {}elete from left / {}kip / {}uit > ",
-                    "(c)".cyan().bold(),
-                    "(d)".red().bold(),
-                    "(s)".yellow().bold(),
-                    "(q)".magenta().bold()
-                );
-                std::io::Write::flush(&mut std::io::stdout()).unwrap();
-
-                loop {
-                    let mut input = String::new();
-                    std::io::stdin().read_line(&mut input)?;
-                    match input.trim().to_lowercase().as_str() {
-                        "c" => {
-                            println!("{}", "  Copying to right...".green());
-                            if !args.dry_run {
-                                apply_file_action(diff, FileAction::Copy, &args.left, &args.right)?;
-                            }
-                            break;
-                        }
-                        "d" => {
-                            println!("{}", "  Deleting from left...".red());
-                            if !args.dry_run {
-                                apply_file_action(
-                                    diff,
-                                    FileAction::Delete,
-                                    &args.left,
-                                    &args.right,
-                                )?;
-                            }
-                            break;
-                        }
-                        "s" => {
-                            println!("{}", "  Skipped".yellow());
-                            skip_choices += 1;
-                            break;
-                        }
-                        "q" => {
-                            println!("{}", "  Quitting...".red());
-                            quit = true;
-                            break;
-                        }
-                        _ => {}
+        match &diff.diff_type {
+            DiffType::LeftOnly | DiffType::RightOnly => self.handle_only(diff),
+            DiffType::Modified => {
+                let left_path = self.args.left().join(&diff.path);
+                let right_path = self.args.right().join(&diff.path);
+                let flow = self.merge_text(&diff.path, &left_path, &right_path)?;
+                // Differing metadata is resolved on its own once the contents are
+                match &diff.metadata {
+                    Some(metadata) if flow == Flow::Continue => {
+                        let entry = DiffEntry::metadata_only(diff.path.clone(), metadata.clone());
+                        self.handle_whole_entry(&entry)
                     }
+                    _ => Ok(flow),
                 }
             }
+            DiffType::TypeMismatch | DiffType::SymlinkChanged | DiffType::MetadataOnly => {
+                self.handle_whole_entry(diff)
+            }
+            DiffType::Renamed {
+                from,
+                to,
+                similarity,
+            } => self.handle_renamed(diff, from, to, *similarity),
+        }
+    }
+
+    /// Resolve an entry by the first matching rule of the config file
+    ///
+    /// Returns false when no rule applies, or when a newer/larger rule cannot
+    /// tell the versions apart and the entry goes through the usual flow.
+    fn apply_rule(&mut self, diff: &DiffEntry) -> Result<bool> {
+        let Some((rule, action)) = self.rules.find(diff) else {
+            return Ok(false);
+        };
+        let args = self.args;
+        let (left_path, right_path) = match &diff.diff_type {
+            DiffType::Renamed { from, to, .. } => (args.left().join(from), args.right().join(to)),
+            _ => (args.left().join(&diff.path), args.right().join(&diff.path)),
+        };
+        let choice = match action {
+            RuleAction::Take(prefer) => match choose_side(prefer, &left_path, &right_path)? {
+                HunkChoice::Skip => return Ok(false),
+                choice => Some(choice),
+            },
+            _ => None,
+        };
+
+        println!();
+        println!(
+            "{} {} (rule '{}' at line {}: {})",
+            "File:".cyan().bold(),
+            diff.path.display(),
+            rule.pattern,
+            rule.line,
+            action
+        );
+        self.stats.rule_resolved += 1;
+        if args.dry_run {
+            return Ok(true);
+        }
+        let description = format!("{} {}", action, diff.path.display());
+        // Rules apply right away, so there is no earlier state to check
+        match (action, choice) {
+            (RuleAction::File(action), _) => {
+                let paths = self.entry_paths(diff);
+                self.journal(description, &paths)?;
+                apply_file_action(
+                    diff,
+                    action,
+                    args.left(),
+                    args.right(),
+                    &[],
+                    args.follow_symlinks,
+                )?;
+            }
+            (_, Some(choice)) => self.apply_choice(diff, choice, &[])?,
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Resolve an entry against the base directory
+    ///
+    /// Returns None when the entry still needs the regular two-way prompt.
+    fn handle_three_way(&mut self, diff: &DiffEntry, base: &Path) -> Result<Option<Flow>> {
+        let args = self.args;
+        let origin = classify_change(diff, base, args.left(), args.right(), self.compare_options)?;
+        if origin != ChangeOrigin::Conflict {
+            let (choice, label) = if origin == ChangeOrigin::Left {
+                (HunkChoice::Left, "changed in left, updating right")
+            } else {
+                (HunkChoice::Right, "changed in right, updating left")
+            };
+            println!();
+            println!(
+                "{} {} ({})",
+                "File:".cyan().bold(),
+                diff.path.display(),
+                label
+            );
+            if !args.dry_run {
+                let paths = self.entry_paths(diff);
+                self.journal(format!("{} {}", label, diff.path.display()), &paths)?;
+                apply_entry_choice(
+                    diff,
+                    choice,
+                    args.left(),
+                    args.right(),
+                    args.follow_symlinks,
+                )?;
+            }
+            self.stats.auto_resolved += 1;
+            return Ok(Some(Flow::Continue));
+        }
+
+        // Both sides edited a text file: merge hunks against the base
+        let base_path = base.join(&diff.path);
+        let left_path = args.left().join(&diff.path);
+        let right_path = args.right().join(&diff.path);
+        if diff.diff_type != DiffType::Modified || !base_path.is_file() {
+            return Ok(None);
+        }
+        let (Ok(left_state), Ok(right_state)) = (
+            FileState::capture(&left_path),
+            FileState::capture(&right_path),
+        ) else {
+            return Ok(None);
+        };
+        let (Ok(Some(base_content)), Ok(Some(left_content)), Ok(Some(right_content))) = (
+            read_text_file(&base_path),
+            read_text_file(&left_path),
+            read_text_file(&right_path),
+        ) else {
+            return Ok(None);
+        };
+
+        let hunks = extract_three_way_hunks(
+            &base_content,
+            &left_content,
+            &right_content,
+            &self.args.hunk_options(),
+        );
+        let conflicts: Vec<_> = hunks.iter().filter(|h| h.is_conflict()).collect();
+
+        println!();
+        println!(
+            "{} {} ({} hunk(s), {} conflict(s))",
+            "File:".cyan().bold(),
+            diff.path.display(),
+            hunks.len(),
+            conflicts.len()
+        );
 
-            DiffType::RightOnly => {
-                // Check for binary file
-                if args.skip_binary && is_binary_file(&right_path) {
+        let mut flow = Flow::Continue;
+        let mut conflict_choices = Vec::new();
+        let mut policy_choice = None;
+        for (i, hunk) in conflicts.iter().enumerate() {
+            let fingerprint = three_way_fingerprint(hunk);
+            let choice = if self.skipped_before(&diff.path, fingerprint) {
+                HunkChoice::Skip
+            } else if let Some(choice) = self
+                .replayed(DecisionKind::Hunk, &diff.path, fingerprint)
+                .and_then(Choice::hunk_choice)
+            {
+                choice
+            } else if self.policy.is_active() {
+                let choice =
+                    self.hunk_policy_choice(&mut policy_choice, &left_path, &right_path)?;
+                if choice == HunkChoice::Skip {
+                    // Unresolved by the policy: neither counted nor recorded
+                    conflict_choices.push(choice);
                     continue;
                 }
-
-                println!();
-                println!(
-                    "{} {} (only in right)",
-                    "File:".cyan().bold(),
-                    diff.path.display()
-                );
-                print!(
-                    "  Choose: {}opy to left / {}elete from right / {}kip / {}uit > ",
-                    "(c)".cyan().bold(),
-                    "(d)".red().bold(),
-                    "(s)".yellow().bold(),
-                    "(q)".magenta().bold()
-                );
-                std::io::Write::flush(&mut std::io::stdout()).unwrap();
-
-                loop {
-                    let mut input = String::new();
-                    std::io::stdin().read_line(&mut input)?;
-                    match input.trim().to_lowercase().as_str() {
-                        "c" => {
-                            println!("{}", "  Copying to left...".green());
-                            if !args.dry_run {
-                                apply_file_action(diff, FileAction::Copy, &args.left, &args.right)?;
-                            }
-                            break;
-                        }
-                        "d" => {
-                            println!("{}", "  Deleting from right...".red());
-                            if !args.dry_run {
-                                apply_file_action(
-                                    diff,
-                                    FileAction::Delete,
-                                    &args.left,
-                                    &args.right,
-                                )?;
-                            }
-                            break;
-                        }
-                        "s" => {
-                            println!("{}", "  Skipped".yellow());
-                            skip_choices += 1;
-                            break;
+                choice
+            } else {
+                display_three_way_hunk(hunk, i, conflicts.len(), &diff.path);
+                let answer = loop {
+                    match prompt_for_hunk_choice() {
+                        HunkUserChoice::Undo => {
+                            self.undo_last()?;
                         }
-                        "q" => {
-                            println!("{}", "  Quitting...".red());
-                            quit = true;
-                            break;
+                        HunkUserChoice::AllWhitespace(_)
+                        | HunkUserChoice::Split
+                        | HunkUserChoice::Edit
+                        | HunkUserChoice::Previous
+                        | HunkUserChoice::Next => println!(
+                            "{}",
+                            "  Whitespace-only, split, edit and moving between hunks are not available for conflicts"
+                                .yellow()
+                        ),
+                        answer => break answer,
+                    }
+                };
+                match answer {
+                    HunkUserChoice::Choice(choice) => choice,
+                    HunkUserChoice::SkipFile => {
+                        for hunk in &conflicts[i..] {
+                            let fingerprint = three_way_fingerprint(hunk);
+                            self.record(DecisionKind::Hunk, &diff.path, fingerprint, Choice::Skip)?;
                         }
-                        _ => {}
+                        break;
                     }
+                    HunkUserChoice::Quit => {
+                        flow = Flow::Quit;
+                        break;
+                    }
+                    HunkUserChoice::Undo
+                    | HunkUserChoice::AllWhitespace(_)
+                    | HunkUserChoice::Split
+                    | HunkUserChoice::Edit
+                    | HunkUserChoice::Previous
+                    | HunkUserChoice::Next => unreachable!("handled above"),
+                }
+            };
+            self.record(DecisionKind::Hunk, &diff.path, fingerprint, choice)?;
+            self.stats.record(choice);
+            conflict_choices.push(choice);
+            self.stats.total_hunks += 1;
+        }
+
+        // Non-conflicting hunks are applied even if the conflicts were skipped
+        self.stats.auto_resolved += hunks.len() - conflicts.len();
+        if !args.dry_run {
+            let (merged_left, merged_right) = apply_three_way_choices(
+                &base_content,
+                &left_content,
+                &right_content,
+                &hunks,
+                &conflict_choices,
+            );
+            let states = [left_state, right_state];
+            let paths = [left_path.clone(), right_path.clone()];
+            check_unchanged(&states)?;
+            self.journal(format!("merge {}", diff.path.display()), &paths)?;
+            apply_hunk_merge(
+                &left_path,
+                &right_path,
+                &merged_left,
+                &merged_right,
+                &states,
+            )?;
+            println!("{}", "  ✓ Applied.".green());
+        }
+        Ok(Some(flow))
+    }
+
+    /// Prompt for an entry that exists on one side only
+    fn handle_only(&mut self, diff: &DiffEntry) -> Result<Flow> {
+        let args = self.args;
+        let (side, other, path) = if diff.diff_type == DiffType::LeftOnly {
+            ("left", "right", args.left().join(&diff.path))
+        } else {
+            ("right", "left", args.right().join(&diff.path))
+        };
+
+        // Check for binary file
+        if args.skip_binary && is_binary_file(&path) {
+            return Ok(Flow::Continue);
+        }
+
+        println!();
+        println!(
+            "{} {} (only in {})",
+            "File:".cyan().bold(),
+            diff.path.display(),
+            side
+        );
+
+        let left_path = args.left().join(&diff.path);
+        let right_path = args.right().join(&diff.path);
+        let states = [
+            FileState::capture(&left_path)?,
+            FileState::capture(&right_path)?,
+        ];
+        let fingerprint = self.entry_fingerprint(&left_path, &right_path)?;
+        let replayed = self
+            .replayed(DecisionKind::File, &diff.path, fingerprint)
+            .and_then(Choice::file_action);
+        let action = if let Some(action) = replayed {
+            action
+        } else if self.policy.is_active() {
+            match self.policy.only_action(&diff.diff_type) {
+                Some(action) => action,
+                None => {
+                    self.mark_unresolved();
+                    return Ok(Flow::Continue);
                 }
             }
+        } else {
+            print!(
+                "  Choose: {}opy to {} / {}elete from {} / {}kip / {}ndo / {}uit > ",
+                "(c)".cyan().bold(),
+                other,
+                "(d)".red().bold(),
+                side,
+                "(s)".yellow().bold(),
+                "(u)".blue().bold(),
+                "(q)".magenta().bold()
+            );
+            std::io::Write::flush(&mut std::io::stdout()).unwrap();
 
-            DiffType::Modified => {
-                // Read file contents
-                let left_content = match read_text_file(&left_path) {
-                    Ok(Some(content)) => content,
-                    Ok(None) => {
-                        if !args.skip_binary {
-                            println!(
-                                "{} {} (binary file - skipping)",
-                                "File:".cyan().bold(),
-                                diff.path.display()
-                            );
-                        }
+            loop {
+                match read_answer()?.as_str() {
+                    "c" => break FileAction::Copy,
+                    "d" => break FileAction::Delete,
+                    "s" => break FileAction::Skip,
+                    "u" => {
+                        self.undo_last()?;
+                    }
+                    "q" => {
+                        println!("{}", "  Quitting...".red());
+                        return Ok(Flow::Quit);
+                    }
+                    _ => {}
+                }
+            }
+        };
+        self.record(DecisionKind::File, &diff.path, fingerprint, action)?;
+
+        match action {
+            FileAction::Copy => println!("{}", format!("  Copying to {}...", other).green()),
+            FileAction::Delete => println!("{}", format!("  Deleting from {}...", side).red()),
+            FileAction::Skip => {
+                println!("{}", "  Skipped".yellow());
+                self.stats.skip_choices += 1;
+                return Ok(Flow::Continue);
+            }
+        }
+        if !args.dry_run {
+            let description = match action {
+                FileAction::Copy => format!("copy {} to {}", diff.path.display(), other),
+                _ => format!("delete {} from {}", diff.path.display(), side),
+            };
+            let paths = self.entry_paths(diff);
+            self.journal(description, &paths)?;
+            apply_file_action(
+                diff,
+                action,
+                args.left(),
+                args.right(),
+                &states,
+                args.follow_symlinks,
+            )?;
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Ask for a displayed hunk until it is resolved, splitting or editing it on request
+    fn ask_hunk(
+        &mut self,
+        hunk: &Hunk,
+        index: usize,
+        total: usize,
+        display_path: &Path,
+    ) -> Result<HunkAnswer> {
+        loop {
+            let answer = match prompt_for_hunk_choice() {
+                HunkUserChoice::Choice(choice) => HunkAnswer::Resolved(choice.into()),
+                HunkUserChoice::AllWhitespace(choice) => HunkAnswer::AllWhitespace(choice),
+                HunkUserChoice::Split => match self.ask_split(hunk, display_path)? {
+                    Some(answer) => answer,
+                    None => {
+                        display_hunk(
+                            hunk,
+                            index,
+                            total,
+                            display_path,
+                            self.args.layout,
+                            self.args.highlighter(display_path).as_ref(),
+                        );
                         continue;
                     }
-                    Err(e) => {
+                },
+                HunkUserChoice::Edit => match edit_hunk(hunk, display_path)? {
+                    Some(lines) => {
+                        println!("{}", "  Using edited lines".green());
+                        HunkAnswer::Resolved(HunkResolution::Edited(lines))
+                    }
+                    None => {
                         println!(
-                            "{} {} (error reading: {})",
-                            "File:".cyan().bold(),
-                            diff.path.display(),
-                            e
+                            "{}",
+                            "  Conflict markers left in place, edit cancelled".yellow()
                         );
                         continue;
                     }
-                };
+                },
+                HunkUserChoice::Previous => HunkAnswer::Previous,
+                HunkUserChoice::Next => HunkAnswer::Next,
+                HunkUserChoice::SkipFile => HunkAnswer::SkipFile,
+                HunkUserChoice::Undo => HunkAnswer::Undo,
+                HunkUserChoice::Quit => HunkAnswer::Quit,
+            };
+            return Ok(answer);
+        }
+    }
 
-                let right_content = match read_text_file(&right_path) {
-                    Ok(Some(content)) => content,
-                    Ok(None) => {
-                        if !args.skip_binary {
-                            println!(
-                                "{} {} (binary file - skipping)",
-                                "File:".cyan().bold(),
-                                diff.path.display()
-                            );
-                        }
-                        continue;
+    /// Resolve a hunk piece by piece after splitting it
+    ///
+    /// `u` and `k` go back one piece, and on the first piece return None to
+    /// ask about the whole hunk again; `j` leaves a piece as it is.
+    fn ask_split(&mut self, hunk: &Hunk, display_path: &Path) -> Result<Option<HunkAnswer>> {
+        let pieces = split_hunk(hunk);
+        if pieces.len() < 2 {
+            println!("{}", "  This hunk cannot be split".yellow());
+            return Ok(None);
+        }
+        println!("{}", format!("  Split into {} hunks", pieces.len()).cyan());
+        let mut resolutions = Vec::new();
+        while resolutions.len() < pieces.len() {
+            let piece = &pieces[resolutions.len()];
+            display_hunk(
+                piece,
+                resolutions.len(),
+                pieces.len(),
+                display_path,
+                self.args.layout,
+                self.args.highlighter(display_path).as_ref(),
+            );
+            match prompt_for_hunk_choice() {
+                HunkUserChoice::Choice(choice) => resolutions.push(choice.into()),
+                HunkUserChoice::AllWhitespace(_) => println!(
+                    "{}",
+                    "  Whitespace-only hunks are resolved from the whole hunk".yellow()
+                ),
+                HunkUserChoice::Split => println!("{}", "  This hunk cannot be split".yellow()),
+                HunkUserChoice::Edit => match edit_hunk(piece, display_path)? {
+                    Some(lines) => {
+                        println!("{}", "  Using edited lines".green());
+                        resolutions.push(HunkResolution::Edited(lines));
                     }
-                    Err(e) => {
+                    None => println!(
+                        "{}",
+                        "  Conflict markers left in place, edit cancelled".yellow()
+                    ),
+                },
+                HunkUserChoice::Undo | HunkUserChoice::Previous => {
+                    if resolutions.pop().is_none() {
+                        return Ok(None);
+                    }
+                }
+                HunkUserChoice::Next => resolutions.push(HunkChoice::Skip.into()),
+                HunkUserChoice::SkipFile => return Ok(Some(HunkAnswer::SkipFile)),
+                HunkUserChoice::Quit => return Ok(Some(HunkAnswer::Quit)),
+            }
+        }
+        Ok(Some(HunkAnswer::Resolved(HunkResolution::Split(
+            resolutions,
+        ))))
+    }
+
+    /// Merge two text files hunk by hunk
+    fn merge_text(
+        &mut self,
+        display_path: &Path,
+        left_path: &Path,
+        right_path: &Path,
+    ) -> Result<Flow> {
+        let args = self.args;
+
+        // Read file contents, noting their state so later edits are detected
+        let mut contents = Vec::new();
+        let mut states = Vec::new();
+        for path in [left_path, right_path] {
+            let read = FileState::capture(path).and_then(|state| {
+                states.push(state);
+                read_text_file(path)
+            });
+            match read {
+                Ok(Some(content)) => contents.push(content),
+                Ok(None) if self.policy.is_active() || self.replay.is_some() => {
+                    // Binary files cannot be merged by hunk, but a policy or
+                    // a replayed decision can take one side
+                    println!();
+                    println!(
+                        "{} {} (binary file)",
+                        "File:".cyan().bold(),
+                        display_path.display()
+                    );
+                    let states = [
+                        FileState::capture(left_path)?,
+                        FileState::capture(right_path)?,
+                    ];
+                    let fingerprint = self.entry_fingerprint(left_path, right_path)?;
+                    let choice = match self
+                        .replayed(DecisionKind::Entry, display_path, fingerprint)
+                        .and_then(Choice::hunk_choice)
+                    {
+                        Some(choice) => Some(choice),
+                        None => self.decide(&DiffType::Modified, left_path, right_path)?,
+                    };
+                    if let Some(choice @ (HunkChoice::Left | HunkChoice::Right)) = choice {
+                        self.record(DecisionKind::Entry, display_path, fingerprint, choice)?;
+                        let entry = DiffEntry::modified(display_path.to_path_buf());
+                        self.apply_whole_entry(&entry, choice, &states)?;
+                    }
+                    return Ok(Flow::Continue);
+                }
+                Ok(None) => {
+                    if !args.skip_binary {
                         println!(
-                            "{} {} (error reading: {})",
+                            "{} {} (binary file - skipping)",
                             "File:".cyan().bold(),
-                            diff.path.display(),
-                            e
+                            display_path.display()
                         );
-                        continue;
                     }
-                };
+                    return Ok(Flow::Continue);
+                }
+                Err(e) => {
+                    println!(
+                        "{} {} (error reading: {})",
+                        "File:".cyan().bold(),
+                        display_path.display(),
+                        e
+                    );
+                    return Ok(Flow::Continue);
+                }
+            }
+        }
+        let right_content = contents.pop().unwrap_or_default();
+        let left_content = contents.pop().unwrap_or_default();
 
-                // Extract hunks
-                let hunks = extract_hunks(&left_content, &right_content, 3);
+        // Extract hunks
+        let hunks = extract_hunks(&left_content, &right_content, &self.args.hunk_options());
+
+        if hunks.is_empty() {
+            return Ok(Flow::Continue);
+        }
 
-                if hunks.is_empty() {
+        println!();
+        println!(
+            "{} {} ({} hunk(s))",
+            "File:".cyan().bold(),
+            display_path.display(),
+            hunks.len()
+        );
+        let apply =
+            |choices: &[Option<HunkResolution>], states: &mut Vec<FileState>| -> Result<()> {
+                if !args.dry_run {
+                    let (merged_left, merged_right) =
+                        apply_hunk_decisions(&left_content, &right_content, &hunks, choices);
+                    apply_hunk_merge(left_path, right_path, &merged_left, &merged_right, states)?;
+                    // Our own write is the state the next hunk is checked against
+                    *states = vec![
+                        FileState::capture(left_path)?,
+                        FileState::capture(right_path)?,
+                    ];
+                    println!("{}", "  ✓ Applied.".green());
+                }
+                Ok(())
+            };
+
+        let description = format!("merge {}", display_path.display());
+        let paths = [left_path.to_path_buf(), right_path.to_path_buf()];
+        // Decision for each hunk; undecided hunks are left as they are
+        let mut hunk_choices: Vec<Option<HunkResolution>> = vec![None; hunks.len()];
+        // Decisions the file currently holds, written from the original contents
+        let mut written = hunk_choices.clone();
+        // Whether a hunk was already offered to the resumed session, replay and policy
+        let mut seen = vec![false; hunks.len()];
+        let mut policy_choice = None;
+        // Hunk, decisions and written decisions before each change, for undo
+        let mut applied = Vec::new();
+        let mut flow = Flow::Continue;
+        let mut i = 0;
+
+        while i < hunks.len() {
+            let hunk = &hunks[i];
+            if !seen[i] {
+                seen[i] = true;
+                let fingerprint = hunk_fingerprint(hunk);
+                let automatic = if self.skipped_before(display_path, fingerprint) {
+                    Some(HunkChoice::Skip)
+                } else if let Some(choice) = self
+                    .replayed(DecisionKind::Hunk, display_path, fingerprint)
+                    .and_then(Choice::hunk_choice)
+                {
+                    Some(choice)
+                } else if let Some(choice) = self
+                    .whitespace_choice
+                    .filter(|_| is_whitespace_only_diff(hunk))
+                {
+                    Some(choice)
+                } else if self.policy.is_active() {
+                    match self.hunk_policy_choice(&mut policy_choice, left_path, right_path)? {
+                        // Unresolved by the policy: neither counted nor recorded
+                        HunkChoice::Skip => None,
+                        choice => Some(choice),
+                    }
+                } else {
+                    None
+                };
+                if automatic.is_some() || self.policy.is_active() {
+                    hunk_choices[i] = automatic.map(HunkResolution::from);
+                    i += 1;
                     continue;
                 }
+            }
 
-                println!();
-                println!(
-                    "{} {} ({} hunk(s))",
-                    "File:".cyan().bold(),
-                    diff.path.display(),
-                    hunks.len()
-                );
+            display_hunk(
+                hunk,
+                i,
+                hunks.len(),
+                display_path,
+                args.layout,
+                args.highlighter(display_path).as_ref(),
+            );
+            if let Some(current) = &hunk_choices[i] {
+                println!("  {} {}", "Current choice:".cyan(), describe(current));
+            }
+            let resolution = match self.ask_hunk(hunk, i, hunks.len(), display_path)? {
+                HunkAnswer::Resolved(resolution) => resolution,
+                HunkAnswer::AllWhitespace(choice) => {
+                    // Later files take the same side as they come
+                    self.whitespace_choice = Some(choice);
+                    let before = hunk_choices.clone();
+                    // Decisions already made for other hunks are kept
+                    let mut count = 0;
+                    for (j, other) in hunks.iter().enumerate() {
+                        let open = j == i || hunk_choices[j].is_none();
+                        if open && is_whitespace_only_diff(other) {
+                            hunk_choices[j] = Some(choice.into());
+                            count += 1;
+                        }
+                    }
+                    println!(
+                        "{}",
+                        format!("  Resolved {} whitespace-only hunk(s)", count).green()
+                    );
+                    if resolved(&hunk_choices) != resolved(&written) {
+                        check_unchanged(&states)?;
+                        self.journal(description.clone(), &paths)?;
+                        apply(&hunk_choices, &mut states)?;
+                        applied.push((i, before, written));
+                        written = hunk_choices.clone();
+                    }
+                    if is_whitespace_only_diff(hunk) {
+                        i += 1;
+                    }
+                    continue;
+                }
+                HunkAnswer::Previous => {
+                    match i.checked_sub(1) {
+                        Some(previous) => i = previous,
+                        None => println!("{}", "  Already at the first hunk".yellow()),
+                    }
+                    continue;
+                }
+                HunkAnswer::Next => {
+                    i += 1;
+                    continue;
+                }
+                HunkAnswer::SkipFile => {
+                    // Skip remaining undecided hunks in this file
+                    for choice in &mut hunk_choices[i..] {
+                        choice.get_or_insert(HunkChoice::Skip.into());
+                    }
+                    break;
+                }
+                HunkAnswer::Quit => {
+                    flow = Flow::Quit;
+                    break;
+                }
+                HunkAnswer::Undo => {
+                    if self.undo_last()? {
+                        if let Some((hunk, choices, previous)) = applied.pop() {
+                            // A change to this file was reverted: ask again from its hunk
+                            (i, hunk_choices, written) = (hunk, choices, previous);
+                            states = vec![
+                                FileState::capture(left_path)?,
+                                FileState::capture(right_path)?,
+                            ];
+                        }
+                    }
+                    continue;
+                }
+            };
+            let before = hunk_choices.clone();
+            hunk_choices[i] = Some(resolution);
 
-                let mut hunk_choices = Vec::new();
+            // Rewrite the file from its original contents with every decision
+            // so far, which also covers revised hunks and pending replayed ones
+            if resolved(&hunk_choices) != resolved(&written) {
+                check_unchanged(&states)?;
+                self.journal(description.clone(), &paths)?;
+                apply(&hunk_choices, &mut states)?;
+                applied.push((i, before, written));
+                written = hunk_choices.clone();
+            }
+            i += 1;
+        }
 
-                for (i, hunk) in hunks.iter().enumerate() {
-                    display_hunk(hunk, i, hunks.len(), &diff.path);
+        // Decisions are recorded once the file is left, so revised hunks count once
+        for (hunk, choice) in hunks.iter().zip(&hunk_choices) {
+            let Some(choice) = choice else {
+                continue;
+            };
+            // Split and edited hunks cannot be replayed, so they are not recorded
+            if let HunkResolution::Choice(choice) = choice {
+                let fingerprint = hunk_fingerprint(hunk);
+                self.record(DecisionKind::Hunk, display_path, fingerprint, *choice)?;
+            }
+            self.stats.record_resolution(choice);
+            self.stats.total_hunks += 1;
+        }
+        if resolved(&hunk_choices) != resolved(&written) {
+            check_unchanged(&states)?;
+            self.journal(description, &paths)?;
+            apply(&hunk_choices, &mut states)?;
+        }
+        Ok(flow)
+    }
 
-                    match prompt_for_hunk_choice() {
-                        HunkUserChoice::Choice(choice) => {
-                            match choice {
-                                ddmerge::diff::HunkChoice::Left => left_choices += 1,
-                                ddmerge::diff::HunkChoice::Right => right_choices += 1,
-                                ddmerge::diff::HunkChoice::Skip => skip_choices += 1,
+    /// Prompt for an entry that can only be taken as a whole from one side
+    fn handle_whole_entry(&mut self, diff: &DiffEntry) -> Result<Flow> {
+        let args = self.args;
+        let left_path = args.left().join(&diff.path);
+        let right_path = args.right().join(&diff.path);
+        let states = [
+            FileState::capture(&left_path)?,
+            FileState::capture(&right_path)?,
+        ];
+
+        println!();
+        if diff.diff_type == DiffType::MetadataOnly {
+            println!(
+                "{} {} (metadata differs: {})",
+                "File:".cyan().bold(),
+                diff.path.display(),
+                diff.metadata.clone().unwrap_or_default()
+            );
+        } else if diff.diff_type == DiffType::SymlinkChanged {
+            println!(
+                "{} {} (symlink target differs: left -> {}, right -> {})",
+                "File:".cyan().bold(),
+                diff.path.display(),
+                read_link_display(&left_path),
+                read_link_display(&right_path)
+            );
+        } else {
+            println!(
+                "{} {} (type mismatch: left is {}, right is {})",
+                "File:".cyan().bold(),
+                diff.path.display(),
+                diff.left_type_name(),
+                diff.right_type_name()
+            );
+        }
+        let fingerprint = self.entry_fingerprint(&left_path, &right_path)?;
+        let replayed = self
+            .replayed(DecisionKind::Entry, &diff.path, fingerprint)
+            .and_then(Choice::hunk_choice);
+        let choice = match replayed {
+            Some(choice) => choice,
+            None => match self.decide(&diff.diff_type, &left_path, &right_path)? {
+                Some(HunkChoice::Skip) => return Ok(Flow::Continue),
+                Some(choice) => choice,
+                None => {
+                    print!(
+                        "  Choose: {}eft (overwrite right) / {}ight (overwrite left) / {}kip / {}ndo / {}uit > ",
+                        "(l)".red().bold(),
+                        "(r)".green().bold(),
+                        "(s)".yellow().bold(),
+                        "(u)".blue().bold(),
+                        "(q)".magenta().bold()
+                    );
+                    std::io::Write::flush(&mut std::io::stdout()).unwrap();
+
+                    loop {
+                        match read_answer()?.as_str() {
+                            "l" => {
+                                println!("{}", "  Using left (updating right)...".green());
+                                break HunkChoice::Left;
                             }
-                            hunk_choices.push(choice);
-                            total_hunks += 1;
-
-                            // Apply changes immediately when left or right is chosen
-                            if choice != ddmerge::diff::HunkChoice::Skip && !args.dry_run {
-                                let (merged_left, merged_right) = ddmerge::diff::apply_hunk_choices(
-                                    &left_content,
-                                    &right_content,
-                                    &hunks,
-                                    &hunk_choices,
-                                );
-                                apply_hunk_merge(
-                                    &left_path,
-                                    &right_path,
-                                    &merged_left,
-                                    &merged_right,
-                                )?;
-                                println!("{}", "  ✓ Applied.".green());
+                            "r" => {
+                                println!("{}", "  Using right (updating left)...".green());
+                                break HunkChoice::Right;
                             }
-                        }
-                        HunkUserChoice::SkipFile => {
-                            // Skip remaining hunks in this file
-                            break;
-                        }
-                        HunkUserChoice::Quit => {
-                            quit = true;
-                            break;
+                            "s" => {
+                                println!("{}", "  Skipped".yellow());
+                                break HunkChoice::Skip;
+                            }
+                            "u" => {
+                                self.undo_last()?;
+                            }
+                            "q" => {
+                                println!("{}", "  Quitting...".red());
+                                return Ok(Flow::Quit);
+                            }
+                            _ => {}
                         }
                     }
                 }
+            },
+        };
 
-                if quit {
-                    break;
+        self.record(DecisionKind::Entry, &diff.path, fingerprint, choice)?;
+        self.apply_whole_entry(diff, choice, &states)?;
+        Ok(Flow::Continue)
+    }
+
+    /// Apply a choice to an entry that is taken as a whole from one side
+    fn apply_whole_entry(
+        &mut self,
+        diff: &DiffEntry,
+        choice: HunkChoice,
+        expected: &[FileState],
+    ) -> Result<()> {
+        if !self.args.dry_run {
+            self.apply_choice(diff, choice, expected)?;
+        }
+        self.stats.record(choice);
+        Ok(())
+    }
+
+    /// Make both sides hold the chosen version of an entry as a whole
+    ///
+    /// Nothing is changed if a path no longer matches its `expected` state.
+    fn apply_choice(
+        &mut self,
+        diff: &DiffEntry,
+        choice: HunkChoice,
+        expected: &[FileState],
+    ) -> Result<()> {
+        if choice == HunkChoice::Skip {
+            return Ok(());
+        }
+        check_unchanged(expected)?;
+        let side = if choice == HunkChoice::Left {
+            "left"
+        } else {
+            "right"
+        };
+        let paths = self.entry_paths(diff);
+        self.journal(format!("take {} {}", side, diff.path.display()), &paths)?;
+
+        let (left, right) = (self.args.left(), self.args.right());
+        let follow = self.args.follow_symlinks;
+        match &diff.diff_type {
+            DiffType::MetadataOnly => apply_metadata_choice(diff, choice, left, right),
+            DiffType::Renamed {
+                from,
+                to,
+                similarity,
+            } => {
+                apply_rename(diff, choice, left, right)?;
+                if *similarity < 100 {
+                    let name = if choice == HunkChoice::Left { from } else { to };
+                    let entry = DiffEntry::modified(name.clone());
+                    apply_entry_choice(&entry, choice, left, right, follow)?;
                 }
+                Ok(())
             }
+            _ => apply_entry_choice(diff, choice, left, right, follow),
+        }
+    }
 
-            DiffType::TypeMismatch => {
-                println!();
-                println!(
-                    "{} {} (type mismatch: left is {}, right is {})",
-                    "File:".cyan().bold(),
-                    diff.path.display(),
-                    if diff.left_is_dir.unwrap_or(false) {
-                        "directory"
-                    } else {
-                        "file"
-                    },
-                    if diff.right_is_dir.unwrap_or(false) {
-                        "directory"
-                    } else {
-                        "file"
-                    }
-                );
-                print!(
-                    "  Choose: {}eft (overwrite right) / {}ight (overwrite left) / {}kip / {}uit > ",
+    /// Prompt for a left-only/right-only pair detected as a rename
+    fn handle_renamed(
+        &mut self,
+        diff: &DiffEntry,
+        from: &Path,
+        to: &Path,
+        similarity: u8,
+    ) -> Result<Flow> {
+        let args = self.args;
+
+        println!();
+        println!(
+            "{} {} -> {} (renamed, {}% similar)",
+            "File:".cyan().bold(),
+            from.display(),
+            to.display(),
+            similarity
+        );
+        let left_path = args.left().join(from);
+        let right_path = args.right().join(to);
+        let states = [
+            FileState::capture(&left_path)?,
+            FileState::capture(&right_path)?,
+        ];
+        let fingerprint = self.entry_fingerprint(&left_path, &right_path)?;
+        let replayed = self.replayed(DecisionKind::Rename, &diff.path, fingerprint);
+        let choice = match replayed {
+            Some(choice) => choice,
+            None => match self.decide(&diff.diff_type, &left_path, &right_path)? {
+                Some(HunkChoice::Skip) => return Ok(Flow::Continue),
+                Some(choice) => choice.into(),
+                None => {
+                    print!(
+                    "  Choose: {}eft name (rename right) / {}ight name (rename left) / {}ndependent / {}kip / {}ndo / {}uit > ",
                     "(l)".red().bold(),
                     "(r)".green().bold(),
+                    "(i)".cyan().bold(),
                     "(s)".yellow().bold(),
+                    "(u)".blue().bold(),
                     "(q)".magenta().bold()
                 );
-                std::io::Write::flush(&mut std::io::stdout()).unwrap();
-
-                loop {
-                    let mut input = String::new();
-                    std::io::stdin().read_line(&mut input)?;
-                    match input.trim().to_lowercase().as_str() {
-                        "l" => {
-                            println!("{}", "  Using left (updating right)...".green());
-                            if !args.dry_run {
-                                // Remove right, copy left to right
-                                let right_path = args.right.join(&diff.path);
-                                if right_path.is_dir() {
-                                    std::fs::remove_dir_all(&right_path)?;
-                                } else {
-                                    std::fs::remove_file(&right_path)?;
-                                }
-                                apply_file_action(diff, FileAction::Copy, &args.left, &args.right)?;
+                    std::io::Write::flush(&mut std::io::stdout()).unwrap();
+
+                    loop {
+                        match read_answer()?.as_str() {
+                            "l" => break Choice::Left,
+                            "r" => break Choice::Right,
+                            "i" => break Choice::Independent,
+                            "s" => break Choice::Skip,
+                            "u" => {
+                                self.undo_last()?;
                             }
-                            left_choices += 1;
-                            break;
-                        }
-                        "r" => {
-                            println!("{}", "  Using right (updating left)...".green());
-                            if !args.dry_run {
-                                // Remove left, copy right to left
-                                let left_path = args.left.join(&diff.path);
-                                if left_path.is_dir() {
-                                    std::fs::remove_dir_all(&left_path)?;
-                                } else {
-                                    std::fs::remove_file(&left_path)?;
-                                }
-                                // Need to swap for RightOnly behavior
-                                let mut swapped_diff = diff.clone();
-                                swapped_diff.diff_type = DiffType::RightOnly;
-                                apply_file_action(
-                                    &swapped_diff,
-                                    FileAction::Copy,
-                                    &args.left,
-                                    &args.right,
-                                )?;
+                            "q" => {
+                                println!("{}", "  Quitting...".red());
+                                return Ok(Flow::Quit);
                             }
-                            right_choices += 1;
-                            break;
+                            _ => {}
                         }
-                        "s" => {
-                            println!("{}", "  Skipped".yellow());
-                            skip_choices += 1;
-                            break;
-                        }
-                        "q" => {
-                            println!("{}", "  Quitting...".red());
-                            quit = true;
-                            break;
-                        }
-                        _ => {}
                     }
                 }
+            },
+        };
+        self.record(DecisionKind::Rename, &diff.path, fingerprint, choice)?;
+
+        let choice = match choice {
+            Choice::Independent => {
+                // Handle both files as unrelated one-sided entries
+                let is_dir = diff.left_is_dir.unwrap_or(false);
+                let left_only = DiffEntry::left_only(from.to_path_buf(), is_dir);
+                if self.handle_only(&left_only)? == Flow::Quit {
+                    return Ok(Flow::Quit);
+                }
+                let right_only = DiffEntry::right_only(to.to_path_buf(), is_dir);
+                return self.handle_only(&right_only);
+            }
+            Choice::Skip => {
+                println!("{}", "  Skipped".yellow());
+                self.stats.skip_choices += 1;
+                return Ok(Flow::Continue);
+            }
+            choice => choice
+                .hunk_choice()
+                .expect("a rename is resolved by left or right"),
+        };
+        let (renamed, old, new) = if choice == HunkChoice::Left {
+            ("right", to, from)
+        } else {
+            ("left", from, to)
+        };
+        println!(
+            "{}",
+            format!(
+                "  Renaming {} {} to {}...",
+                renamed,
+                old.display(),
+                new.display()
+            )
+            .green()
+        );
+        self.stats.record(choice);
+
+        let description = format!("rename {} {} to {}", renamed, old.display(), new.display());
+        if similarity == 100 {
+            if !args.dry_run {
+                let paths = self.entry_paths(diff);
+                check_unchanged(&states)?;
+                self.journal(description, &paths)?;
+                apply_rename(diff, choice, args.left(), args.right())?;
+            }
+            return Ok(Flow::Continue);
+        }
+
+        // Content differs too: merge it under the common name
+        let name = if choice == HunkChoice::Left { from } else { to };
+        let (left_path, right_path) = if args.dry_run {
+            (left_path, right_path)
+        } else {
+            let paths = self.entry_paths(diff);
+            check_unchanged(&states)?;
+            self.journal(description, &paths)?;
+            apply_rename(diff, choice, args.left(), args.right())?;
+            (args.left().join(name), args.right().join(name))
+        };
+        self.merge_text(name, &left_path, &right_path)
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        Some(Command::PruneCache) => return prune_cache(&args),
+        Some(Command::Undo {
+            count,
+            last_session,
+        }) => return undo(&args, count, last_session),
+        None => {}
+    }
+
+    // Validate input directories
+    if !args.left().is_dir() {
+        anyhow::bail!("Left path is not a directory: {}", args.left().display());
+    }
+    if !args.right().is_dir() {
+        anyhow::bail!("Right path is not a directory: {}", args.right().display());
+    }
+    if let Some(base) = &args.base {
+        if !base.is_dir() {
+            anyhow::bail!("Base path is not a directory: {}", base.display());
+        }
+    }
+
+    // Build path filters shared by both sides
+    let mut filter = PathFilter::new();
+    for pattern in &args.include {
+        filter = filter.include(pattern)?;
+    }
+    for pattern in &args.exclude {
+        filter = filter.exclude(pattern)?;
+    }
+    for path in &args.exclude_from {
+        filter = filter.exclude_from(path)?;
+    }
+    if let Some(pattern) = &args.exclude_regex_left {
+        filter = filter
+            .exclude_regex_left(pattern)
+            .context("Invalid regex pattern for --exclude-regex-left")?;
+    }
+    if let Some(pattern) = &args.exclude_regex_right {
+        filter = filter
+            .exclude_regex_right(pattern)
+            .context("Invalid regex pattern for --exclude-regex-right")?;
+    }
+
+    let rules = match &args.config {
+        Some(path) => RuleSet::from_file(path)?,
+        None => RuleSet::default(),
+    };
+    let replay = args.replay.as_deref().map(Replay::from_file).transpose()?;
+    let recorder = args.record.as_deref().map(Recorder::create).transpose()?;
+
+    println!("{}", "Comparing directories...".cyan());
+    let compare_options = CompareOptions {
+        follow_symlinks: args.follow_symlinks,
+        compare_mode: !args.ignore_mode,
+        compare_owner: args.compare_owner,
+        compare_mtime: args.compare_mtime,
+        respect_ignore_files: !args.no_ignore,
+        filter,
+        trust_mtime: args.trust_mtime,
+        cache: args.cache_path().as_deref().and_then(load_cache),
+        jobs: args.jobs,
+        whitespace: args.whitespace(),
+    };
+    let mut diffs = compare_directories_with_options(args.left(), args.right(), &compare_options)
+        .context("Failed to compare directories")?;
+    if let (Some(cache), Some(path)) = (&compare_options.cache, args.cache_path()) {
+        if cache.is_dirty() {
+            if let Err(e) = cache.save(&path) {
+                println!("{} {:#}", "Warning:".yellow(), e);
+            }
+        }
+    }
+    if !args.no_renames {
+        let rename_options = RenameOptions {
+            min_similarity: f32::from(args.rename_threshold) / 100.0,
+        };
+        diffs = detect_renames(
+            diffs,
+            args.left(),
+            args.right(),
+            &rename_options,
+            &compare_options,
+        )
+        .context("Failed to detect renames")?;
+    }
+
+    if diffs.is_empty() {
+        println!("{}", "Directories are identical!".green());
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!("Found {} file(s) with differences.", diffs.len()).yellow()
+    );
+
+    // Progress is kept in a session file so an interrupted merge can be resumed
+    let session_path = args.session_path()?;
+    let mut resumed = None;
+    if args.resume {
+        let Some(path) = &session_path else {
+            anyhow::bail!("No session location; pass --session <FILE>");
+        };
+        match SessionState::load(path)? {
+            Some(state) => {
+                println!("{}", format!("Resuming session {}", path.display()).cyan());
+                if !args.revisit_skipped {
+                    resumed = Some(state);
+                }
             }
+            None => println!(
+                "{} No saved session to resume; starting from the beginning",
+                "Warning:".yellow()
+            ),
         }
     }
+    let log = match &session_path {
+        Some(path) if !args.dry_run => {
+            if !args.resume && path.exists() {
+                println!(
+                    "{} Discarding an interrupted session (pass --resume to continue it)",
+                    "Warning:".yellow()
+                );
+            }
+            match SessionLog::open(path, args.resume) {
+                Ok(log) => Some(log),
+                Err(e) if args.session.is_none() => {
+                    println!(
+                        "{} {:#} (continuing without a session file)",
+                        "Warning:".yellow(),
+                        e
+                    );
+                    None
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        _ => None,
+    };
+
+    let journal = match args.journal_dir() {
+        Some(dir) if !args.dry_run => match Journal::open(&dir) {
+            Ok(journal) => Some(journal),
+            Err(e) if args.journal.is_none() => {
+                println!(
+                    "{} {:#} (continuing without undo journal)",
+                    "Warning:".yellow(),
+                    e
+                );
+                None
+            }
+            Err(e) => return Err(e),
+        },
+        _ => None,
+    };
+
+    let mut session = Session::new(&args, &compare_options, rules);
+    session.recorder = recorder;
+    session.replay = replay;
+    session.log = log;
+    session.resumed = resumed;
+    session.journal = journal;
+    let mut quit = false;
+    let tui = args.tui && Screen::is_available();
+    if args.tui && !tui {
+        println!(
+            "{} --tui needs a terminal; asking with prompts instead",
+            "Warning:".yellow()
+        );
+    }
+    if tui {
+        quit = session.run_tui(&diffs)? == Flow::Quit;
+    } else {
+        for diff in &diffs {
+            if session.process(diff)? == Flow::Quit {
+                quit = true;
+                break;
+            }
+        }
+    }
+    let unused_decisions = session.replay.as_ref().map_or(0, Replay::remaining);
+    let saved_session = match (session.log.take(), &session_path) {
+        (Some(_), Some(path)) if !quit => {
+            std::fs::remove_file(path).context("Failed to remove session file")?;
+            false
+        }
+        (Some(_), Some(_)) => true,
+        _ => false,
+    };
+    let stats = session.stats;
 
     // Summary
     println!();
     if quit {
         println!("{}", "Merge cancelled.".yellow());
+        if saved_session {
+            println!("Progress was saved; run again with --resume to continue.");
+        }
     } else if args.dry_run {
         println!("{}", "Dry run complete. No files were modified.".yellow());
     } else {
@@ -464,18 +1944,223 @@ fn main() -> Result<()> {
 
     println!();
     println!("{}", "Summary:".cyan().bold());
-    if total_hunks > 0 {
-        println!("  Total hunks processed: {}", total_hunks);
+    if stats.total_hunks > 0 {
+        println!("  Total hunks processed: {}", stats.total_hunks);
     }
-    if left_choices > 0 {
-        println!("  Left choices (updated right): {}", left_choices);
+    if stats.left_choices > 0 {
+        println!("  Left choices (updated right): {}", stats.left_choices);
     }
-    if right_choices > 0 {
-        println!("  Right choices (updated left): {}", right_choices);
+    if stats.right_choices > 0 {
+        println!("  Right choices (updated left): {}", stats.right_choices);
     }
-    if skip_choices > 0 {
-        println!("  Skipped: {}", skip_choices);
+    if stats.skip_choices > 0 {
+        println!("  Skipped: {}", stats.skip_choices);
+    }
+    if stats.both_choices > 0 {
+        println!("  Both versions kept: {}", stats.both_choices);
+    }
+    if stats.edited > 0 {
+        println!("  Edited by hand: {}", stats.edited);
+    }
+    if stats.auto_resolved > 0 {
+        println!("  Auto-resolved against base: {}", stats.auto_resolved);
+    }
+    if stats.rule_resolved > 0 {
+        println!("  Resolved by rules: {}", stats.rule_resolved);
+    }
+    if stats.undone > 0 {
+        println!("  Undone: {}", stats.undone);
+    }
+    if stats.previously_done > 0 {
+        println!(
+            "  Handled in the previous session: {}",
+            stats.previously_done
+        );
+    }
+    if stats.replayed > 0 {
+        println!("  Replayed decisions: {}", stats.replayed);
+    }
+    if stats.replay_mismatches > 0 {
+        println!(
+            "  Recorded decisions not matching current content: {}",
+            stats.replay_mismatches
+        );
+    }
+    if unused_decisions > 0 && !quit {
+        println!("  Recorded decisions not used: {}", unused_decisions);
+    }
+    if stats.unresolved > 0 {
+        println!("  Unresolved by policy: {}", stats.unresolved);
+        std::process::exit(EXIT_UNRESOLVED);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ddmerge::diff::compare_directories;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Left and right trees holding the given files
+    fn create_trees(left: &[(&str, &str)], right: &[(&str, &str)]) -> (TempDir, TempDir) {
+        let trees = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        for (tree, files) in [(&trees.0, left), (&trees.1, right)] {
+            for (path, content) in files {
+                fs::write(tree.path().join(path), content).unwrap();
+            }
+        }
+        trees
+    }
+
+    /// Arguments merging the trees with the given flags, without cache or journal
+    fn parse_args(left: &TempDir, right: &TempDir, flags: &[&str]) -> Args {
+        let mut argv = vec!["ddmerge", "--no-cache"];
+        argv.extend(flags);
+        argv.extend([
+            left.path().to_str().unwrap(),
+            right.path().to_str().unwrap(),
+        ]);
+        Args::try_parse_from(argv).unwrap()
+    }
+
+    /// Process every difference between the trees, as the prompt loop does
+    fn process_all(session: &mut Session, left: &TempDir, right: &TempDir) -> Flow {
+        let diffs = compare_directories(left.path(), right.path()).unwrap();
+        for diff in &diffs {
+            if session.process(diff).unwrap() == Flow::Quit {
+                return Flow::Quit;
+            }
+        }
+        Flow::Continue
+    }
+
+    fn read(tree: &TempDir, path: &str) -> String {
+        fs::read_to_string(tree.path().join(path)).unwrap()
+    }
+
+    #[test]
+    fn test_process_policy_resolves_and_records() {
+        // Given: A modified file and a left-only file, with policies for both
+        // and a decisions file being recorded
+        let (left, right) = create_trees(
+            &[("a.txt", "a\nleft\n"), ("new.txt", "new\n")],
+            &[("a.txt", "a\nright\n")],
+        );
+        let scratch = TempDir::new().unwrap();
+        let record = scratch.path().join("decisions.txt");
+        let args = parse_args(
+            &left,
+            &right,
+            &["--prefer", "left", "--on-left-only", "copy"],
+        );
+        let options = CompareOptions::default();
+        let mut session = Session::new(&args, &options, RuleSet::default());
+        session.recorder = Some(Recorder::create(&record).unwrap());
+
+        // When: Processing the differences
+        let flow = process_all(&mut session, &left, &right);
+
+        // Then: Both are resolved without prompting, and the decisions are recorded
+        assert_eq!(flow, Flow::Continue);
+        assert_eq!(read(&right, "a.txt"), "a\nleft\n");
+        assert_eq!(read(&right, "new.txt"), "new\n");
+        assert_eq!(session.stats.left_choices, 1);
+        assert_eq!(session.stats.unresolved, 0);
+        let recorded = fs::read_to_string(&record).unwrap();
+        assert!(recorded.contains("left hunk "));
+        assert!(recorded.contains("copy file "));
+    }
+
+    #[test]
+    fn test_process_policy_leaves_uncovered_entries_unresolved() {
+        // Given: A right-only file with a policy that only covers modified files
+        let (left, right) = create_trees(&[], &[("only.txt", "right\n")]);
+        let args = parse_args(&left, &right, &["--prefer", "left"]);
+        let options = CompareOptions::default();
+        let mut session = Session::new(&args, &options, RuleSet::default());
+
+        // When: Processing the differences
+        process_all(&mut session, &left, &right);
+
+        // Then: The file is left alone and counted as unresolved
+        assert!(!left.path().join("only.txt").exists());
+        assert!(right.path().join("only.txt").exists());
+        assert_eq!(session.stats.unresolved, 1);
+    }
+
+    #[test]
+    fn test_process_rule_fires_before_policy() {
+        // Given: A lock file taken from the right by rule, and a left policy
+        let (left, right) = create_trees(
+            &[("deps.lock", "v1\n"), ("a.txt", "left\n")],
+            &[("deps.lock", "v2\n"), ("a.txt", "right\n")],
+        );
+        let rules = RuleSet::parse("[[rule]]\npath = \"*.lock\"\ntake = \"right\"\n").unwrap();
+        let args = parse_args(&left, &right, &["--prefer", "left"]);
+        let options = CompareOptions::default();
+        let mut session = Session::new(&args, &options, rules);
+
+        // When: Processing the differences
+        process_all(&mut session, &left, &right);
+
+        // Then: The rule decides the lock file and the policy the rest
+        assert_eq!(read(&left, "deps.lock"), "v2\n");
+        assert_eq!(read(&right, "a.txt"), "left\n");
+        assert_eq!(session.stats.rule_resolved, 1);
+    }
+
+    #[test]
+    fn test_process_replays_recorded_hunks() {
+        // Given: A decisions file taking the right side of the only hunk
+        let (left, right) = create_trees(&[("a.txt", "a\nleft\n")], &[("a.txt", "a\nright\n")]);
+        let args = parse_args(&left, &right, &[]);
+        let hunks = extract_hunks("a\nleft\n", "a\nright\n", &args.hunk_options());
+        let decisions = format!("right hunk {:016x} a.txt\n", hunk_fingerprint(&hunks[0]));
+        let options = CompareOptions::default();
+        let mut session = Session::new(&args, &options, RuleSet::default());
+        session.replay = Some(Replay::parse(&decisions).unwrap());
+
+        // When: Processing the differences without a policy
+        process_all(&mut session, &left, &right);
+
+        // Then: The recorded decision is applied without prompting
+        assert_eq!(read(&left, "a.txt"), "a\nright\n");
+        assert_eq!(session.stats.replayed, 1);
+        assert_eq!(session.replay.as_ref().map(Replay::remaining), Some(0));
+    }
+
+    #[test]
+    fn test_process_resume_skips_done_entries_and_hunks() {
+        // Given: A session that finished one file and skipped the hunk of another
+        let (left, right) = create_trees(
+            &[("done.txt", "left\n"), ("half.txt", "a\nleft\n")],
+            &[("done.txt", "right\n"), ("half.txt", "a\nright\n")],
+        );
+        let args = parse_args(&left, &right, &[]);
+        let hunks = extract_hunks("a\nleft\n", "a\nright\n", &args.hunk_options());
+        let state = format!(
+            "done done.txt\nskip hunk {:016x} half.txt\n",
+            hunk_fingerprint(&hunks[0])
+        );
+        let scratch = TempDir::new().unwrap();
+        let log_path = scratch.path().join("session.txt");
+        let options = CompareOptions::default();
+        let mut session = Session::new(&args, &options, RuleSet::default());
+        session.resumed = Some(SessionState::parse(&state).unwrap());
+        session.log = Some(SessionLog::open(&log_path, false).unwrap());
+
+        // When: Resuming without a policy
+        process_all(&mut session, &left, &right);
+
+        // Then: Neither file is asked about or changed, and the other file is logged as done
+        assert_eq!(read(&left, "done.txt"), "left\n");
+        assert_eq!(read(&left, "half.txt"), "a\nleft\n");
+        assert_eq!(session.stats.previously_done, 1);
+        let log = fs::read_to_string(&log_path).unwrap();
+        assert!(log.contains("done half.txt"));
+        assert!(!log.contains("done done.txt"));
+    }
+}
@@ -0,0 +1,487 @@
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::strategy::FileAction;
+use crate::diff::file::{hash_bytes, hash_file};
+use crate::diff::{BothOrder, Hunk, HunkChoice, ThreeWayHunk};
+
+/// First line of a decisions file; bump the version when the format changes
+const DECISIONS_HEADER: &str = "# ddmerge decisions v1";
+
+/// What a recorded decision was made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecisionKind {
+    /// One hunk of a modified text file
+    Hunk,
+    /// An entry that exists on one side only
+    File,
+    /// An entry taken as a whole (type mismatch, symlink, metadata, binary)
+    Entry,
+    /// A left-only/right-only pair detected as a rename
+    Rename,
+}
+
+impl DecisionKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            DecisionKind::Hunk => "hunk",
+            DecisionKind::File => "file",
+            DecisionKind::Entry => "entry",
+            DecisionKind::Rename => "rename",
+        }
+    }
+}
+
+impl fmt::Display for DecisionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DecisionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hunk" => Ok(DecisionKind::Hunk),
+            "file" => Ok(DecisionKind::File),
+            "entry" => Ok(DecisionKind::Entry),
+            "rename" => Ok(DecisionKind::Rename),
+            _ => Err(format!("unknown kind '{}'", s)),
+        }
+    }
+}
+
+/// A recorded answer, covering both hunk choices and file actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Left,
+    Right,
+    Skip,
+    /// Keep both versions of a hunk in the given order
+    Both(BothOrder),
+    Copy,
+    Delete,
+    /// Handle a rename pair as two unrelated one-sided entries
+    Independent,
+}
+
+impl Choice {
+    fn as_str(self) -> &'static str {
+        match self {
+            Choice::Left => "left",
+            Choice::Right => "right",
+            Choice::Skip => "skip",
+            Choice::Both(BothOrder::LeftFirst) => "left+right",
+            Choice::Both(BothOrder::RightFirst) => "right+left",
+            Choice::Copy => "copy",
+            Choice::Delete => "delete",
+            Choice::Independent => "independent",
+        }
+    }
+
+    /// Whether this answer can be given for the kind of decision
+    fn fits(self, kind: DecisionKind) -> bool {
+        match self {
+            Choice::Skip => true,
+            Choice::Left | Choice::Right => kind != DecisionKind::File,
+            Choice::Both(_) => kind == DecisionKind::Hunk,
+            Choice::Copy | Choice::Delete => kind == DecisionKind::File,
+            Choice::Independent => kind == DecisionKind::Rename,
+        }
+    }
+
+    /// The answer as a hunk choice, if it is one
+    pub fn hunk_choice(self) -> Option<HunkChoice> {
+        match self {
+            Choice::Left => Some(HunkChoice::Left),
+            Choice::Right => Some(HunkChoice::Right),
+            Choice::Skip => Some(HunkChoice::Skip),
+            Choice::Both(order) => Some(HunkChoice::Both(order)),
+            _ => None,
+        }
+    }
+
+    /// The answer as a file action, if it is one
+    pub fn file_action(self) -> Option<FileAction> {
+        match self {
+            Choice::Copy => Some(FileAction::Copy),
+            Choice::Delete => Some(FileAction::Delete),
+            Choice::Skip => Some(FileAction::Skip),
+            _ => None,
+        }
+    }
+}
+
+impl From<HunkChoice> for Choice {
+    fn from(choice: HunkChoice) -> Self {
+        match choice {
+            HunkChoice::Left => Choice::Left,
+            HunkChoice::Right => Choice::Right,
+            HunkChoice::Skip => Choice::Skip,
+            HunkChoice::Both(order) => Choice::Both(order),
+        }
+    }
+}
+
+impl From<FileAction> for Choice {
+    fn from(action: FileAction) -> Self {
+        match action {
+            FileAction::Copy => Choice::Copy,
+            FileAction::Delete => Choice::Delete,
+            FileAction::Skip => Choice::Skip,
+        }
+    }
+}
+
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Choice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Choice::Left),
+            "right" => Ok(Choice::Right),
+            "skip" => Ok(Choice::Skip),
+            "left+right" => Ok(Choice::Both(BothOrder::LeftFirst)),
+            "right+left" => Ok(Choice::Both(BothOrder::RightFirst)),
+            "copy" => Ok(Choice::Copy),
+            "delete" => Ok(Choice::Delete),
+            "independent" => Ok(Choice::Independent),
+            _ => Err(format!("unknown choice '{}'", s)),
+        }
+    }
+}
+
+/// One line of a decisions file: `<choice> <kind> <fingerprint> <path>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub choice: Choice,
+    pub kind: DecisionKind,
+    /// Fingerprint of the content the decision was made for
+    pub fingerprint: u64,
+    /// Relative path with `/` separators
+    pub path: String,
+}
+
+impl Decision {
+    pub fn new(choice: Choice, kind: DecisionKind, fingerprint: u64, path: &Path) -> Self {
+        Self {
+            choice,
+            kind,
+            fingerprint,
+            path: path_key(path),
+        }
+    }
+
+    /// Parse one line; the path comes last so it may contain spaces
+    pub(super) fn parse(line: &str) -> Result<Self> {
+        let mut fields = line.splitn(4, ' ');
+        let (Some(choice), Some(kind), Some(fingerprint), Some(path)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            bail!("expected <choice> <kind> <fingerprint> <path>");
+        };
+        let choice: Choice = choice.parse().map_err(anyhow::Error::msg)?;
+        let kind: DecisionKind = kind.parse().map_err(anyhow::Error::msg)?;
+        if !choice.fits(kind) {
+            bail!("choice '{}' is not valid for a {}", choice, kind);
+        }
+        let fingerprint = u64::from_str_radix(fingerprint, 16)
+            .with_context(|| format!("invalid fingerprint '{}'", fingerprint))?;
+        Ok(Self {
+            choice,
+            kind,
+            fingerprint,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:016x} {}",
+            self.choice, self.kind, self.fingerprint, self.path
+        )
+    }
+}
+
+/// Relative path as written to a decisions file, independent of the platform
+pub(super) fn path_key(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Appends decisions to a file as they are made
+///
+/// Every line is flushed right away, so quitting or a crash keeps the
+/// decisions taken so far.
+#[derive(Debug)]
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    /// Create (or truncate) a decisions file
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut file = File::create(path)
+            .with_context(|| format!("Failed to create decisions file {}", path.display()))?;
+        writeln!(file, "{}", DECISIONS_HEADER)?;
+        writeln!(file, "# <choice> <kind> <fingerprint> <path>")?;
+        Ok(Self { file })
+    }
+
+    pub fn record(&mut self, decision: &Decision) -> Result<()> {
+        writeln!(self.file, "{}", decision)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Result of looking up a recorded decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    /// A decision was recorded for this exact content
+    Found(Choice),
+    /// Decisions exist for the path, but the content has changed since
+    Mismatch,
+    /// Nothing was recorded for the path
+    Missing,
+}
+
+/// Decisions loaded from a file, consumed as they are replayed
+#[derive(Debug, Default)]
+pub struct Replay {
+    pending: HashMap<(DecisionKind, String), VecDeque<(u64, Choice)>>,
+}
+
+impl Replay {
+    /// Load a decisions file written by `Recorder` (possibly edited by hand)
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read decisions file {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid decisions file {}", path.display()))
+    }
+
+    /// Parse decisions, skipping blank lines and `#` comments
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut replay = Self::default();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim_end();
+            if line.trim_start().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let decision = Decision::parse(line).with_context(|| format!("line {}", index + 1))?;
+            replay
+                .pending
+                .entry((decision.kind, decision.path))
+                .or_default()
+                .push_back((decision.fingerprint, decision.choice));
+        }
+        Ok(replay)
+    }
+
+    /// Take the decision recorded for a path and content fingerprint
+    ///
+    /// Hunks of one file are matched by fingerprint rather than position, so
+    /// a hunk that disappeared does not shift the decisions of the others.
+    pub fn take(&mut self, kind: DecisionKind, path: &Path, fingerprint: u64) -> Lookup {
+        let Some(queue) = self.pending.get_mut(&(kind, path_key(path))) else {
+            return Lookup::Missing;
+        };
+        match queue.iter().position(|(fp, _)| *fp == fingerprint) {
+            Some(index) => {
+                let (_, choice) = queue.remove(index).expect("index is in range");
+                Lookup::Found(choice)
+            }
+            None => Lookup::Mismatch,
+        }
+    }
+
+    /// Number of decisions that were not replayed
+    pub fn remaining(&self) -> usize {
+        self.pending.values().map(VecDeque::len).sum()
+    }
+}
+
+/// Fingerprint of a two-way hunk's changed lines
+pub fn hunk_fingerprint(hunk: &Hunk) -> u64 {
+    lines_fingerprint(&[&hunk.left_lines, &hunk.right_lines])
+}
+
+/// Fingerprint of a three-way hunk's base, left and right lines
+pub fn three_way_fingerprint(hunk: &ThreeWayHunk) -> u64 {
+    lines_fingerprint(&[&hunk.base_lines, &hunk.left_lines, &hunk.right_lines])
+}
+
+fn lines_fingerprint(sides: &[&Vec<String>]) -> u64 {
+    let mut bytes = Vec::new();
+    for lines in sides {
+        for line in lines.iter() {
+            bytes.extend_from_slice(line.as_bytes());
+            bytes.push(b'\n');
+        }
+        bytes.push(0);
+    }
+    hash_bytes(&bytes) as u64
+}
+
+/// Fingerprint of an entry as a whole, from what each side holds
+///
+/// Files contribute their content hash, symlinks their target, and
+/// directories only their type, so large trees are not hashed.
+pub fn entry_fingerprint(left: &Path, right: &Path) -> Result<u64> {
+    let mut bytes = Vec::new();
+    for path in [left, right] {
+        let side = match fs::symlink_metadata(path) {
+            Ok(m) if m.is_file() => format!("file {:032x}", hash_file(path)?),
+            Ok(m) if m.is_symlink() => format!("link {}", fs::read_link(path)?.display()),
+            Ok(_) => "dir".to_string(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => "-".to_string(),
+            Err(e) => return Err(e.into()),
+        };
+        bytes.extend_from_slice(side.as_bytes());
+        bytes.push(0);
+    }
+    Ok(hash_bytes(&bytes) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    // ==================== Format ====================
+
+    #[test]
+    fn test_recorded_decisions_replay() {
+        // Given: Decisions recorded to a file
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("decisions");
+        let mut recorder = Recorder::create(&path).unwrap();
+        let file = PathBuf::from("src/my file.rs");
+        recorder
+            .record(&Decision::new(Choice::Left, DecisionKind::Hunk, 1, &file))
+            .unwrap();
+        recorder
+            .record(&Decision::new(Choice::Skip, DecisionKind::Hunk, 2, &file))
+            .unwrap();
+        recorder
+            .record(&Decision::new(
+                Choice::Copy,
+                DecisionKind::File,
+                3,
+                Path::new("new.txt"),
+            ))
+            .unwrap();
+
+        // When: Loading them for replay
+        let mut replay = Replay::from_file(&path).unwrap();
+
+        // Then: Each decision is found by path and fingerprint, in any order
+        assert_eq!(replay.remaining(), 3);
+        assert_eq!(
+            replay.take(DecisionKind::Hunk, &file, 2),
+            Lookup::Found(Choice::Skip)
+        );
+        assert_eq!(
+            replay.take(DecisionKind::Hunk, &file, 1),
+            Lookup::Found(Choice::Left)
+        );
+        assert_eq!(
+            replay.take(DecisionKind::File, Path::new("new.txt"), 3),
+            Lookup::Found(Choice::Copy)
+        );
+        assert_eq!(replay.remaining(), 0);
+    }
+
+    #[test]
+    fn test_changed_content_is_a_mismatch() {
+        // Given: A decision for one hunk of a file
+        let mut replay = Replay::parse("right hunk 00000000000000aa a.txt\n").unwrap();
+
+        // When: Looking up a hunk with other content, or another file
+        let changed = replay.take(DecisionKind::Hunk, Path::new("a.txt"), 0xbb);
+        let other = replay.take(DecisionKind::Hunk, Path::new("b.txt"), 0xaa);
+
+        // Then: The first is a mismatch and the second has no decision
+        assert_eq!(changed, Lookup::Mismatch);
+        assert_eq!(other, Lookup::Missing);
+    }
+
+    #[test]
+    fn test_parse_errors_report_line() {
+        // Given: A hand-edited file with an action that does not fit its kind
+        let content = "# comment\n\nleft hunk 01 a.txt\ncopy hunk 02 a.txt\n";
+
+        // When: Parsing it
+        let err = Replay::parse(content).unwrap_err();
+
+        // Then: The error names the offending line
+        assert!(format!("{:#}", err).contains("line 4"));
+    }
+
+    #[test]
+    fn test_both_choice_round_trips_for_hunks_only() {
+        // Given: A file keeping both versions of a hunk, and one for a file entry
+        let content = "right+left hunk 01 a.txt\n";
+
+        // When: Parsing them
+        let mut replay = Replay::parse(content).unwrap();
+        let err = Replay::parse("left+right file 02 b.txt\n");
+
+        // Then: The hunk decision is read back and the file one is refused
+        let both = Choice::Both(BothOrder::RightFirst);
+        assert_eq!(
+            replay.take(DecisionKind::Hunk, Path::new("a.txt"), 1),
+            Lookup::Found(both)
+        );
+        assert_eq!(both.to_string(), "right+left");
+        assert!(err.is_err());
+    }
+
+    // ==================== Fingerprints ====================
+
+    #[test]
+    fn test_hunk_fingerprint_depends_on_content() {
+        // Given: Hunks that differ only in which side holds a line
+        let a = Hunk::for_test(&["x\n"], &["y\n"]);
+        let b = Hunk::for_test(&["x\n", "y\n"], &[]);
+
+        // When/Then: Equal content gives equal fingerprints, different content does not
+        assert_eq!(hunk_fingerprint(&a), hunk_fingerprint(&a.clone()));
+        assert_ne!(hunk_fingerprint(&a), hunk_fingerprint(&b));
+    }
+
+    #[test]
+    fn test_entry_fingerprint_follows_content() {
+        // Given: A file that exists on the left only
+        let dir = TempDir::new().unwrap();
+        let left = dir.path().join("left.txt");
+        let right = dir.path().join("missing.txt");
+        fs::write(&left, "one").unwrap();
+        let before = entry_fingerprint(&left, &right).unwrap();
+
+        // When: Its content changes
+        fs::write(&left, "two").unwrap();
+        let after = entry_fingerprint(&left, &right).unwrap();
+
+        // Then: The fingerprint changes
+        assert_ne!(before, after);
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::diff::file::hash_file;
+
+/// What a path held when it was read
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Absent,
+    File {
+        size: u64,
+        modified: Option<SystemTime>,
+        hash: u128,
+    },
+    /// Only the directory itself is compared, not its contents
+    Dir {
+        modified: Option<SystemTime>,
+    },
+    Link {
+        target: PathBuf,
+    },
+}
+
+/// Fingerprint of a path taken when it was read for a prompt
+///
+/// Writes check it again first, so changes made by an editor or a build
+/// while the prompt was open are never overwritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileState {
+    path: PathBuf,
+    state: State,
+}
+
+impl FileState {
+    /// Take the fingerprint of a path as it is now
+    pub fn capture(path: &Path) -> Result<Self> {
+        let state = read_state(path)
+            .with_context(|| format!("Failed to read state of {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            state,
+        })
+    }
+
+    /// Check that the path still matches the fingerprint
+    pub fn verify(&self) -> Result<()> {
+        if Self::capture(&self.path)?.state != self.state {
+            return Err(ChangedOnDisk {
+                path: self.path.clone(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Check every fingerprint before a write
+pub fn check_unchanged(expected: &[FileState]) -> Result<()> {
+    expected.iter().try_for_each(FileState::verify)
+}
+
+/// Error returned when a path changed after it was read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedOnDisk {
+    pub path: PathBuf,
+}
+
+impl fmt::Display for ChangedOnDisk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} was changed by another program since it was read; nothing was written",
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for ChangedOnDisk {}
+
+fn read_state(path: &Path) -> Result<State> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(State::Absent),
+        Err(e) => return Err(e.into()),
+    };
+    let state = if metadata.is_symlink() {
+        State::Link {
+            target: fs::read_link(path)?,
+        }
+    } else if metadata.is_dir() {
+        State::Dir {
+            modified: metadata.modified().ok(),
+        }
+    } else {
+        State::File {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            hash: hash_file(path)?,
+        }
+    };
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_unchanged_paths_verify() {
+        // Given: A file and a missing path
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "content").unwrap();
+        let states = [
+            FileState::capture(&file).unwrap(),
+            FileState::capture(&dir.path().join("none")).unwrap(),
+        ];
+
+        // When/Then: Nothing changed, so both still match
+        check_unchanged(&states).unwrap();
+    }
+
+    #[test]
+    fn test_changed_file_is_reported() {
+        // Given: A file that is rewritten with content of the same size
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "before").unwrap();
+        let state = FileState::capture(&file).unwrap();
+        fs::write(&file, "after!").unwrap();
+
+        // When: Verifying it
+        let err = state.verify().unwrap_err();
+
+        // Then: The error names the changed path
+        let changed = err.downcast_ref::<ChangedOnDisk>().unwrap();
+        assert_eq!(changed.path, file);
+    }
+
+    #[test]
+    fn test_created_path_is_reported() {
+        // Given: A path that was absent when captured
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file.txt");
+        let state = FileState::capture(&file).unwrap();
+
+        // When: Something creates it
+        fs::write(&file, "new").unwrap();
+
+        // Then: It no longer matches
+        assert!(state.verify().is_err());
+    }
+}
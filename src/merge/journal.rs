@@ -0,0 +1,372 @@
+use anyhow::{bail, Context, Result};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::strategy::{copy_entry, remove_entry};
+use crate::diff::cache_dir;
+
+/// Name of the index file inside the journal directory
+const INDEX_FILE: &str = "index";
+
+/// Number of sessions whose operations are kept; older ones are dropped
+const KEPT_SESSIONS: usize = 10;
+
+/// What a path held before an operation changed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Saved {
+    File,
+    Dir,
+    Link,
+    Absent,
+}
+
+impl Saved {
+    fn as_str(self) -> &'static str {
+        match self {
+            Saved::File => "file",
+            Saved::Dir => "dir",
+            Saved::Link => "link",
+            Saved::Absent => "absent",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "file" => Some(Saved::File),
+            "dir" => Some(Saved::Dir),
+            "link" => Some(Saved::Link),
+            "absent" => Some(Saved::Absent),
+            _ => None,
+        }
+    }
+}
+
+/// One applied change and the original state of every path it touched
+#[derive(Debug, Clone)]
+struct Operation {
+    /// `<session>.<sequence>`, also the name of the backup directory
+    id: String,
+    description: String,
+    paths: Vec<(Saved, PathBuf)>,
+}
+
+impl Operation {
+    fn session(&self) -> &str {
+        self.id
+            .rsplit_once('.')
+            .map_or(&self.id, |(session, _)| session)
+    }
+}
+
+/// Journal of the changes applied by one merge session
+///
+/// Before an operation changes the trees, the original entries are copied
+/// into a backup area and the operation is appended to an index, so it can
+/// be reverted later with `undo`.
+#[derive(Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    session: String,
+    sequence: usize,
+}
+
+impl Journal {
+    /// Start a new session in a journal directory, dropping old sessions
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create journal {}", dir.display()))?;
+        let session = format!(
+            "{}-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            std::process::id()
+        );
+
+        let operations = read_index(dir)?;
+        let mut sessions: Vec<&str> = operations.iter().map(Operation::session).collect();
+        sessions.dedup();
+        if sessions.len() >= KEPT_SESSIONS {
+            let first_kept = sessions[sessions.len() + 1 - KEPT_SESSIONS].to_string();
+            let position = operations
+                .iter()
+                .position(|op| op.session() == first_kept)
+                .unwrap_or(operations.len());
+            for op in &operations[..position] {
+                remove_entry(&dir.join(&op.id))?;
+            }
+            write_index(dir, &operations[position..])?;
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            session,
+            sequence: 0,
+        })
+    }
+
+    /// Back up the paths an operation is about to change and log it
+    pub fn record(&mut self, description: &str, paths: &[PathBuf]) -> Result<()> {
+        self.sequence += 1;
+        let id = format!("{}.{}", self.session, self.sequence);
+        let backup_dir = self.dir.join(&id);
+        fs::create_dir_all(&backup_dir)?;
+
+        let mut saved_paths = Vec::new();
+        for (index, path) in paths.iter().enumerate() {
+            let path = std::path::absolute(path)?;
+            let saved = save(&path, &backup_dir.join(index.to_string()))
+                .with_context(|| format!("Failed to back up {}", path.display()))?;
+            saved_paths.push((saved, path));
+        }
+
+        // The index is written last so it never names a missing backup
+        let op = Operation {
+            id,
+            description: description.to_string(),
+            paths: saved_paths,
+        };
+        let mut index = File::options()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(INDEX_FILE))?;
+        index.write_all(format_operation(&op)?.as_bytes())?;
+        index.sync_all()?;
+        Ok(())
+    }
+
+    /// Revert the latest operation of this session
+    ///
+    /// Returns its description, or None if nothing is left to undo.
+    pub fn undo_last(&mut self) -> Result<Option<String>> {
+        let operations = read_index(&self.dir)?;
+        match operations.last() {
+            Some(op) if op.session() == self.session => Ok(undo(&self.dir, operations, 1)?.pop()),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Default journal directory, under the cache directory
+pub fn default_journal_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("journal"))
+}
+
+/// Revert the last `count` operations, newest first
+///
+/// Returns the descriptions of the reverted operations.
+pub fn undo_operations(dir: &Path, count: usize) -> Result<Vec<String>> {
+    let operations = read_index(dir)?;
+    undo(dir, operations, count)
+}
+
+/// Revert every operation of the most recent session
+pub fn undo_last_session(dir: &Path) -> Result<Vec<String>> {
+    let operations = read_index(dir)?;
+    let count = match operations.last() {
+        Some(last) => {
+            let session = last.session();
+            operations
+                .iter()
+                .rev()
+                .take_while(|op| op.session() == session)
+                .count()
+        }
+        None => 0,
+    };
+    undo(dir, operations, count)
+}
+
+fn undo(dir: &Path, mut operations: Vec<Operation>, count: usize) -> Result<Vec<String>> {
+    let mut undone = Vec::new();
+    for _ in 0..count {
+        let Some(op) = operations.pop() else {
+            break;
+        };
+        let backup_dir = dir.join(&op.id);
+        for (index, (saved, path)) in op.paths.iter().enumerate().rev() {
+            restore(*saved, &backup_dir.join(index.to_string()), path)
+                .with_context(|| format!("Failed to restore {}", path.display()))?;
+        }
+        // Drop the operation from the index before its backups, so an
+        // interrupted undo can be retried
+        write_index(dir, &operations)?;
+        remove_entry(&backup_dir)?;
+        undone.push(op.description);
+    }
+    Ok(undone)
+}
+
+/// Copy an entry into the backup area, keeping a file's modification time
+fn save(path: &Path, backup: &Path) -> Result<Saved> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Saved::Absent),
+        Err(e) => return Err(e.into()),
+    };
+    copy_entry(path, backup, false)?;
+    if metadata.is_symlink() {
+        Ok(Saved::Link)
+    } else if metadata.is_dir() {
+        Ok(Saved::Dir)
+    } else {
+        // Opened read-only so read-only files can be handled too
+        File::open(backup)?.set_modified(metadata.modified()?)?;
+        Ok(Saved::File)
+    }
+}
+
+/// Put a saved entry back in place of whatever the path holds now
+fn restore(saved: Saved, backup: &Path, path: &Path) -> Result<()> {
+    if saved == Saved::Absent {
+        return remove_entry(path);
+    }
+    copy_entry(backup, path, false)?;
+    if saved == Saved::File {
+        let modified = fs::metadata(backup)?.modified()?;
+        File::open(path)?.set_modified(modified)?;
+    }
+    Ok(())
+}
+
+/// Index lines of an operation: `op <id> <description>` followed by one
+/// `path <id> <saved> <absolute path>` line per path, tab-separated
+fn format_operation(op: &Operation) -> Result<String> {
+    let mut lines = format!("op\t{}\t{}\n", op.id, op.description.replace('\n', " "));
+    for (saved, path) in &op.paths {
+        let Some(path) = path.to_str().filter(|p| !p.contains('\n')) else {
+            bail!("Cannot journal path {}", path.display());
+        };
+        lines.push_str(&format!("path\t{}\t{}\t{}\n", op.id, saved.as_str(), path));
+    }
+    Ok(lines)
+}
+
+fn read_index(dir: &Path) -> Result<Vec<Operation>> {
+    let path = dir.join(INDEX_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read journal {}", path.display()))
+        }
+    };
+
+    let mut operations: Vec<Operation> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let fields: Vec<&str> = line.splitn(4, '\t').collect();
+        match fields.as_slice() {
+            ["op", id, description] => operations.push(Operation {
+                id: id.to_string(),
+                description: description.to_string(),
+                paths: Vec::new(),
+            }),
+            ["path", id, saved, file] => {
+                let (Some(op), Some(saved)) = (operations.last_mut(), Saved::parse(saved)) else {
+                    bail!("Invalid journal {} at line {}", path.display(), index + 1);
+                };
+                if op.id != *id {
+                    bail!("Invalid journal {} at line {}", path.display(), index + 1);
+                }
+                op.paths.push((saved, PathBuf::from(file)));
+            }
+            _ => bail!("Invalid journal {} at line {}", path.display(), index + 1),
+        }
+    }
+    Ok(operations)
+}
+
+/// Rewrite the index, replacing it atomically
+fn write_index(dir: &Path, operations: &[Operation]) -> Result<()> {
+    let mut content = String::new();
+    for op in operations {
+        content.push_str(&format_operation(op)?);
+    }
+    let path = dir.join(INDEX_FILE);
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, &path)
+        .with_context(|| format!("Failed to write journal {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_undo_restores_overwritten_and_created_files() {
+        // Given: An operation that overwrites one file and creates another
+        let dir = TempDir::new().unwrap();
+        let journal_dir = dir.path().join("journal");
+        let changed = dir.path().join("changed.txt");
+        let created = dir.path().join("created.txt");
+        fs::write(&changed, "original").unwrap();
+        let mut journal = Journal::open(&journal_dir).unwrap();
+        journal
+            .record("merge", &[changed.clone(), created.clone()])
+            .unwrap();
+        fs::write(&changed, "merged").unwrap();
+        fs::write(&created, "new").unwrap();
+
+        // When: Undoing it
+        let undone = undo_operations(&journal_dir, 1).unwrap();
+
+        // Then: Both paths are back to their original state
+        assert_eq!(undone, vec!["merge"]);
+        assert_eq!(fs::read_to_string(&changed).unwrap(), "original");
+        assert!(!created.exists());
+        assert!(read_index(&journal_dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undo_restores_deleted_directory() {
+        // Given: A directory deleted after being journaled
+        let dir = TempDir::new().unwrap();
+        let journal_dir = dir.path().join("journal");
+        let tree = dir.path().join("tree");
+        fs::create_dir_all(tree.join("sub")).unwrap();
+        fs::write(tree.join("sub/file.txt"), "content").unwrap();
+        let mut journal = Journal::open(&journal_dir).unwrap();
+        journal
+            .record("delete tree", std::slice::from_ref(&tree))
+            .unwrap();
+        fs::remove_dir_all(&tree).unwrap();
+
+        // When: Undoing the last operation of the session
+        let undone = journal.undo_last().unwrap();
+
+        // Then: The directory comes back with its content
+        assert_eq!(undone.as_deref(), Some("delete tree"));
+        assert_eq!(
+            fs::read_to_string(tree.join("sub/file.txt")).unwrap(),
+            "content"
+        );
+        assert_eq!(journal.undo_last().unwrap(), None);
+    }
+
+    #[test]
+    fn test_undo_last_session_reverts_newest_first() {
+        // Given: Two operations on the same file in one session
+        let dir = TempDir::new().unwrap();
+        let journal_dir = dir.path().join("journal");
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "v1").unwrap();
+        let mut journal = Journal::open(&journal_dir).unwrap();
+        journal
+            .record("first", std::slice::from_ref(&file))
+            .unwrap();
+        fs::write(&file, "v2").unwrap();
+        journal
+            .record("second", std::slice::from_ref(&file))
+            .unwrap();
+        fs::write(&file, "v3").unwrap();
+
+        // When: Undoing the whole session
+        let undone = undo_last_session(&journal_dir).unwrap();
+
+        // Then: Both are reverted in reverse order
+        assert_eq!(undone, vec!["second", "first"]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "v1");
+    }
+}
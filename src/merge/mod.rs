@@ -1,6 +1,21 @@
+mod decisions;
+mod guard;
+mod journal;
+mod policy;
+mod resume;
+mod rules;
 mod strategy;
 
+pub use decisions::{
+    entry_fingerprint, hunk_fingerprint, three_way_fingerprint, Choice, Decision, DecisionKind,
+    Lookup, Recorder, Replay,
+};
+pub use guard::{check_unchanged, ChangedOnDisk, FileState};
+pub use journal::{default_journal_dir, undo_last_session, undo_operations, Journal};
+pub use policy::{choose_side, Policy, Prefer};
+pub use resume::{default_session_path, SessionLog, SessionState};
+pub use rules::{Rule, RuleAction, RuleSet};
 pub use strategy::{
-    apply_file_action, apply_hunk_merge, copy_unchanged_files, perform_merge, FileAction,
-    MergeAction,
+    apply_entry_choice, apply_file_action, apply_hunk_merge, apply_metadata_choice, apply_rename,
+    copy_unchanged_files, perform_merge, FileAction, MergeAction,
 };
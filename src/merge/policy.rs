@@ -0,0 +1,213 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use walkdir::WalkDir;
+
+use super::strategy::FileAction;
+use crate::diff::{DiffType, HunkChoice};
+
+/// Which side wins when both sides have a version of an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefer {
+    /// Always take the left version
+    Left,
+    /// Always take the right version
+    Right,
+    /// Take the version with the later modification time
+    Newer,
+    /// Take the version with more bytes
+    Larger,
+}
+
+impl FromStr for Prefer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Prefer::Left),
+            "right" => Ok(Prefer::Right),
+            "newer" => Ok(Prefer::Newer),
+            "larger" => Ok(Prefer::Larger),
+            _ => Err(format!(
+                "invalid value '{}' (expected left, right, newer or larger)",
+                s
+            )),
+        }
+    }
+}
+
+impl FromStr for FileAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "copy" => Ok(FileAction::Copy),
+            "delete" => Ok(FileAction::Delete),
+            "skip" => Ok(FileAction::Skip),
+            _ => Err(format!(
+                "invalid value '{}' (expected copy, delete or skip)",
+                s
+            )),
+        }
+    }
+}
+
+/// Decisions taken without prompting
+///
+/// Each field covers one kind of difference; None leaves that kind
+/// undecided. An active policy never prompts, so undecided entries are
+/// reported as unresolved instead.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    /// Side to take for modified files, hunks, symlinks, metadata and renames
+    pub prefer: Option<Prefer>,
+    /// Action for entries that exist only in the left directory
+    pub on_left_only: Option<FileAction>,
+    /// Action for entries that exist only in the right directory
+    pub on_right_only: Option<FileAction>,
+    /// Side to take when the entry types differ (defaults to `prefer`)
+    pub on_type_mismatch: Option<Prefer>,
+}
+
+impl Policy {
+    /// Whether any decision was configured (then nothing is prompted)
+    pub fn is_active(&self) -> bool {
+        self.prefer.is_some()
+            || self.on_left_only.is_some()
+            || self.on_right_only.is_some()
+            || self.on_type_mismatch.is_some()
+    }
+
+    /// Configured action for a one-sided entry
+    pub fn only_action(&self, diff_type: &DiffType) -> Option<FileAction> {
+        match diff_type {
+            DiffType::LeftOnly => self.on_left_only,
+            DiffType::RightOnly => self.on_right_only,
+            _ => None,
+        }
+    }
+
+    /// Configured preference for an entry present on both sides
+    pub fn preference(&self, diff_type: &DiffType) -> Option<Prefer> {
+        match diff_type {
+            DiffType::LeftOnly | DiffType::RightOnly => None,
+            DiffType::TypeMismatch => self.on_type_mismatch.or(self.prefer),
+            _ => self.prefer,
+        }
+    }
+}
+
+/// Pick a side by preference, comparing the two versions if needed
+///
+/// Returns `Skip` when newer/larger cannot tell the versions apart.
+pub fn choose_side(prefer: Prefer, left: &Path, right: &Path) -> Result<HunkChoice> {
+    let ordering = match prefer {
+        Prefer::Left => return Ok(HunkChoice::Left),
+        Prefer::Right => return Ok(HunkChoice::Right),
+        Prefer::Newer => {
+            let left_time = fs::symlink_metadata(left)?.modified()?;
+            let right_time = fs::symlink_metadata(right)?.modified()?;
+            left_time.cmp(&right_time)
+        }
+        Prefer::Larger => entry_size(left)?.cmp(&entry_size(right)?),
+    };
+    Ok(match ordering {
+        std::cmp::Ordering::Greater => HunkChoice::Left,
+        std::cmp::Ordering::Less => HunkChoice::Right,
+        std::cmp::Ordering::Equal => HunkChoice::Skip,
+    })
+}
+
+/// Size of a file or link, or the total size of the files in a directory
+fn entry_size(path: &Path) -> Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in WalkDir::new(path) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_values() {
+        // Given/When: Parsing command line values
+
+        // Then: Known values parse and unknown ones are rejected
+        assert_eq!("newer".parse::<Prefer>(), Ok(Prefer::Newer));
+        assert_eq!("delete".parse::<FileAction>(), Ok(FileAction::Delete));
+        assert!("oldest".parse::<Prefer>().is_err());
+        assert!("move".parse::<FileAction>().is_err());
+    }
+
+    #[test]
+    fn test_type_mismatch_falls_back_to_prefer() {
+        // Given: A policy with only a general preference
+        let policy = Policy {
+            prefer: Some(Prefer::Right),
+            ..Policy::default()
+        };
+
+        // When: Asking for decisions
+
+        // Then: Type mismatches use it and one-sided entries stay undecided
+        assert!(policy.is_active());
+        assert_eq!(
+            policy.preference(&DiffType::TypeMismatch),
+            Some(Prefer::Right)
+        );
+        assert_eq!(policy.preference(&DiffType::LeftOnly), None);
+        assert_eq!(policy.only_action(&DiffType::LeftOnly), None);
+    }
+
+    #[test]
+    fn test_choose_side_newer() {
+        // Given: A right file that is older than the left one
+        let dir = TempDir::new().unwrap();
+        let left = dir.path().join("left");
+        let right = dir.path().join("right");
+        fs::write(&left, "new").unwrap();
+        fs::write(&right, "old").unwrap();
+        File::options()
+            .write(true)
+            .open(&right)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+
+        // When: Preferring the newer version
+        let choice = choose_side(Prefer::Newer, &left, &right).unwrap();
+
+        // Then: Left wins
+        assert_eq!(choice, HunkChoice::Left);
+    }
+
+    #[test]
+    fn test_choose_side_larger_tie_is_skip() {
+        // Given: Two files of equal size
+        let dir = TempDir::new().unwrap();
+        let left = dir.path().join("left");
+        let right = dir.path().join("right");
+        fs::write(&left, "aaa").unwrap();
+        fs::write(&right, "bbb").unwrap();
+
+        // When: Preferring the larger version
+        let choice = choose_side(Prefer::Larger, &left, &right).unwrap();
+
+        // Then: The tie stays unresolved
+        assert_eq!(choice, HunkChoice::Skip);
+    }
+}
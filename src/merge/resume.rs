@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::decisions::{path_key, Choice, Decision, DecisionKind};
+use crate::diff::cache_dir;
+use crate::diff::file::hash_bytes;
+
+/// First line of a session file; bump the version when the format changes
+const SESSION_HEADER: &str = "# ddmerge session v1";
+
+/// Progress of an interrupted merge, loaded for `--resume`
+///
+/// Lines are either `done <path>` for entries that were fully handled, or
+/// `skip hunk <fingerprint> <path>` for hunks skipped in files that were
+/// left half done.
+#[derive(Debug, Default)]
+pub struct SessionState {
+    done: HashSet<String>,
+    skipped_hunks: HashSet<(String, u64)>,
+}
+
+impl SessionState {
+    /// Load a session file, or None if there is no saved session
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read session file {}", path.display()))
+            }
+        };
+        Self::parse(&content)
+            .map(Some)
+            .with_context(|| format!("Invalid session file {}", path.display()))
+    }
+
+    /// Parse a session file, skipping blank lines and `#` comments
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut state = Self::default();
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(path) = line.strip_prefix("done ") {
+                state.done.insert(path.to_string());
+                continue;
+            }
+            let decision = Decision::parse(line).with_context(|| format!("line {}", index + 1))?;
+            if decision.kind == DecisionKind::Hunk && decision.choice == Choice::Skip {
+                state
+                    .skipped_hunks
+                    .insert((decision.path, decision.fingerprint));
+            }
+        }
+        Ok(state)
+    }
+
+    /// Whether the entry was fully handled in the saved session
+    pub fn is_done(&self, path: &Path) -> bool {
+        self.done.contains(&path_key(path))
+    }
+
+    /// Whether the hunk was skipped in the saved session
+    pub fn is_skipped_hunk(&self, path: &Path, fingerprint: u64) -> bool {
+        self.skipped_hunks.contains(&(path_key(path), fingerprint))
+    }
+}
+
+/// Writes the progress of the current merge as it is made
+///
+/// Every line is flushed right away, so quitting or a crash keeps the
+/// progress made so far.
+#[derive(Debug)]
+pub struct SessionLog {
+    file: File,
+}
+
+impl SessionLog {
+    /// Start a new session file, or append to it when resuming
+    pub fn open(path: &Path, append: bool) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = if append && path.exists() {
+            File::options().append(true).open(path)
+        } else {
+            File::create(path).and_then(|mut file| {
+                writeln!(file, "{}", SESSION_HEADER)?;
+                Ok(file)
+            })
+        };
+        let file =
+            file.with_context(|| format!("Failed to write session file {}", path.display()))?;
+        Ok(Self { file })
+    }
+
+    /// Note a hunk that was skipped
+    pub fn skipped_hunk(&mut self, path: &Path, fingerprint: u64) -> Result<()> {
+        let decision = Decision::new(Choice::Skip, DecisionKind::Hunk, fingerprint, path);
+        self.write_line(&decision.to_string())
+    }
+
+    /// Note an entry that was fully handled
+    pub fn done(&mut self, path: &Path) -> Result<()> {
+        self.write_line(&format!("done {}", path_key(path)))
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Default session file for a pair of directories, under the cache directory
+pub fn default_session_path(left: &Path, right: &Path) -> Result<Option<PathBuf>> {
+    let Some(dir) = cache_dir() else {
+        return Ok(None);
+    };
+    let mut key = std::path::absolute(left)?.into_os_string();
+    key.push("\0");
+    key.push(std::path::absolute(right)?);
+    let hash = hash_bytes(key.as_encoded_bytes()) as u64;
+    Ok(Some(dir.join("sessions").join(format!("{:016x}", hash))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_logged_progress_is_resumed() {
+        // Given: A session that handled one entry and skipped a hunk in another
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sessions/abc");
+        let mut log = SessionLog::open(&path, false).unwrap();
+        log.done(Path::new("docs/a b.md")).unwrap();
+        log.skipped_hunk(Path::new("src/main.rs"), 0x2a).unwrap();
+        drop(log);
+
+        // When: Loading it
+        let state = SessionState::load(&path).unwrap().unwrap();
+
+        // Then: Both are remembered, keyed by path and fingerprint
+        assert!(state.is_done(Path::new("docs/a b.md")));
+        assert!(!state.is_done(Path::new("src/main.rs")));
+        assert!(state.is_skipped_hunk(Path::new("src/main.rs"), 0x2a));
+        assert!(!state.is_skipped_hunk(Path::new("src/main.rs"), 0x2b));
+    }
+
+    #[test]
+    fn test_resumed_log_appends() {
+        // Given: A saved session
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("session");
+        SessionLog::open(&path, false)
+            .unwrap()
+            .done(Path::new("a.txt"))
+            .unwrap();
+
+        // When: Reopening it to resume and handling another entry
+        SessionLog::open(&path, true)
+            .unwrap()
+            .done(Path::new("b.txt"))
+            .unwrap();
+
+        // Then: Both entries are in the session
+        let state = SessionState::load(&path).unwrap().unwrap();
+        assert!(state.is_done(Path::new("a.txt")));
+        assert!(state.is_done(Path::new("b.txt")));
+    }
+
+    #[test]
+    fn test_missing_session() {
+        // Given/When: Loading a session file that does not exist
+        let dir = TempDir::new().unwrap();
+        let state = SessionState::load(&dir.path().join("none")).unwrap();
+
+        // Then: There is nothing to resume
+        assert!(state.is_none());
+    }
+}
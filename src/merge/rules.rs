@@ -0,0 +1,372 @@
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::path::Path;
+
+use super::policy::Prefer;
+use super::strategy::FileAction;
+use crate::diff::glob::{path_to_slash, GlobPattern};
+use crate::diff::{DiffEntry, DiffType};
+
+/// What a rule does with one kind of difference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    /// Apply a file-level action to a one-sided entry
+    File(FileAction),
+    /// Take one side of a two-sided entry (and all of its hunks)
+    Take(Prefer),
+    /// Leave both sides untouched without prompting
+    Keep,
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleAction::File(FileAction::Copy) => write!(f, "copy"),
+            RuleAction::File(FileAction::Delete) => write!(f, "delete"),
+            RuleAction::File(FileAction::Skip) | RuleAction::Keep => write!(f, "keep"),
+            RuleAction::Take(prefer) => write!(f, "take {}", prefer_name(*prefer)),
+        }
+    }
+}
+
+fn prefer_name(prefer: Prefer) -> &'static str {
+    match prefer {
+        Prefer::Left => "left",
+        Prefer::Right => "right",
+        Prefer::Newer => "newer",
+        Prefer::Larger => "larger",
+    }
+}
+
+/// A path glob with per-kind resolutions
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// The glob as written in the config file
+    pub pattern: String,
+    glob: GlobPattern,
+    /// Line of the `[[rule]]` header, for logging which rule fired
+    pub line: usize,
+    /// Resolution for `Modified` entries (also symlinks, metadata and renames)
+    pub modified: Option<RuleAction>,
+    /// Resolution for `LeftOnly` entries
+    pub left_only: Option<RuleAction>,
+    /// Resolution for `RightOnly` entries
+    pub right_only: Option<RuleAction>,
+    /// Resolution for `TypeMismatch` entries
+    pub type_mismatch: Option<RuleAction>,
+}
+
+impl Rule {
+    fn new(pattern: &str, line: usize) -> Result<Self> {
+        Ok(Self {
+            pattern: pattern.to_string(),
+            glob: GlobPattern::new(pattern)?,
+            line,
+            modified: None,
+            left_only: None,
+            right_only: None,
+            type_mismatch: None,
+        })
+    }
+
+    /// Check whether the rule's glob matches a path or one of its parent directories
+    pub fn matches(&self, rel_path: &Path, is_dir: bool) -> bool {
+        let path = path_to_slash(rel_path);
+        if self.glob.matches(&path, is_dir) || (is_dir && self.glob.matches(&(path + "/"), true)) {
+            return true;
+        }
+        rel_path
+            .ancestors()
+            .skip(1)
+            .take_while(|a| !a.as_os_str().is_empty())
+            .any(|a| self.glob.matches(&path_to_slash(a), true))
+    }
+
+    /// Resolution for a kind of difference, if this rule sets one
+    pub fn action_for(&self, diff_type: &DiffType) -> Option<RuleAction> {
+        match diff_type {
+            DiffType::LeftOnly => self.left_only,
+            DiffType::RightOnly => self.right_only,
+            DiffType::TypeMismatch => self.type_mismatch,
+            DiffType::Modified
+            | DiffType::SymlinkChanged
+            | DiffType::MetadataOnly
+            | DiffType::Renamed { .. } => self.modified,
+        }
+    }
+
+    /// Set one key of a `[[rule]]` table
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "take" => {
+                // Make both trees look like the chosen side for every kind
+                let prefer = parse_value::<Prefer>(value)?;
+                self.modified = Some(RuleAction::Take(prefer));
+                self.type_mismatch = Some(RuleAction::Take(prefer));
+                let (left_only, right_only) = match prefer {
+                    Prefer::Left => (FileAction::Copy, FileAction::Delete),
+                    Prefer::Right => (FileAction::Delete, FileAction::Copy),
+                    // Only one version exists, so there is nothing to compare
+                    Prefer::Newer | Prefer::Larger => (FileAction::Copy, FileAction::Copy),
+                };
+                self.left_only = Some(RuleAction::File(left_only));
+                self.right_only = Some(RuleAction::File(right_only));
+            }
+            "action" if value == "never" => {
+                self.modified = Some(RuleAction::Keep);
+                self.left_only = Some(RuleAction::Keep);
+                self.right_only = Some(RuleAction::Keep);
+                self.type_mismatch = Some(RuleAction::Keep);
+            }
+            "action" => bail!("invalid action '{}' (expected never)", value),
+            "modified" => self.modified = Some(parse_side_action(value)?),
+            "type-mismatch" => self.type_mismatch = Some(parse_side_action(value)?),
+            "left-only" => self.left_only = Some(RuleAction::File(parse_value(value)?)),
+            "right-only" => self.right_only = Some(RuleAction::File(parse_value(value)?)),
+            _ => bail!("unknown key '{}'", key),
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: std::str::FromStr<Err = String>>(value: &str) -> Result<T> {
+    value.parse().map_err(anyhow::Error::msg)
+}
+
+/// Parse a resolution for a two-sided entry: a side or `skip`
+fn parse_side_action(value: &str) -> Result<RuleAction> {
+    if value == "skip" {
+        return Ok(RuleAction::Keep);
+    }
+    Ok(RuleAction::Take(parse_value(value)?))
+}
+
+/// `(line, key, value)` entries of one `[[rule]]` table
+type RuleKeys = Vec<(usize, String, String)>;
+
+/// Ordered rules from the `[[rule]]` tables of a config file
+///
+/// The config file uses a small TOML subset:
+///
+/// ```toml
+/// [[rule]]
+/// path = "*.lock"
+/// take = "right"
+///
+/// [[rule]]
+/// path = "secrets/**"
+/// action = "never"
+/// ```
+///
+/// Other tables are ignored so the file can hold further settings.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Load rules from a config file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Parse the `[[rule]]` tables of a config file
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        // (header line, keys) of the rule being read
+        let mut current: Option<(usize, RuleKeys)> = None;
+        let mut in_other_table = false;
+
+        for (index, raw) in content.lines().enumerate() {
+            let line_no = index + 1;
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                if let Some((header, keys)) = current.take() {
+                    rules.push(build_rule(header, keys)?);
+                }
+                in_other_table = line != "[[rule]]";
+                if !in_other_table {
+                    current = Some((line_no, Vec::new()));
+                }
+                continue;
+            }
+            if in_other_table {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                bail!("line {}: expected key = \"value\"", line_no);
+            };
+            let value = parse_string(value.trim())
+                .with_context(|| format!("line {}: expected a quoted string", line_no))?;
+            match current.as_mut() {
+                Some((_, keys)) => keys.push((line_no, key.trim().to_string(), value)),
+                None => bail!("line {}: key outside of a [[rule]] table", line_no),
+            }
+        }
+        if let Some((header, keys)) = current {
+            rules.push(build_rule(header, keys)?);
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// First rule that matches the entry's path and sets a resolution for its kind
+    pub fn find(&self, entry: &DiffEntry) -> Option<(&Rule, RuleAction)> {
+        let is_dir = entry.left_is_dir.unwrap_or(false) || entry.right_is_dir.unwrap_or(false);
+        self.rules.iter().find_map(|rule| {
+            let action = rule.action_for(&entry.diff_type)?;
+            rule.matches(&entry.path, is_dir).then_some((rule, action))
+        })
+    }
+}
+
+fn build_rule(header: usize, keys: RuleKeys) -> Result<Rule> {
+    let Some((_, _, pattern)) = keys.iter().find(|(_, key, _)| key == "path") else {
+        bail!("line {}: rule has no path", header);
+    };
+    let mut rule = Rule::new(pattern, header).with_context(|| format!("line {}", header))?;
+    for (line_no, key, value) in keys.iter().filter(|(_, key, _)| key != "path") {
+        rule.set(key, value)
+            .with_context(|| format!("line {}", line_no))?;
+    }
+    Ok(rule)
+}
+
+/// Remove a `#` comment that is not inside a quoted string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Parse a basic TOML string (`"..."`) or literal string (`'...'`)
+fn parse_string(value: &str) -> Option<String> {
+    if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return Some(inner.to_string());
+    }
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                '\\' => result.push('\\'),
+                '"' => result.push('"'),
+                't' => result.push('\t'),
+                'n' => result.push('\n'),
+                _ => return None,
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const CONFIG: &str = r#"
+# Resolutions applied before prompting
+[settings]
+ignored = "yes"
+
+[[rule]]
+path = "*.lock"   # lock files are regenerated on the right
+take = "right"
+
+[[rule]]
+path = "secrets/**"
+action = "never"
+
+[[rule]]
+path = "generated/**"
+modified = "left"
+left-only = "copy"
+"#;
+
+    #[test]
+    fn test_parse_rules() {
+        // Given: A config file with three rules and an unrelated table
+
+        // When: Parsing it
+        let rules = RuleSet::parse(CONFIG).unwrap();
+
+        // Then: Every rule is read in order
+        assert_eq!(rules.rules.len(), 3);
+        assert_eq!(rules.rules[0].pattern, "*.lock");
+        assert_eq!(rules.rules[0].line, 6);
+        assert_eq!(
+            rules.rules[0].right_only,
+            Some(RuleAction::File(FileAction::Copy))
+        );
+        assert_eq!(rules.rules[1].modified, Some(RuleAction::Keep));
+        assert_eq!(rules.rules[2].right_only, None);
+    }
+
+    #[test]
+    fn test_find_per_kind() {
+        // Given: The parsed rules
+        let rules = RuleSet::parse(CONFIG).unwrap();
+
+        // When: Looking up entries of different kinds
+        let lock = DiffEntry::modified(PathBuf::from("web/yarn.lock"));
+        let secret = DiffEntry::left_only(PathBuf::from("secrets/key.pem"), false);
+        let generated = DiffEntry::right_only(PathBuf::from("generated/api.rs"), false);
+        let generated_dir = DiffEntry::left_only(PathBuf::from("generated"), true);
+
+        // Then: The first rule setting that kind fires
+        assert_eq!(
+            rules.find(&lock).map(|(_, a)| a),
+            Some(RuleAction::Take(Prefer::Right))
+        );
+        assert_eq!(rules.find(&secret).map(|(_, a)| a), Some(RuleAction::Keep));
+        assert!(rules.find(&generated).is_none());
+        assert_eq!(
+            rules.find(&generated_dir).map(|(_, a)| a),
+            Some(RuleAction::File(FileAction::Copy))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_report_line() {
+        // Given: A rule with an invalid side
+        let config = "[[rule]]\npath = \"*.txt\"\nmodified = \"middle\"\n";
+
+        // When: Parsing it
+        let err = RuleSet::parse(config).unwrap_err();
+
+        // Then: The error names the offending line
+        assert!(format!("{:#}", err).contains("line 3"));
+    }
+
+    #[test]
+    fn test_rule_without_path_is_rejected() {
+        // Given: A rule table without a path
+        let config = "[[rule]]\ntake = \"left\"\n";
+
+        // When/Then: Parsing fails
+        assert!(RuleSet::parse(config).is_err());
+    }
+}
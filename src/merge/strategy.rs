@@ -1,8 +1,10 @@
-use anyhow::Result;
-use std::fs;
-use std::path::Path;
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use crate::diff::{DiffEntry, DiffType};
+use super::guard::{check_unchanged, FileState};
+use crate::diff::{DiffEntry, DiffType, HunkChoice};
 
 /// Action to take for a file-level diff entry (LeftOnly/RightOnly)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -16,19 +18,27 @@ pub enum FileAction {
 }
 
 /// Apply file-level action for LeftOnly/RightOnly entries
+///
+/// Nothing is changed if a path no longer matches its `expected` state.
+/// With `follow_symlinks`, copies take what symlinks point to.
 pub fn apply_file_action(
     entry: &DiffEntry,
     action: FileAction,
     left_root: &Path,
     right_root: &Path,
+    expected: &[FileState],
+    follow_symlinks: bool,
 ) -> Result<()> {
+    if action != FileAction::Skip {
+        check_unchanged(expected)?;
+    }
     match (&entry.diff_type, action) {
         // LeftOnly: file exists only in left
         (DiffType::LeftOnly, FileAction::Copy) => {
             // Copy from left to right
             let src = left_root.join(&entry.path);
             let dst = right_root.join(&entry.path);
-            copy_entry(&src, &dst)?;
+            copy_entry(&src, &dst, follow_symlinks)?;
         }
         (DiffType::LeftOnly, FileAction::Delete) => {
             // Delete from left
@@ -44,7 +54,7 @@ pub fn apply_file_action(
             // Copy from right to left
             let src = right_root.join(&entry.path);
             let dst = left_root.join(&entry.path);
-            copy_entry(&src, &dst)?;
+            copy_entry(&src, &dst, follow_symlinks)?;
         }
         (DiffType::RightOnly, FileAction::Delete) => {
             // Delete from right
@@ -60,8 +70,7 @@ pub fn apply_file_action(
             // This is ambiguous - for now, copy left to right
             let src = left_root.join(&entry.path);
             let dst = right_root.join(&entry.path);
-            remove_entry(&dst)?;
-            copy_entry(&src, &dst)?;
+            copy_entry(&src, &dst, follow_symlinks)?;
         }
         (DiffType::TypeMismatch, FileAction::Delete) => {
             // Delete both? Or just one? For now, delete from right
@@ -80,58 +89,311 @@ pub fn apply_file_action(
     Ok(())
 }
 
+/// Make both directories hold the chosen side's version of an entry
+///
+/// Left replaces the right entry with the left one (or deletes it when the
+/// left side has none), Right does the reverse, and Skip does nothing.
+/// Both only applies to hunks and does nothing here either.
+/// With `follow_symlinks`, the copy takes what symlinks point to.
+pub fn apply_entry_choice(
+    entry: &DiffEntry,
+    choice: HunkChoice,
+    left_root: &Path,
+    right_root: &Path,
+    follow_symlinks: bool,
+) -> Result<()> {
+    let (src, dst) = match choice {
+        HunkChoice::Left => (left_root.join(&entry.path), right_root.join(&entry.path)),
+        HunkChoice::Right => (right_root.join(&entry.path), left_root.join(&entry.path)),
+        HunkChoice::Skip | HunkChoice::Both(_) => return Ok(()),
+    };
+
+    if entry_exists(&src) {
+        copy_entry(&src, &dst, follow_symlinks)
+    } else {
+        remove_entry(&dst)
+    }
+}
+
+/// Copy the differing metadata of the chosen side onto the other side
+///
+/// Only the fields recorded in `entry.metadata` are touched, so content and
+/// metadata that was not compared stay as they are.
+pub fn apply_metadata_choice(
+    entry: &DiffEntry,
+    choice: HunkChoice,
+    left_root: &Path,
+    right_root: &Path,
+) -> Result<()> {
+    let Some(metadata) = &entry.metadata else {
+        return Ok(());
+    };
+    let (src, dst) = match choice {
+        HunkChoice::Left => (left_root.join(&entry.path), right_root.join(&entry.path)),
+        HunkChoice::Right => (right_root.join(&entry.path), left_root.join(&entry.path)),
+        HunkChoice::Skip | HunkChoice::Both(_) => return Ok(()),
+    };
+    let src_meta = fs::metadata(&src)?;
+
+    // The mtime goes first, from a read-only handle, so a read-only mode
+    // (either the current one or the one taken) does not get in the way
+    if metadata.mtime.is_some() {
+        fs::File::open(&dst)?.set_modified(src_meta.modified()?)?;
+    }
+    #[cfg(unix)]
+    if metadata.owner.is_some() {
+        use std::os::unix::fs::MetadataExt;
+        std::os::unix::fs::chown(&dst, Some(src_meta.uid()), Some(src_meta.gid()))
+            .with_context(|| format!("Failed to change owner of {}", dst.display()))?;
+    }
+    if metadata.mode.is_some() {
+        fs::set_permissions(&dst, src_meta.permissions())?;
+    }
+    Ok(())
+}
+
+/// Make both directories use the same name for a renamed entry
+///
+/// Left renames the right entry back to the left path (reverting the
+/// rename), Right applies the rename to the left entry, and Skip (or Both,
+/// which only applies to hunks) does nothing.
+pub fn apply_rename(
+    entry: &DiffEntry,
+    choice: HunkChoice,
+    left_root: &Path,
+    right_root: &Path,
+) -> Result<()> {
+    let DiffType::Renamed { from, to, .. } = &entry.diff_type else {
+        anyhow::bail!("Not a rename: {}", entry.path.display());
+    };
+    let (src, dst) = match choice {
+        HunkChoice::Left => (right_root.join(to), right_root.join(from)),
+        HunkChoice::Right => (left_root.join(from), left_root.join(to)),
+        HunkChoice::Skip | HunkChoice::Both(_) => return Ok(()),
+    };
+
+    if entry_exists(&dst) {
+        anyhow::bail!("Rename target already exists: {}", dst.display());
+    }
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&src, &dst)
+        .with_context(|| format!("Failed to rename {} to {}", src.display(), dst.display()))?;
+    Ok(())
+}
+
 /// Apply hunk choices to merge a modified file
 /// Updates left file with left_content and right file with right_content
+///
+/// Nothing is written if a file no longer matches its `expected` state.
 pub fn apply_hunk_merge(
     left_path: &Path,
     right_path: &Path,
     left_content: &str,
     right_content: &str,
+    expected: &[FileState],
 ) -> Result<()> {
+    check_unchanged(expected)?;
     // Write merged content to both files
-    fs::write(left_path, left_content)?;
-    fs::write(right_path, right_content)?;
+    write_atomic(left_path, left_content.as_bytes())?;
+    write_atomic(right_path, right_content.as_bytes())?;
+    Ok(())
+}
+
+/// Replace a file's content without ever leaving it half written
+///
+/// The content goes to a staging file in the same directory, which takes the
+/// original file's permissions, is synced and then renamed over the target.
+/// A symlink is written through, like `fs::write` does.
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let path = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_symlink() => fs::canonicalize(path)?,
+        _ => path.to_path_buf(),
+    };
+    let staging = staging_path(&path);
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&staging)?;
+        file.write_all(content)?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&staging, &path)?;
+        sync_parent(&path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&staging);
+    }
+    result.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Hidden sibling of a path used while its new version is being written
+fn staging_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.ddmerge-{}", name, std::process::id()))
+}
+
+/// Make a rename in the path's directory durable
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        File::open(parent)?.sync_all()?;
+    }
     Ok(())
 }
 
-/// Copy a file or directory recursively
-fn copy_entry(src: &Path, dst: &Path) -> Result<()> {
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Copy a file, symlink or directory recursively
+///
+/// Symlinks are recreated as links with the same target rather than copied
+/// through, so a link can never pull in content from outside the tree.
+/// With `follow_symlinks`, what they point to is copied instead (dangling
+/// links are still recreated as links).
+/// The copy is built under a staging name and moved into place once
+/// complete, replacing whatever `dst` holds, so `dst` never holds a partial
+/// copy or goes missing.
+pub(super) fn copy_entry(src: &Path, dst: &Path, follow_symlinks: bool) -> Result<()> {
     if let Some(parent) = dst.parent() {
         fs::create_dir_all(parent)?;
     }
 
-    if src.is_dir() {
-        copy_dir_all(src, dst)?;
-    } else {
-        fs::copy(src, dst)?;
+    let staging = staging_path(dst);
+    remove_entry(&staging)?;
+    let result = (|| -> Result<()> {
+        let file_type = copied_type(src, follow_symlinks)?;
+        if file_type.is_symlink() {
+            copy_symlink(src, &staging)?;
+        } else if file_type.is_dir() {
+            copy_dir_all(src, &staging, follow_symlinks)?;
+        } else {
+            copy_file(src, &staging)?;
+        }
+        replace_with(&staging, dst)
+    })();
+    if result.is_err() {
+        let _ = remove_entry(&staging);
+    }
+    result.with_context(|| format!("Failed to copy {} to {}", src.display(), dst.display()))
+}
+
+/// Move a staged entry to `path`, replacing whatever is there
+///
+/// Files and symlinks are replaced by a single rename. A directory on either
+/// side cannot be, so the old entry is renamed aside first, deleted once the
+/// staged one is in place, and put back if the staged one cannot be moved in.
+fn replace_with(staging: &Path, path: &Path) -> Result<()> {
+    let old_is_dir = fs::symlink_metadata(path).is_ok_and(|m| m.is_dir());
+    let new_is_dir = fs::symlink_metadata(staging)?.is_dir();
+    if !entry_exists(path) || !(old_is_dir || new_is_dir) {
+        fs::rename(staging, path)?;
+        return sync_parent(path);
     }
+
+    let name = staging.file_name().unwrap_or_default().to_string_lossy();
+    let aside = staging.with_file_name(format!("{}.old", name));
+    remove_entry(&aside)?;
+    fs::rename(path, &aside)?;
+    if let Err(e) = fs::rename(staging, path) {
+        fs::rename(&aside, path).with_context(|| {
+            format!(
+                "Failed to restore {} from {}",
+                path.display(),
+                aside.display()
+            )
+        })?;
+        return Err(e.into());
+    }
+    sync_parent(path)?;
+    remove_entry(&aside)
+        .with_context(|| format!("Failed to remove the replaced {}", aside.display()))
+}
+
+/// Type of the entry a copy of `path` takes: the link itself, or with
+/// `follow_symlinks` what it points to (a dangling link stays a link)
+fn copied_type(path: &Path, follow_symlinks: bool) -> Result<fs::FileType> {
+    let metadata = fs::symlink_metadata(path)?;
+    if follow_symlinks && metadata.is_symlink() {
+        if let Ok(target) = fs::metadata(path) {
+            return Ok(target.file_type());
+        }
+    }
+    Ok(metadata.file_type())
+}
+
+/// Copy a file with its permissions and sync it to disk
+fn copy_file(src: &Path, dst: &Path) -> Result<()> {
+    fs::copy(src, dst)?;
+    File::open(dst)?.sync_all()?;
     Ok(())
 }
 
-/// Remove a file or directory
-fn remove_entry(path: &Path) -> Result<()> {
-    if path.is_dir() {
-        fs::remove_dir_all(path)?;
-    } else if path.exists() {
-        fs::remove_file(path)?;
+/// Remove a file, symlink or directory (a symlink is removed, never its target)
+pub(super) fn remove_entry(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
     }
     Ok(())
 }
 
-/// Recursively copy a directory
-fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
+/// Check whether an entry exists, without following symlinks
+fn entry_exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// Recursively copy a directory, preserving permissions
+/// (permissions of files are preserved by `fs::copy`)
+///
+/// With `follow_symlinks`, a link to a directory holding it is refused
+/// rather than copied forever.
+fn copy_dir_all(src: &Path, dst: &Path, follow_symlinks: bool) -> Result<()> {
     fs::create_dir_all(dst)?;
     for entry in fs::read_dir(src)? {
         let entry = entry?;
-        let ty = entry.file_type()?;
         let src_path = entry.path();
         let dst_path = dst.join(entry.file_name());
-        if ty.is_dir() {
-            copy_dir_all(&src_path, &dst_path)?;
+        let ty = copied_type(&src_path, follow_symlinks)?;
+        if ty.is_symlink() {
+            copy_symlink(&src_path, &dst_path)?;
+        } else if ty.is_dir() {
+            if entry.file_type()?.is_symlink()
+                && fs::canonicalize(src)?.starts_with(fs::canonicalize(&src_path)?)
+            {
+                anyhow::bail!("Symlink loop at {}", src_path.display());
+            }
+            copy_dir_all(&src_path, &dst_path, follow_symlinks)?;
         } else {
-            fs::copy(&src_path, &dst_path)?;
+            copy_file(&src_path, &dst_path)?;
         }
     }
+    // Applied last so a read-only source directory can still be filled
+    fs::set_permissions(dst, fs::metadata(src)?.permissions())?;
+    Ok(())
+}
+
+/// Recreate a symlink at `dst` with the same target as `src`
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
+    let target = fs::read_link(src)?;
+    std::os::unix::fs::symlink(target, dst)?;
+    Ok(())
+}
+
+/// Recreate a symlink at `dst` with the same target as `src`
+#[cfg(windows)]
+fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
+    let target = fs::read_link(src)?;
+    if fs::metadata(src).is_ok_and(|m| m.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, dst)?;
+    } else {
+        std::os::windows::fs::symlink_file(target, dst)?;
+    }
     Ok(())
 }
 
@@ -169,6 +431,7 @@ pub fn copy_unchanged_files(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::merge::guard::ChangedOnDisk;
     use std::path::PathBuf;
     use tempfile::TempDir;
 
@@ -184,12 +447,18 @@ mod tests {
             DiffType::RightOnly => (None, Some(false)),
             DiffType::Modified => (Some(false), Some(false)),
             DiffType::TypeMismatch => (Some(false), Some(true)),
+            DiffType::SymlinkChanged | DiffType::MetadataOnly | DiffType::Renamed { .. } => {
+                (Some(false), Some(false))
+            }
         };
         DiffEntry {
             path: PathBuf::from(path),
             diff_type,
             left_is_dir,
             right_is_dir,
+            left_is_symlink: false,
+            right_is_symlink: false,
+            metadata: None,
         }
     }
 
@@ -204,6 +473,9 @@ mod tests {
             diff_type,
             left_is_dir,
             right_is_dir,
+            left_is_symlink: false,
+            right_is_symlink: false,
+            metadata: None,
         }
     }
 
@@ -221,7 +493,15 @@ mod tests {
         let entry = create_diff_entry(file_path, DiffType::LeftOnly);
 
         // When: Copy action is applied
-        apply_file_action(&entry, FileAction::Copy, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The file is copied to the right directory
         assert!(right.path().join(file_path).exists());
@@ -242,7 +522,15 @@ mod tests {
         let entry = create_diff_entry(file_path, DiffType::LeftOnly);
 
         // When: Copy action is applied
-        apply_file_action(&entry, FileAction::Copy, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The file and parent directories are created in the right directory
         assert!(right.path().join(file_path).exists());
@@ -262,7 +550,15 @@ mod tests {
         let entry = create_diff_entry(file_path, DiffType::LeftOnly);
 
         // When: Delete action is applied
-        apply_file_action(&entry, FileAction::Delete, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Delete,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The file is deleted from the left directory
         assert!(!left.path().join(file_path).exists());
@@ -278,7 +574,15 @@ mod tests {
         let entry = create_diff_entry(file_path, DiffType::LeftOnly);
 
         // When: Skip action is applied
-        apply_file_action(&entry, FileAction::Skip, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Skip,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The file remains in the left directory and is not copied to right
         assert!(left.path().join(file_path).exists());
@@ -299,7 +603,15 @@ mod tests {
         let entry = create_diff_entry(file_path, DiffType::RightOnly);
 
         // When: Copy action is applied
-        apply_file_action(&entry, FileAction::Copy, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The file is copied to the left directory
         assert!(left.path().join(file_path).exists());
@@ -319,7 +631,15 @@ mod tests {
         let entry = create_diff_entry(file_path, DiffType::RightOnly);
 
         // When: Delete action is applied
-        apply_file_action(&entry, FileAction::Delete, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Delete,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The file is deleted from the right directory
         assert!(!right.path().join(file_path).exists());
@@ -335,7 +655,15 @@ mod tests {
         let entry = create_diff_entry(file_path, DiffType::RightOnly);
 
         // When: Skip action is applied
-        apply_file_action(&entry, FileAction::Skip, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Skip,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The file remains in the right directory and is not copied to left
         assert!(right.path().join(file_path).exists());
@@ -357,7 +685,15 @@ mod tests {
         let entry = create_diff_entry(name, DiffType::TypeMismatch);
 
         // When: Copy action is applied
-        apply_file_action(&entry, FileAction::Copy, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The right directory is replaced with the left file
         assert!(right.path().join(name).is_file());
@@ -379,7 +715,15 @@ mod tests {
         let entry = create_diff_entry(name, DiffType::TypeMismatch);
 
         // When: Copy action is applied
-        apply_file_action(&entry, FileAction::Copy, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The right file is replaced with the left directory
         assert!(right.path().join(name).is_dir());
@@ -397,7 +741,15 @@ mod tests {
         let entry = create_diff_entry(name, DiffType::TypeMismatch);
 
         // When: Delete action is applied
-        apply_file_action(&entry, FileAction::Delete, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Delete,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The right item is deleted, left remains
         assert!(!right.path().join(name).exists());
@@ -415,7 +767,15 @@ mod tests {
         let entry = create_diff_entry(name, DiffType::TypeMismatch);
 
         // When: Skip action is applied
-        apply_file_action(&entry, FileAction::Skip, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Skip,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: Both items remain unchanged
         assert!(left.path().join(name).is_file());
@@ -437,7 +797,15 @@ mod tests {
         let entry = create_diff_entry(file_path, DiffType::Modified);
 
         // When: Any action is applied to a Modified entry
-        apply_file_action(&entry, FileAction::Copy, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: Both files remain unchanged (Modified uses hunk-based merge)
         assert_eq!(
@@ -466,7 +834,15 @@ mod tests {
         let entry = create_diff_entry_with_types(dir_path, DiffType::LeftOnly, Some(true), None);
 
         // When: Copy action is applied
-        apply_file_action(&entry, FileAction::Copy, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The entire directory structure is copied to the right
         assert!(right.path().join(dir_path).is_dir());
@@ -480,6 +856,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_apply_file_action_refuses_copy_over_new_file() {
+        // Given: A left-only file, and a right file created after the prompt
+        let (left, right) = create_test_dirs();
+        fs::write(left.path().join("file.txt"), "left").unwrap();
+        let entry = create_diff_entry("file.txt", DiffType::LeftOnly);
+        let expected = [
+            FileState::capture(&left.path().join("file.txt")).unwrap(),
+            FileState::capture(&right.path().join("file.txt")).unwrap(),
+        ];
+        fs::write(right.path().join("file.txt"), "created elsewhere").unwrap();
+
+        // When: Copy action is applied
+        let result = apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &expected,
+            false,
+        );
+
+        // Then: The new right file is kept
+        assert!(result.is_err());
+        assert_eq!(
+            fs::read_to_string(right.path().join("file.txt")).unwrap(),
+            "created elsewhere"
+        );
+    }
+
+    #[test]
+    fn test_copy_directory_leaves_no_staging_entries() {
+        // Given: A directory only in the left directory
+        let (left, right) = create_test_dirs();
+        fs::create_dir_all(left.path().join("dir/sub")).unwrap();
+        fs::write(left.path().join("dir/sub/file.txt"), "content").unwrap();
+        let entry = create_diff_entry_with_types("dir", DiffType::LeftOnly, Some(true), None);
+
+        // When: Copy action is applied
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
+
+        // Then: Only the copied directory exists on the right
+        let names: Vec<_> = fs::read_dir(right.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["dir"]);
+        assert_eq!(
+            fs::read_to_string(right.path().join("dir/sub/file.txt")).unwrap(),
+            "content"
+        );
+    }
+
     #[test]
     fn test_apply_file_action_copy_nested_directory() {
         // Given: A nested directory structure exists only in the left directory
@@ -491,7 +928,15 @@ mod tests {
         let entry = create_diff_entry_with_types("parent", DiffType::LeftOnly, Some(true), None);
 
         // When: Copy action is applied
-        apply_file_action(&entry, FileAction::Copy, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The entire nested structure is copied
         assert!(right.path().join(dir_path).is_dir());
@@ -512,7 +957,15 @@ mod tests {
         let entry = create_diff_entry_with_types(dir_path, DiffType::LeftOnly, Some(true), None);
 
         // When: Delete action is applied
-        apply_file_action(&entry, FileAction::Delete, left.path(), right.path()).unwrap();
+        apply_file_action(
+            &entry,
+            FileAction::Delete,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
 
         // Then: The entire directory is deleted from left
         assert!(!left.path().join(dir_path).exists());
@@ -537,6 +990,7 @@ mod tests {
             &right_path,
             "new left content",
             "new right content",
+            &[],
         )
         .unwrap();
 
@@ -556,7 +1010,14 @@ mod tests {
         let right_path = right.path().join("new.txt");
 
         // When: apply_hunk_merge is called
-        apply_hunk_merge(&left_path, &right_path, "left content", "right content").unwrap();
+        apply_hunk_merge(
+            &left_path,
+            &right_path,
+            "left content",
+            "right content",
+            &[],
+        )
+        .unwrap();
 
         // Then: Both files are created with the specified content
         assert_eq!(fs::read_to_string(&left_path).unwrap(), "left content");
@@ -574,13 +1035,84 @@ mod tests {
 
         // When: apply_hunk_merge is called with the same content for both
         let synced_content = "synced content\nline2\n";
-        apply_hunk_merge(&left_path, &right_path, synced_content, synced_content).unwrap();
+        apply_hunk_merge(&left_path, &right_path, synced_content, synced_content, &[]).unwrap();
 
         // Then: Both files have identical content
         assert_eq!(fs::read_to_string(&left_path).unwrap(), synced_content);
         assert_eq!(fs::read_to_string(&right_path).unwrap(), synced_content);
     }
 
+    #[test]
+    fn test_apply_hunk_merge_refuses_changed_file() {
+        // Given: A right file edited after its state was captured
+        let (left, right) = create_test_dirs();
+        let left_path = left.path().join("test.txt");
+        let right_path = right.path().join("test.txt");
+        fs::write(&left_path, "left").unwrap();
+        fs::write(&right_path, "right").unwrap();
+        let expected = [
+            FileState::capture(&left_path).unwrap(),
+            FileState::capture(&right_path).unwrap(),
+        ];
+        fs::write(&right_path, "edited elsewhere").unwrap();
+
+        // When: apply_hunk_merge is called
+        let err =
+            apply_hunk_merge(&left_path, &right_path, "merged", "merged", &expected).unwrap_err();
+
+        // Then: Neither file is written
+        assert!(err.downcast_ref::<ChangedOnDisk>().is_some());
+        assert_eq!(fs::read_to_string(&left_path).unwrap(), "left");
+        assert_eq!(fs::read_to_string(&right_path).unwrap(), "edited elsewhere");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_hunk_merge_keeps_permissions_and_no_staging_files() {
+        // Given: An executable file on each side
+        use std::os::unix::fs::PermissionsExt;
+        let (left, right) = create_test_dirs();
+        let left_path = left.path().join("run.sh");
+        let right_path = right.path().join("run.sh");
+        for path in [&left_path, &right_path] {
+            fs::write(path, "old").unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o750)).unwrap();
+        }
+
+        // When: apply_hunk_merge replaces their content
+        apply_hunk_merge(&left_path, &right_path, "new\n", "new\n", &[]).unwrap();
+
+        // Then: The mode is kept and only the target files remain
+        let mode = fs::metadata(&left_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+        assert_eq!(fs::read_to_string(&right_path).unwrap(), "new\n");
+        assert_eq!(fs::read_dir(left.path()).unwrap().count(), 1);
+        assert_eq!(fs::read_dir(right.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_hunk_merge_writes_through_symlink() {
+        // Given: A right path that is a symlink to a file
+        let (left, right) = create_test_dirs();
+        let left_path = left.path().join("test.txt");
+        let right_target = right.path().join("target.txt");
+        let right_path = right.path().join("test.txt");
+        fs::write(&left_path, "old").unwrap();
+        fs::write(&right_target, "old").unwrap();
+        std::os::unix::fs::symlink("target.txt", &right_path).unwrap();
+
+        // When: apply_hunk_merge writes new content
+        apply_hunk_merge(&left_path, &right_path, "new", "new", &[]).unwrap();
+
+        // Then: The link is kept and its target is updated
+        assert!(fs::symlink_metadata(&right_path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&right_target).unwrap(), "new");
+    }
+
     #[test]
     fn test_apply_hunk_merge_preserves_trailing_newline() {
         // Given: Content with specific trailing newline behavior
@@ -589,10 +1121,412 @@ mod tests {
         let right_path = right.path().join("test.txt");
 
         // When: apply_hunk_merge is called with content without trailing newline
-        apply_hunk_merge(&left_path, &right_path, "no newline", "has newline\n").unwrap();
+        apply_hunk_merge(&left_path, &right_path, "no newline", "has newline\n", &[]).unwrap();
 
         // Then: The exact content is preserved including trailing newline differences
         assert_eq!(fs::read_to_string(&left_path).unwrap(), "no newline");
         assert_eq!(fs::read_to_string(&right_path).unwrap(), "has newline\n");
     }
+
+    // ========================================
+    // apply_entry_choice tests
+    // ========================================
+
+    #[test]
+    fn test_apply_entry_choice_left_overwrites_right() {
+        // Given: A modified file in both directories
+        let (left, right) = create_test_dirs();
+        fs::write(left.path().join("test.txt"), "left content").unwrap();
+        fs::write(right.path().join("test.txt"), "right content").unwrap();
+        let entry = create_diff_entry("test.txt", DiffType::Modified);
+
+        // When: Left is chosen for the whole entry
+        apply_entry_choice(&entry, HunkChoice::Left, left.path(), right.path(), false).unwrap();
+
+        // Then: The right file receives the left content
+        assert_eq!(
+            fs::read_to_string(right.path().join("test.txt")).unwrap(),
+            "left content"
+        );
+    }
+
+    #[test]
+    fn test_apply_entry_choice_right_deletes_missing() {
+        // Given: A file that exists only in the left directory
+        let (left, right) = create_test_dirs();
+        fs::write(left.path().join("test.txt"), "left content").unwrap();
+        let entry = create_diff_entry("test.txt", DiffType::LeftOnly);
+
+        // When: Right (where the file is absent) is chosen
+        apply_entry_choice(&entry, HunkChoice::Right, left.path(), right.path(), false).unwrap();
+
+        // Then: The file is removed from the left directory
+        assert!(!left.path().join("test.txt").exists());
+    }
+
+    #[test]
+    fn test_apply_entry_choice_replaces_directory() {
+        // Given: A directory in both trees holding different files
+        let (left, right) = create_test_dirs();
+        fs::create_dir(left.path().join("dir")).unwrap();
+        fs::create_dir(right.path().join("dir")).unwrap();
+        fs::write(left.path().join("dir/new.txt"), "new").unwrap();
+        fs::write(right.path().join("dir/old.txt"), "old").unwrap();
+        let entry = create_diff_entry_with_types("dir", DiffType::Modified, Some(true), Some(true));
+
+        // When: Left is chosen for the whole entry
+        apply_entry_choice(&entry, HunkChoice::Left, left.path(), right.path(), false).unwrap();
+
+        // Then: The right directory holds the left tree only, with nothing left aside
+        assert!(right.path().join("dir/new.txt").exists());
+        assert!(!right.path().join("dir/old.txt").exists());
+        assert_eq!(fs::read_dir(right.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_entry_choice_failed_copy_keeps_target() {
+        // Given: A left directory that cannot be copied (it links to itself)
+        // and a right file of the same name
+        let (left, right) = create_test_dirs();
+        fs::create_dir(left.path().join("dir")).unwrap();
+        std::os::unix::fs::symlink(".", left.path().join("dir/self")).unwrap();
+        fs::write(right.path().join("dir"), "right content").unwrap();
+        let entry = create_diff_entry("dir", DiffType::TypeMismatch);
+
+        // When: Left is chosen while following symlinks
+        let result = apply_entry_choice(&entry, HunkChoice::Left, left.path(), right.path(), true);
+
+        // Then: The copy fails and the right file is untouched
+        assert!(result.is_err());
+        assert_eq!(
+            fs::read_to_string(right.path().join("dir")).unwrap(),
+            "right content"
+        );
+        assert_eq!(fs::read_dir(right.path()).unwrap().count(), 1);
+    }
+
+    // ========================================
+    // Symlink handling tests
+    // ========================================
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_file_action_copy_symlink_recreates_link() {
+        // Given: A symlink that exists only in the left directory
+        let (left, right) = create_test_dirs();
+        fs::write(left.path().join("target.txt"), "content").unwrap();
+        std::os::unix::fs::symlink("target.txt", left.path().join("link")).unwrap();
+
+        let entry = create_diff_entry("link", DiffType::LeftOnly);
+
+        // When: Copy action is applied
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
+
+        // Then: The right side gets a link with the same target, not a file copy
+        let copied = right.path().join("link");
+        assert!(fs::symlink_metadata(&copied)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_link(&copied).unwrap(), PathBuf::from("target.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_file_action_copy_follows_symlinks() {
+        // Given: A left-only directory holding a link to a file outside it,
+        // a dangling link and a link back to the directory itself
+        let (left, right) = create_test_dirs();
+        fs::write(left.path().join("target.txt"), "content").unwrap();
+        fs::create_dir(left.path().join("dir")).unwrap();
+        std::os::unix::fs::symlink("../target.txt", left.path().join("dir/link")).unwrap();
+        std::os::unix::fs::symlink("missing", left.path().join("dir/dangling")).unwrap();
+        let entry = create_diff_entry_with_types("dir", DiffType::LeftOnly, Some(true), None);
+
+        // When: Copying it while following symlinks
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            true,
+        )
+        .unwrap();
+
+        // Then: The link's target content is copied and the dangling link kept as a link
+        let copied = right.path().join("dir/link");
+        assert!(!fs::symlink_metadata(&copied).unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&copied).unwrap(), "content");
+        let dangling = right.path().join("dir/dangling");
+        assert!(fs::symlink_metadata(&dangling).unwrap().is_symlink());
+
+        // When: The directory also links to itself
+        std::os::unix::fs::symlink(".", left.path().join("dir/self")).unwrap();
+        fs::remove_dir_all(right.path().join("dir")).unwrap();
+        let result = apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            true,
+        );
+
+        // Then: The loop is refused and nothing is left behind
+        assert!(result.is_err());
+        assert!(!right.path().join("dir").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_file_action_delete_symlink_keeps_target() {
+        // Given: A symlink to a directory that exists only in the left directory
+        let (left, right) = create_test_dirs();
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("keep.txt"), "keep").unwrap();
+        std::os::unix::fs::symlink(outside.path(), left.path().join("link")).unwrap();
+
+        let entry = create_diff_entry("link", DiffType::LeftOnly);
+
+        // When: Delete action is applied
+        apply_file_action(
+            &entry,
+            FileAction::Delete,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
+
+        // Then: Only the link is removed, the target directory is untouched
+        assert!(fs::symlink_metadata(left.path().join("link")).is_err());
+        assert!(outside.path().join("keep.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_entry_choice_symlink_changed() {
+        // Given: A symlink in both directories with different targets
+        let (left, right) = create_test_dirs();
+        std::os::unix::fs::symlink("a.txt", left.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("b.txt", right.path().join("link")).unwrap();
+
+        let entry = create_diff_entry("link", DiffType::SymlinkChanged);
+
+        // When: Left is chosen
+        apply_entry_choice(&entry, HunkChoice::Left, left.path(), right.path(), false).unwrap();
+
+        // Then: The right link now points to the left target
+        assert_eq!(
+            fs::read_link(right.path().join("link")).unwrap(),
+            PathBuf::from("a.txt")
+        );
+    }
+
+    // ========================================
+    // Metadata handling tests
+    // ========================================
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_metadata_choice_copies_mode() {
+        // Given: Identical files where only the left one is executable
+        use crate::diff::MetadataDiff;
+        use std::os::unix::fs::PermissionsExt;
+        let (left, right) = create_test_dirs();
+        fs::write(left.path().join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::write(right.path().join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(
+            left.path().join("run.sh"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        fs::set_permissions(
+            right.path().join("run.sh"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+        let entry = DiffEntry::metadata_only(
+            PathBuf::from("run.sh"),
+            MetadataDiff {
+                mode: Some((0o755, 0o644)),
+                ..MetadataDiff::default()
+            },
+        );
+
+        // When: Left metadata is chosen
+        apply_metadata_choice(&entry, HunkChoice::Left, left.path(), right.path()).unwrap();
+
+        // Then: The right file becomes executable too
+        let mode = fs::metadata(right.path().join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_metadata_choice_read_only_mode_and_mtime() {
+        // Given: Identical files where the left one is read-only and older
+        use crate::diff::MetadataDiff;
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, SystemTime};
+        let (left, right) = create_test_dirs();
+        let left_file = left.path().join("config");
+        let right_file = right.path().join("config");
+        fs::write(&left_file, "key = 1\n").unwrap();
+        fs::write(&right_file, "key = 1\n").unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::open(&left_file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        fs::set_permissions(&left_file, fs::Permissions::from_mode(0o444)).unwrap();
+        let entry = DiffEntry::metadata_only(
+            PathBuf::from("config"),
+            MetadataDiff {
+                mode: Some((0o444, 0o644)),
+                mtime: Some((0, 0)),
+                ..MetadataDiff::default()
+            },
+        );
+
+        // When: Left metadata is chosen
+        apply_metadata_choice(&entry, HunkChoice::Left, left.path(), right.path()).unwrap();
+
+        // Then: The right file takes both the read-only mode and the mtime
+        let meta = fs::metadata(&right_file).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o444);
+        assert_eq!(meta.modified().unwrap(), modified);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_file_action_copy_directory_preserves_mode() {
+        // Given: A directory with restricted permissions only in the left directory
+        use std::os::unix::fs::PermissionsExt;
+        let (left, right) = create_test_dirs();
+        fs::create_dir(left.path().join("private")).unwrap();
+        fs::write(left.path().join("private/run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(
+            left.path().join("private/run.sh"),
+            fs::Permissions::from_mode(0o750),
+        )
+        .unwrap();
+        fs::set_permissions(
+            left.path().join("private"),
+            fs::Permissions::from_mode(0o700),
+        )
+        .unwrap();
+        let entry = create_diff_entry_with_types("private", DiffType::LeftOnly, Some(true), None);
+
+        // When: Copy action is applied
+        apply_file_action(
+            &entry,
+            FileAction::Copy,
+            left.path(),
+            right.path(),
+            &[],
+            false,
+        )
+        .unwrap();
+
+        // Then: Directory and file permissions are preserved
+        let dir_mode = fs::metadata(right.path().join("private"))
+            .unwrap()
+            .permissions()
+            .mode();
+        let file_mode = fs::metadata(right.path().join("private/run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(dir_mode & 0o777, 0o700);
+        assert_eq!(file_mode & 0o777, 0o750);
+    }
+
+    // ========================================
+    // Rename handling tests
+    // ========================================
+
+    #[test]
+    fn test_apply_rename_right_moves_left_entry() {
+        // Given: A file moved from a/main.tf (left) to b/main.tf (right)
+        let (left, right) = create_test_dirs();
+        fs::create_dir(left.path().join("a")).unwrap();
+        fs::write(left.path().join("a/main.tf"), "resource {}\n").unwrap();
+        fs::create_dir(right.path().join("b")).unwrap();
+        fs::write(right.path().join("b/main.tf"), "resource {}\n").unwrap();
+        let entry = DiffEntry::renamed(
+            PathBuf::from("a/main.tf"),
+            PathBuf::from("b/main.tf"),
+            100,
+            false,
+        );
+
+        // When: The right name is chosen
+        apply_rename(&entry, HunkChoice::Right, left.path(), right.path()).unwrap();
+
+        // Then: The left file is moved to the right name
+        assert!(!left.path().join("a/main.tf").exists());
+        assert!(left.path().join("b/main.tf").exists());
+        assert!(right.path().join("b/main.tf").exists());
+    }
+
+    #[test]
+    fn test_apply_rename_left_reverts_right_entry() {
+        // Given: A file renamed from old.txt (left) to new.txt (right)
+        let (left, right) = create_test_dirs();
+        fs::write(left.path().join("old.txt"), "content\n").unwrap();
+        fs::write(right.path().join("new.txt"), "content\n").unwrap();
+        let entry = DiffEntry::renamed(
+            PathBuf::from("old.txt"),
+            PathBuf::from("new.txt"),
+            100,
+            false,
+        );
+
+        // When: The left name is chosen
+        apply_rename(&entry, HunkChoice::Left, left.path(), right.path()).unwrap();
+
+        // Then: The right file gets the left name back
+        assert!(right.path().join("old.txt").exists());
+        assert!(!right.path().join("new.txt").exists());
+    }
+
+    #[test]
+    fn test_apply_rename_refuses_to_overwrite() {
+        // Given: A rename whose target already exists on the left
+        let (left, right) = create_test_dirs();
+        fs::write(left.path().join("old.txt"), "old\n").unwrap();
+        fs::write(left.path().join("new.txt"), "other\n").unwrap();
+        fs::write(right.path().join("new.txt"), "old\n").unwrap();
+        let entry = DiffEntry::renamed(
+            PathBuf::from("old.txt"),
+            PathBuf::from("new.txt"),
+            100,
+            false,
+        );
+
+        // When: The right name is chosen
+        let result = apply_rename(&entry, HunkChoice::Right, left.path(), right.path());
+
+        // Then: The rename fails and nothing is overwritten
+        assert!(result.is_err());
+        assert_eq!(
+            fs::read_to_string(left.path().join("new.txt")).unwrap(),
+            "other\n"
+        );
+    }
 }
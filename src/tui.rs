@@ -0,0 +1,757 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+
+use ddmerge::diff::file::read_text_file;
+use ddmerge::diff::{
+    apply_hunk_decisions, compare_path, extract_hunks, DiffEntry, DiffType, Hunk, HunkChoice,
+    HunkResolution,
+};
+use ddmerge::merge::{
+    apply_file_action, apply_hunk_merge, check_unchanged, ChangedOnDisk, FileAction, FileState,
+};
+use ddmerge::ui::{
+    action_for, edit_hunk, help, hunk_lines, is_whitespace_only_diff, pane_width, Action, FileTree,
+    Focus, Frame, Layout, Mark, PaneHunk, Screen,
+};
+
+use super::{describe, is_binary_file, read_link_display, resolved, Flow, Session};
+
+/// State of one entry in the full-screen UI
+struct TuiEntry {
+    diff: DiffEntry,
+    mark: Mark,
+    /// Whether the entry was read since it was listed or compared again
+    loaded: bool,
+    /// State of every path of the entry when read, checked before each write
+    states: Vec<FileState>,
+    /// Hunks of a modified text file
+    text: Option<TextMerge>,
+    /// Description shown in the pane, above the hunks if there are any
+    info: String,
+    /// Choice applied to the entry as a whole, counted in the summary
+    decision: Option<HunkChoice>,
+}
+
+/// A text file merged hunk by hunk in the full-screen UI
+struct TextMerge {
+    left_content: String,
+    right_content: String,
+    hunks: Vec<Hunk>,
+    /// Decision for each hunk; undecided hunks are left as they are
+    choices: Vec<Option<HunkResolution>>,
+    /// Hunk under the cursor
+    selected: usize,
+}
+
+/// An entry as it was before a change, restored by undo
+struct TuiUndo {
+    index: usize,
+    mark: Mark,
+    choices: Option<Vec<Option<HunkResolution>>>,
+    /// Hunk under the cursor when the change was made
+    selected: usize,
+    decision: Option<HunkChoice>,
+}
+
+impl TuiEntry {
+    fn new(diff: DiffEntry) -> Self {
+        Self {
+            diff,
+            mark: Mark::Pending,
+            loaded: false,
+            states: Vec::new(),
+            text: None,
+            info: String::new(),
+            decision: None,
+        }
+    }
+
+    fn snapshot(&self, index: usize) -> TuiUndo {
+        TuiUndo {
+            index,
+            mark: self.mark,
+            choices: self.text.as_ref().map(|text| text.choices.clone()),
+            selected: self.text.as_ref().map_or(0, |text| text.selected),
+            decision: self.decision,
+        }
+    }
+
+    fn restore(&mut self, undo: TuiUndo) {
+        match (&mut self.text, undo.choices) {
+            (Some(text), Some(choices)) if text.choices.len() == choices.len() => {
+                text.choices = choices;
+                text.selected = undo.selected;
+            }
+            // Compared again since the change: start over from what is on disk
+            (Some(_), _) => {
+                *self = Self::new(self.diff.clone());
+                return;
+            }
+            _ => {}
+        }
+        self.mark = undo.mark;
+        self.decision = undo.decision;
+    }
+
+    /// Mark of a text file from its hunk decisions
+    fn text_mark(text: &TextMerge) -> Mark {
+        let decided = text.choices.iter().flatten().count();
+        let skip = HunkResolution::Choice(HunkChoice::Skip);
+        if decided == 0 {
+            Mark::Pending
+        } else if decided < text.choices.len() {
+            Mark::Partial
+        } else if text.choices.iter().flatten().all(|choice| *choice == skip) {
+            Mark::Skipped
+        } else {
+            Mark::Resolved
+        }
+    }
+}
+
+/// Cursor, focus and message line of the full-screen UI
+struct TuiView {
+    tree: FileTree,
+    focus: Focus,
+    layout: Layout,
+    status: String,
+}
+
+/// Result of a key in the full-screen UI
+enum TuiOutcome {
+    /// Nothing was written; the message goes to the status line
+    Message(String),
+    /// A change was journaled, so undo can restore the entry as it was
+    Changed(String),
+}
+
+/// Next entry after `after` in tree order that still needs a decision
+fn next_open(tree: &FileTree, entries: &[TuiEntry], after: Option<usize>) -> Option<usize> {
+    let order = tree.order();
+    let start = after
+        .and_then(|index| order.iter().position(|&i| i == index))
+        .map_or(0, |position| position + 1);
+    order[start..]
+        .iter()
+        .chain(&order[..start])
+        .copied()
+        .find(|&index| entries[index].mark.is_open())
+}
+
+/// The full-screen UI (`--tui`)
+impl Session<'_> {
+    /// Resolve entries in the full-screen UI, in any order
+    pub(super) fn run_tui(&mut self, diffs: &[DiffEntry]) -> Result<Flow> {
+        let mut entries = Vec::new();
+        for diff in diffs {
+            if let Some(state) = &self.resumed {
+                if state.is_done(&diff.path) {
+                    self.stats.previously_done += 1;
+                    continue;
+                }
+            }
+            // Rules are applied before the screen opens, so their output stays visible
+            if self.apply_rule(diff)? {
+                self.log_done(&diff.path)?;
+                continue;
+            }
+            entries.push(TuiEntry::new(diff.clone()));
+        }
+        if entries.is_empty() {
+            return Ok(Flow::Continue);
+        }
+
+        let paths = entries
+            .iter()
+            .map(|entry| entry.diff.path.clone())
+            .collect();
+        let mut view = TuiView {
+            tree: FileTree::new(paths),
+            focus: Focus::Tree,
+            layout: self.args.layout,
+            status: String::new(),
+        };
+        if let Some(first) = next_open(&view.tree, &entries, None) {
+            view.tree.select(first);
+        }
+        let mut history = Vec::new();
+        let screen = Screen::enter()?;
+        loop {
+            let selected = view.tree.selected_entry();
+            if let Some(index) = selected {
+                self.tui_load(&mut entries[index]);
+            }
+            if selected.is_none_or(|index| entries[index].text.is_none()) {
+                view.focus = Focus::Tree;
+            }
+            self.tui_draw(&screen, &view, &entries)?;
+
+            let Some(action) = action_for(&screen.read_key()?) else {
+                continue;
+            };
+            view.status.clear();
+            let text = selected.and_then(|index| entries[index].text.as_mut());
+            match action {
+                Action::Quit => break,
+                Action::Up => match text {
+                    Some(text) if view.focus == Focus::Hunks => {
+                        text.selected = text.selected.saturating_sub(1)
+                    }
+                    _ => view.tree.up(),
+                },
+                Action::Down => match text {
+                    Some(text) if view.focus == Focus::Hunks => {
+                        text.selected = (text.selected + 1).min(text.hunks.len() - 1)
+                    }
+                    _ => view.tree.down(),
+                },
+                Action::Left if view.focus == Focus::Hunks => view.focus = Focus::Tree,
+                Action::Left => view.tree.collapse(),
+                Action::Right | Action::Enter | Action::SwitchFocus if text.is_some() => {
+                    view.focus = match view.focus {
+                        Focus::Tree => Focus::Hunks,
+                        Focus::Hunks if action == Action::SwitchFocus => Focus::Tree,
+                        Focus::Hunks => Focus::Hunks,
+                    }
+                }
+                Action::Right => view.tree.expand(),
+                Action::Enter => view.tree.toggle(),
+                Action::SwitchFocus => {}
+                Action::ToggleLayout => view.layout = view.layout.toggled(),
+                Action::NextPending => match next_open(&view.tree, &entries, selected) {
+                    Some(index) => view.tree.select(index),
+                    None => view.status = "Nothing left to decide".green().to_string(),
+                },
+                Action::Undo => {
+                    view.status = match self.tui_undo(&mut entries, &mut history) {
+                        Ok(Some((index, message))) => {
+                            if let Some(index) = index {
+                                view.tree.select(index);
+                            }
+                            message.green().to_string()
+                        }
+                        Ok(None) => "Nothing to undo".yellow().to_string(),
+                        Err(e) => format!("Error: {:#}", e).red().to_string(),
+                    }
+                }
+                Action::Choose(_)
+                | Action::Whitespace(_)
+                | Action::Copy
+                | Action::Delete
+                | Action::Edit
+                | Action::Rediff => {
+                    let Some(index) = selected else {
+                        view.status = "Select a file first".yellow().to_string();
+                        continue;
+                    };
+                    let entry = &mut entries[index];
+                    let snapshot = entry.snapshot(index);
+                    let was_open = entry.mark.is_open();
+                    let result = match action {
+                        Action::Choose(choice) => {
+                            self.tui_choose(entry, choice, view.focus == Focus::Tree)
+                        }
+                        Action::Whitespace(choice) => self.tui_whitespace(entry, choice),
+                        Action::Copy => self.tui_file_action(entry, FileAction::Copy),
+                        Action::Delete => self.tui_file_action(entry, FileAction::Delete),
+                        Action::Edit => self.tui_edit(&screen, entry, view.focus),
+                        _ => self.tui_rediff(entry),
+                    };
+                    view.status = match result {
+                        Ok(TuiOutcome::Message(message)) => message,
+                        Ok(TuiOutcome::Changed(message)) => {
+                            history.push(snapshot);
+                            message.green().to_string()
+                        }
+                        Err(e) => {
+                            entry.restore(snapshot);
+                            match e.downcast_ref::<ChangedOnDisk>() {
+                                Some(changed) => {
+                                    entry.mark = Mark::Failed;
+                                    format!("{}; press R to compare it again", changed)
+                                        .red()
+                                        .to_string()
+                                }
+                                None => format!("Error: {:#}", e).red().to_string(),
+                            }
+                        }
+                    };
+                    if was_open && !entries[index].mark.is_open() {
+                        self.log_done(&entries[index].diff.path)?;
+                        if let Some(next) = next_open(&view.tree, &entries, Some(index)) {
+                            view.tree.select(next);
+                        }
+                    }
+                }
+            }
+        }
+        drop(screen);
+
+        // Decisions are counted once the screen is closed, so revised hunks count once
+        for entry in &entries {
+            if let Some(text) = &entry.text {
+                for choice in text.choices.iter().flatten() {
+                    self.stats.record_resolution(choice);
+                    self.stats.total_hunks += 1;
+                }
+            }
+            if let Some(choice) = entry.decision {
+                self.stats.record(choice);
+            }
+        }
+        if entries.iter().any(|entry| entry.mark.is_open()) {
+            Ok(Flow::Quit)
+        } else {
+            Ok(Flow::Continue)
+        }
+    }
+
+    fn log_done(&mut self, path: &Path) -> Result<()> {
+        if let Some(log) = &mut self.log {
+            log.done(path)?;
+        }
+        Ok(())
+    }
+
+    /// State of every path an entry covers
+    fn capture_states(&self, diff: &DiffEntry) -> Result<Vec<FileState>> {
+        self.entry_paths(diff)
+            .iter()
+            .map(|path| FileState::capture(path))
+            .collect()
+    }
+
+    /// Read an entry when it is first shown
+    fn tui_load(&self, entry: &mut TuiEntry) {
+        if entry.loaded {
+            return;
+        }
+        entry.loaded = true;
+        entry.states = match self.capture_states(&entry.diff) {
+            Ok(states) => states,
+            Err(e) => {
+                entry.info = format!("Error: {:#}", e);
+                entry.mark = Mark::Failed;
+                return;
+            }
+        };
+        let args = self.args;
+        let diff = &entry.diff;
+        let left_path = args.left().join(&diff.path);
+        let right_path = args.right().join(&diff.path);
+        entry.info = match &diff.diff_type {
+            DiffType::LeftOnly | DiffType::RightOnly => {
+                let (side, other, path, kind) = if diff.diff_type == DiffType::LeftOnly {
+                    ("left", "right", &left_path, diff.left_type_name())
+                } else {
+                    ("right", "left", &right_path, diff.right_type_name())
+                };
+                if args.skip_binary && is_binary_file(path) {
+                    entry.mark = Mark::Skipped;
+                }
+                format!(
+                    "The {} is only in {}: (c)opy it to {} or (d)elete it from {}",
+                    kind, side, other, side
+                )
+            }
+            DiffType::Modified => match (read_text_file(&left_path), read_text_file(&right_path)) {
+                (Ok(Some(left_content)), Ok(Some(right_content))) => {
+                    let hunks =
+                        extract_hunks(&left_content, &right_content, &self.args.hunk_options());
+                    if hunks.is_empty() {
+                        entry.mark = Mark::Resolved;
+                        "No differences in content".to_string()
+                    } else {
+                        entry.text = Some(TextMerge {
+                            left_content,
+                            right_content,
+                            choices: vec![None; hunks.len()],
+                            hunks,
+                            selected: 0,
+                        });
+                        match &diff.metadata {
+                            Some(metadata) => format!("Metadata differs: {}", metadata),
+                            None => String::new(),
+                        }
+                    }
+                }
+                (Ok(_), Ok(_)) => {
+                    if args.skip_binary {
+                        entry.mark = Mark::Skipped;
+                    }
+                    "Binary file: take it whole from the (l)eft or (r)ight".to_string()
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    entry.mark = Mark::Failed;
+                    format!("Error reading: {:#}", e)
+                }
+            },
+            DiffType::TypeMismatch => format!(
+                "Type mismatch: left is {}, right is {}",
+                diff.left_type_name(),
+                diff.right_type_name()
+            ),
+            DiffType::SymlinkChanged => format!(
+                "Symlink target differs: left -> {}, right -> {}",
+                read_link_display(&left_path),
+                read_link_display(&right_path)
+            ),
+            DiffType::MetadataOnly => format!(
+                "Metadata differs: {}",
+                diff.metadata.clone().unwrap_or_default()
+            ),
+            DiffType::Renamed {
+                from,
+                to,
+                similarity,
+            } => format!(
+                "Renamed {} -> {} ({}% similar): keep the (l)eft or (r)ight name and content",
+                from.display(),
+                to.display(),
+                similarity
+            ),
+        };
+    }
+
+    fn tui_draw(&self, screen: &Screen, view: &TuiView, entries: &[TuiEntry]) -> Result<()> {
+        let (width, height) = screen.size();
+        let items: Vec<(&DiffEntry, Mark)> = entries
+            .iter()
+            .map(|entry| (&entry.diff, entry.mark))
+            .collect();
+        let done = entries.iter().filter(|entry| !entry.mark.is_open()).count();
+        let title = format!(
+            " ddmerge  {} ⇄ {}  {}/{} done{}",
+            self.args.left().display(),
+            self.args.right().display(),
+            done,
+            entries.len(),
+            if self.args.dry_run { "  (dry run)" } else { "" }
+        );
+
+        let mut pane_title = String::new();
+        let mut pane = Vec::new();
+        let mut anchor = 0;
+        if let Some(entry) = view.tree.selected_entry().map(|index| &entries[index]) {
+            pane_title = entry.diff.path.display().to_string();
+            if !entry.info.is_empty() {
+                pane.push(entry.info.yellow().to_string());
+                pane.push(String::new());
+            }
+            if let Some(text) = &entry.text {
+                pane_title = format!("{} ({} hunk(s))", pane_title, text.hunks.len());
+                let hunks: Vec<PaneHunk> = text
+                    .hunks
+                    .iter()
+                    .zip(&text.choices)
+                    .map(|(hunk, choice)| PaneHunk {
+                        hunk,
+                        decision: choice.as_ref().map(describe),
+                    })
+                    .collect();
+                let highlighter = self.args.highlighter(&entry.diff.path);
+                let (lines, selected) = hunk_lines(
+                    &hunks,
+                    text.selected,
+                    view.layout,
+                    highlighter.as_ref(),
+                    pane_width(width),
+                );
+                // The description stays in view while the first hunk is selected
+                if text.selected > 0 {
+                    anchor = pane.len() + selected;
+                }
+                pane.extend(lines);
+            }
+        }
+
+        let frame = Frame {
+            title: &title,
+            tree: view.tree.lines(&items, view.focus == Focus::Tree),
+            tree_cursor: view.tree.cursor(),
+            pane_title: &pane_title,
+            pane,
+            pane_anchor: anchor,
+            status: &view.status,
+            help: help(view.focus),
+        };
+        screen.draw(&frame.render(width, height))?;
+        Ok(())
+    }
+
+    /// Decide the selected hunk, every hunk of a file from the tree, or an entry as a whole
+    fn tui_choose(
+        &mut self,
+        entry: &mut TuiEntry,
+        choice: HunkChoice,
+        whole_file: bool,
+    ) -> Result<TuiOutcome> {
+        if entry.text.is_some() {
+            return self.tui_resolve(entry, choice.into(), whole_file);
+        }
+        let one_sided = matches!(
+            entry.diff.diff_type,
+            DiffType::LeftOnly | DiffType::RightOnly
+        );
+        let side = match choice {
+            HunkChoice::Skip => {
+                entry.mark = Mark::Skipped;
+                entry.decision = Some(choice);
+                return Ok(TuiOutcome::Message("Skipped".yellow().to_string()));
+            }
+            _ if one_sided => {
+                return Ok(TuiOutcome::Message(
+                    "Use c to copy or d to delete an entry on one side"
+                        .yellow()
+                        .to_string(),
+                ))
+            }
+            HunkChoice::Both(_) => {
+                return Ok(TuiOutcome::Message(
+                    "Both versions can only be kept for hunks of text files"
+                        .yellow()
+                        .to_string(),
+                ))
+            }
+            HunkChoice::Left => "left",
+            HunkChoice::Right => "right",
+        };
+        if !self.args.dry_run {
+            self.apply_choice(&entry.diff, choice, &entry.states)?;
+        }
+        entry.mark = Mark::Resolved;
+        entry.decision = Some(choice);
+        Ok(TuiOutcome::Changed(format!(
+            "Took {} {}",
+            side,
+            entry.diff.path.display()
+        )))
+    }
+
+    /// Decide hunks of a text file and rewrite it when its contents change
+    fn tui_resolve(
+        &mut self,
+        entry: &mut TuiEntry,
+        resolution: HunkResolution,
+        whole_file: bool,
+    ) -> Result<TuiOutcome> {
+        let Some(text) = &mut entry.text else {
+            return Ok(TuiOutcome::Message(String::new()));
+        };
+        let before = resolved(&text.choices);
+        let message = if whole_file {
+            format!("All hunks: {}", describe(&resolution))
+        } else {
+            format!(
+                "Hunk {}/{}: {}",
+                text.selected + 1,
+                text.hunks.len(),
+                describe(&resolution)
+            )
+        };
+        if whole_file {
+            text.choices.fill(Some(resolution));
+        } else {
+            text.choices[text.selected] = Some(resolution);
+        }
+        // Move on to the next undecided hunk of the file
+        let count = text.choices.len();
+        if let Some(next) = (1..count)
+            .map(|step| (text.selected + step) % count)
+            .find(|&i| text.choices[i].is_none())
+        {
+            text.selected = next;
+        }
+        entry.mark = TuiEntry::text_mark(text);
+        if resolved(&text.choices) == before {
+            return Ok(TuiOutcome::Message(message));
+        }
+        self.tui_write_text(entry)?;
+        Ok(TuiOutcome::Changed(message))
+    }
+
+    /// Resolve the undecided whitespace-only hunks of a text file to one side
+    fn tui_whitespace(&mut self, entry: &mut TuiEntry, choice: HunkChoice) -> Result<TuiOutcome> {
+        let Some(text) = &mut entry.text else {
+            return Ok(TuiOutcome::Message(
+                "Whitespace-only hunks are for text files"
+                    .yellow()
+                    .to_string(),
+            ));
+        };
+        let mut count = 0;
+        for (slot, hunk) in text.choices.iter_mut().zip(&text.hunks) {
+            if slot.is_none() && is_whitespace_only_diff(hunk) {
+                *slot = Some(choice.into());
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return Ok(TuiOutcome::Message(
+                "No undecided whitespace-only hunks".yellow().to_string(),
+            ));
+        }
+        entry.mark = TuiEntry::text_mark(text);
+        let message = format!(
+            "Whitespace-only hunks: {} ({})",
+            describe(&choice.into()),
+            count
+        );
+        self.tui_write_text(entry)?;
+        Ok(TuiOutcome::Changed(message))
+    }
+
+    /// Write the hunk decisions of a text file to both sides, from the contents first read
+    fn tui_write_text(&mut self, entry: &mut TuiEntry) -> Result<()> {
+        let Some(text) = &entry.text else {
+            return Ok(());
+        };
+        if self.args.dry_run {
+            return Ok(());
+        }
+        let left_path = self.args.left().join(&entry.diff.path);
+        let right_path = self.args.right().join(&entry.diff.path);
+        // Checked before journaling, so a refused write leaves nothing to undo
+        check_unchanged(&entry.states)?;
+        let (merged_left, merged_right) = apply_hunk_decisions(
+            &text.left_content,
+            &text.right_content,
+            &text.hunks,
+            &text.choices,
+        );
+        let paths = [left_path.clone(), right_path.clone()];
+        self.journal(format!("merge {}", entry.diff.path.display()), &paths)?;
+        apply_hunk_merge(
+            &left_path,
+            &right_path,
+            &merged_left,
+            &merged_right,
+            &entry.states,
+        )?;
+        // Our own write is the state the next change is checked against
+        entry.states = self.capture_states(&entry.diff)?;
+        Ok(())
+    }
+
+    /// Copy or delete an entry that exists on one side only
+    fn tui_file_action(&mut self, entry: &mut TuiEntry, action: FileAction) -> Result<TuiOutcome> {
+        let diff = &entry.diff;
+        let (side, other) = match diff.diff_type {
+            DiffType::LeftOnly => ("left", "right"),
+            DiffType::RightOnly => ("right", "left"),
+            _ => {
+                return Ok(TuiOutcome::Message(
+                    "Copy and delete are for entries on one side only"
+                        .yellow()
+                        .to_string(),
+                ))
+            }
+        };
+        let description = match action {
+            FileAction::Copy => format!("copy {} to {}", diff.path.display(), other),
+            _ => format!("delete {} from {}", diff.path.display(), side),
+        };
+        if !self.args.dry_run {
+            check_unchanged(&entry.states)?;
+            let paths = self.entry_paths(diff);
+            self.journal(description.clone(), &paths)?;
+            let args = self.args;
+            apply_file_action(
+                diff,
+                action,
+                args.left(),
+                args.right(),
+                &entry.states,
+                args.follow_symlinks,
+            )?;
+        }
+        entry.mark = Mark::Resolved;
+        Ok(TuiOutcome::Changed(format!("Applied: {}", description)))
+    }
+
+    /// Resolve the selected hunk with lines written in the editor
+    fn tui_edit(
+        &mut self,
+        screen: &Screen,
+        entry: &mut TuiEntry,
+        focus: Focus,
+    ) -> Result<TuiOutcome> {
+        let Some(text) = entry.text.as_ref().filter(|_| focus == Focus::Hunks) else {
+            return Ok(TuiOutcome::Message(
+                "Select a hunk to edit".yellow().to_string(),
+            ));
+        };
+        let hunk = &text.hunks[text.selected];
+        match screen.suspend(|| edit_hunk(hunk, &entry.diff.path))?? {
+            Some(lines) => self.tui_resolve(entry, HunkResolution::Edited(lines), false),
+            None => Ok(TuiOutcome::Message(
+                "Conflict markers left in place, edit cancelled"
+                    .yellow()
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// Compare an entry again, e.g. after another program changed it
+    fn tui_rediff(&mut self, entry: &mut TuiEntry) -> Result<TuiOutcome> {
+        let path = entry.diff.path.clone();
+        // A rename is handled again as is, since its contents are read anew
+        let diff = if matches!(entry.diff.diff_type, DiffType::Renamed { .. }) {
+            Some(entry.diff.clone())
+        } else {
+            let args = self.args;
+            compare_path(args.left(), args.right(), &path, self.compare_options)?
+        };
+        match diff {
+            Some(diff) => {
+                *entry = TuiEntry::new(diff);
+                Ok(TuiOutcome::Message(format!(
+                    "Compared {} again",
+                    path.display()
+                )))
+            }
+            None => {
+                *entry = TuiEntry::new(entry.diff.clone());
+                entry.loaded = true;
+                entry.mark = Mark::Resolved;
+                entry.info = "No differences left".to_string();
+                Ok(TuiOutcome::Message(entry.info.green().to_string()))
+            }
+        }
+    }
+
+    /// Revert the last change of this run and restore the entry it was made to
+    ///
+    /// Returns the restored entry, if any, and a message, or None when there
+    /// is nothing to undo.
+    fn tui_undo(
+        &mut self,
+        entries: &mut [TuiEntry],
+        history: &mut Vec<TuiUndo>,
+    ) -> Result<Option<(Option<usize>, String)>> {
+        let undone = if self.args.dry_run {
+            history.last().map(|_| "last decision".to_string())
+        } else {
+            match &mut self.journal {
+                Some(journal) => journal.undo_last()?,
+                None => None,
+            }
+        };
+        let Some(description) = undone else {
+            return Ok(None);
+        };
+        self.stats.undone += 1;
+        // Changes made by rules before the screen opened have no entry to restore
+        let Some(undo) = history.pop() else {
+            return Ok(Some((None, format!("Undid: {}", description))));
+        };
+        let index = undo.index;
+        let entry = &mut entries[index];
+        entry.restore(undo);
+        if let Ok(states) = self.capture_states(&entry.diff) {
+            entry.states = states;
+        }
+        Ok(Some((Some(index), format!("Undid: {}", description))))
+    }
+}
@@ -1,10 +1,11 @@
 use colored::Colorize;
-use similar::{ChangeTag, TextDiff};
+use similar::{group_diff_ops, ChangeTag};
 use std::fs;
 use std::path::Path;
 
+use super::syntax::{Change, Highlighter};
 use crate::diff::file::read_text_file;
-use crate::diff::{DiffEntry, DiffType};
+use crate::diff::{DiffEntry, DiffType, HunkOptions};
 
 /// Display a diff entry with colored output
 pub fn display_diff(
@@ -13,6 +14,8 @@ pub fn display_diff(
     total: usize,
     left_root: &Path,
     right_root: &Path,
+    options: &HunkOptions,
+    highlighter: Option<&Highlighter>,
 ) {
     println!();
     println!(
@@ -46,28 +49,59 @@ pub fn display_diff(
 
             show_file_info(&left_path, "Left");
             show_file_info(&right_path, "Right");
+            if let Some(metadata) = &entry.metadata {
+                println!("  {} {}", "Metadata differs:".yellow(), metadata);
+            }
 
             // Show text diff if possible
-            show_text_diff(&left_path, &right_path);
+            show_text_diff(&left_path, &right_path, options, highlighter);
         }
         DiffType::TypeMismatch => {
-            let left_type = if entry.left_is_dir.unwrap_or(false) {
-                "directory"
-            } else {
-                "file"
-            };
-            let right_type = if entry.right_is_dir.unwrap_or(false) {
-                "directory"
-            } else {
-                "file"
-            };
             println!(
                 "  {} Left is {}, Right is {}",
                 "Type mismatch:".red().bold(),
-                left_type.yellow(),
-                right_type.yellow()
+                entry.left_type_name().yellow(),
+                entry.right_type_name().yellow()
             );
         }
+        DiffType::SymlinkChanged => {
+            show_link_target(&left_root.join(&entry.path), "Left");
+            show_link_target(&right_root.join(&entry.path), "Right");
+        }
+        DiffType::MetadataOnly => {
+            show_file_info(&left_root.join(&entry.path), "Left");
+            show_file_info(&right_root.join(&entry.path), "Right");
+            if let Some(metadata) = &entry.metadata {
+                println!("  {} {}", "Metadata differs:".yellow(), metadata);
+            }
+        }
+        DiffType::Renamed {
+            from,
+            to,
+            similarity,
+        } => {
+            println!(
+                "  {} {} -> {} ({}% similar)",
+                "Renamed:".yellow().bold(),
+                from.display(),
+                to.display(),
+                similarity
+            );
+            if *similarity < 100 {
+                show_text_diff(
+                    &left_root.join(from),
+                    &right_root.join(to),
+                    options,
+                    highlighter,
+                );
+            }
+        }
+    }
+}
+
+fn show_link_target(path: &Path, side: &str) {
+    if let Ok(target) = fs::read_link(path) {
+        println!("  {}: -> {}", side.cyan(), target.display());
     }
 }
 
@@ -102,7 +136,12 @@ fn format_size(size: u64) -> String {
     }
 }
 
-fn show_text_diff(left_path: &Path, right_path: &Path) {
+fn show_text_diff(
+    left_path: &Path,
+    right_path: &Path,
+    options: &HunkOptions,
+    highlighter: Option<&Highlighter>,
+) {
     let left_content = match read_text_file(left_path) {
         Ok(Some(content)) => content,
         Ok(None) => {
@@ -141,23 +180,46 @@ fn show_text_diff(left_path: &Path, right_path: &Path) {
         .green()
     );
 
-    let diff = TextDiff::from_lines(&left_content, &right_content);
+    // Ignored whitespace differences are left out of the operations
+    let left_lines: Vec<&str> = left_content.split_inclusive('\n').collect();
+    let right_lines: Vec<&str> = right_content.split_inclusive('\n').collect();
+    let ops = options.line_ops(&left_content, &right_content);
 
-    for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+    for (idx, group) in group_diff_ops(ops, options.context_lines)
+        .iter()
+        .enumerate()
+    {
         if idx > 0 {
             println!("  {}", "...".dimmed());
         }
 
         for op in group {
-            for change in diff.iter_changes(op) {
+            for change in op.iter_changes(&left_lines, &right_lines) {
                 let (sign, style): (&str, fn(&str) -> colored::ColoredString) = match change.tag() {
                     ChangeTag::Delete => ("-", |s: &str| s.red()),
                     ChangeTag::Insert => ("+", |s: &str| s.green()),
                     ChangeTag::Equal => (" ", |s: &str| s.normal()),
                 };
+                let kind = match change.tag() {
+                    ChangeTag::Delete => Change::Deleted,
+                    ChangeTag::Insert => Change::Inserted,
+                    ChangeTag::Equal => Change::Context,
+                };
 
-                let line = format!("  {}{}", sign, change.value().trim_end());
-                println!("{}", style(&line));
+                let text = change.value().trim_end();
+                match highlighter.filter(|h| h.covers(kind)) {
+                    Some(highlighter) => {
+                        let text: String = highlighter
+                            .tokens(text)
+                            .into_iter()
+                            .map(|(token, text)| {
+                                highlighter.paint(&text, token, kind, false).to_string()
+                            })
+                            .collect();
+                        println!("  {}{}", style(sign), text);
+                    }
+                    None => println!("{}", style(&format!("  {}{}", sign, text))),
+                }
             }
         }
     }
@@ -0,0 +1,140 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use tempfile::NamedTempFile;
+
+use crate::diff::Hunk;
+
+/// Conflict marker lines written around both sides of the hunk
+const MARKERS: [&str; 3] = ["<<<<<<< left", "=======", ">>>>>>> right"];
+
+/// Open a hunk in `$VISUAL`/`$EDITOR` and return the merged lines written there
+///
+/// The file starts with both sides between conflict markers. Returns None when
+/// the editor left any marker in place, which cancels the edit.
+pub fn edit_hunk(hunk: &Hunk, file_path: &Path) -> Result<Option<Vec<String>>> {
+    let mut file = edit_file(file_path)?;
+    file.write_all(conflict_text(hunk).as_bytes())
+        .and_then(|()| file.flush())
+        .with_context(|| format!("Failed to write {}", file.path().display()))?;
+    run_editor(file.path())?;
+    // Read by path, since some editors save by replacing the file
+    let content = fs::read_to_string(file.path())?;
+    Ok(parse_edited(&content))
+}
+
+/// Temporary file for an edit, keeping the extension for the editor's highlighting
+///
+/// It gets an unpredictable name and is removed when dropped.
+fn edit_file(file_path: &Path) -> Result<NamedTempFile> {
+    let suffix = file_path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    tempfile::Builder::new()
+        .prefix("ddmerge-edit-")
+        .suffix(&suffix)
+        .tempfile()
+        .context("Failed to create a file to edit")
+}
+
+fn conflict_text(hunk: &Hunk) -> String {
+    let mut text = String::new();
+    for (marker, lines) in [
+        (MARKERS[0], &hunk.left_lines),
+        (MARKERS[1], &hunk.right_lines),
+    ] {
+        text.push_str(marker);
+        text.push('\n');
+        for line in lines {
+            text.push_str(line.trim_end_matches('\n'));
+            text.push('\n');
+        }
+    }
+    text.push_str(MARKERS[2]);
+    text.push('\n');
+    text
+}
+
+/// Lines of the edited text, or None while any marker line is left in place
+///
+/// Only the exact marker lines are looked for, so content that merely
+/// starts like one (such as a `=======` underline) is kept.
+fn parse_edited(content: &str) -> Option<Vec<String>> {
+    let lines: Vec<String> = content.lines().map(String::from).collect();
+    let has_markers = lines.iter().any(|line| MARKERS.contains(&line.as_str()));
+    (!has_markers).then_some(lines)
+}
+
+/// Run the user's editor on a file; the command may include arguments
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.trim().is_empty()))
+        .unwrap_or_else(|| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to run editor '{}'", editor))?;
+    if !status.success() {
+        bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unedited_conflict_cancels() {
+        // Given: The text offered to the editor, saved unchanged
+        let hunk = Hunk::for_test(&["old\n"], &["new\n"]);
+        let text = conflict_text(&hunk);
+
+        // When: Parsing it back
+        let result = parse_edited(&text);
+
+        // Then: The markers are still there, so the edit is cancelled
+        assert_eq!(text, "<<<<<<< left\nold\n=======\nnew\n>>>>>>> right\n");
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_edited_lines_are_returned() {
+        // Given/When: Text with the markers resolved by hand
+        let result = parse_edited("old\nnew\n");
+
+        // Then: The lines are returned without newlines
+        assert_eq!(result, Some(vec!["old".to_string(), "new".to_string()]));
+    }
+
+    #[test]
+    fn test_marker_like_content_is_kept() {
+        // Given: Resolved text with lines that only start like markers
+        let text = "Title\n=========\n<<<<<<< HEAD\n>>>>>>>\n";
+
+        // When: Parsing it back
+        let result = parse_edited(text);
+
+        // Then: None of them cancels the edit
+        assert_eq!(result.map(|lines| lines.len()), Some(4));
+    }
+
+    #[test]
+    fn test_edit_file_keeps_extension() {
+        // Given/When: Files to edit hunks of the same file
+        let first = edit_file(Path::new("src/main.rs")).unwrap();
+        let second = edit_file(Path::new("src/main.rs")).unwrap();
+
+        // Then: They are distinct, exist, and keep the extension
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().exists());
+        assert_eq!(first.path().extension().unwrap(), "rs");
+    }
+}
@@ -1,11 +1,58 @@
-use colored::Colorize;
+use colored::{ColoredString, Colorize};
+use console::{measure_text_width, Term};
 use std::io::{self, Write};
 use std::path::Path;
+use std::str::FromStr;
+
+use super::inline::{hunk_segments, trim_end, Segments};
+use super::syntax::{Change, Highlighter, Token};
+use crate::diff::{BothOrder, Hunk, HunkChoice, ThreeWayHunk};
+
+/// How the lines of a hunk are arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// Left lines above right lines, like `diff -u`
+    #[default]
+    Unified,
+    /// Left and right lines next to each other, with line numbers
+    SideBySide,
+}
+
+impl Layout {
+    pub fn toggled(self) -> Self {
+        match self {
+            Layout::Unified => Layout::SideBySide,
+            Layout::SideBySide => Layout::Unified,
+        }
+    }
+}
 
-use crate::diff::{Hunk, HunkChoice};
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unified" => Ok(Layout::Unified),
+            "side-by-side" => Ok(Layout::SideBySide),
+            _ => Err(format!(
+                "invalid value '{}' (expected unified or side-by-side)",
+                s
+            )),
+        }
+    }
+}
+
+/// Width of the terminal, or `$COLUMNS` when output is not a terminal
+fn terminal_width() -> usize {
+    Term::stdout()
+        .size_checked()
+        .map(|(_, columns)| usize::from(columns))
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(80)
+}
 
 /// Check if a hunk contains only whitespace differences
-fn is_whitespace_only_diff(hunk: &Hunk) -> bool {
+pub fn is_whitespace_only_diff(hunk: &Hunk) -> bool {
     // Combine all left and right lines, strip whitespace, and compare
     let left_stripped: String = hunk
         .left_lines
@@ -34,7 +81,14 @@ fn visualize_whitespace(line: &str) -> String {
 }
 
 /// Display a hunk with colored output
-pub fn display_hunk(hunk: &Hunk, index: usize, total: usize, file_path: &Path) {
+pub fn display_hunk(
+    hunk: &Hunk,
+    index: usize,
+    total: usize,
+    file_path: &Path,
+    layout: Layout,
+    highlighter: Option<&Highlighter>,
+) {
     let whitespace_only = is_whitespace_only_diff(hunk);
 
     println!();
@@ -65,44 +119,292 @@ pub fn display_hunk(hunk: &Hunk, index: usize, total: usize, file_path: &Path) {
         hunk.right_count
     );
 
-    // Show context before
-    for line in &hunk.context_before {
-        let display_line = if whitespace_only {
-            visualize_whitespace(line)
-        } else {
-            line.trim_end().to_string()
-        };
-        println!("  {}", format!(" {}", display_line).dimmed());
+    if layout == Layout::SideBySide {
+        // Narrow terminals fall back to the unified layout
+        let width = terminal_width().saturating_sub(2);
+        if let Some(rows) = side_by_side_rows(hunk, width, whitespace_only, highlighter) {
+            for row in rows {
+                println!("  {}", row);
+            }
+            return;
+        }
     }
 
-    // Show left lines (what would be removed/changed)
-    for line in &hunk.left_lines {
-        let display_line = if whitespace_only {
-            visualize_whitespace(line)
-        } else {
-            line.trim_end().to_string()
-        };
-        println!("  {}", format!("-{}", display_line).red());
+    for row in unified_rows(hunk, whitespace_only, highlighter) {
+        println!("  {}", row);
     }
+}
 
-    // Show right lines (what would be added/changed)
-    for line in &hunk.right_lines {
-        let display_line = if whitespace_only {
-            visualize_whitespace(line)
-        } else {
-            line.trim_end().to_string()
-        };
-        println!("  {}", format!("+{}", display_line).green());
+/// Text of changed lines as shown, with whitespace made visible in
+/// whitespace-only hunks and trailing whitespace dropped otherwise
+fn shown(segments: &Segments, whitespace_only: bool) -> Segments {
+    if whitespace_only {
+        segments
+            .iter()
+            .map(|(emphasized, text)| (*emphasized, visualize_whitespace(text)))
+            .collect()
+    } else {
+        trim_end(segments)
     }
+}
 
-    // Show context after
-    for line in &hunk.context_after {
-        let display_line = if whitespace_only {
-            visualize_whitespace(line)
-        } else {
-            line.trim_end().to_string()
+/// Shown text in pieces that differ from the other side or not, and have
+/// one kind of syntax each
+type Runs = Vec<((bool, Token), String)>;
+
+/// Split segments further by syntax when the line is highlighted
+fn runs(segments: &Segments, change: Change, highlighter: Option<&Highlighter>) -> Runs {
+    let Some(highlighter) = highlighter.filter(|h| h.covers(change)) else {
+        return segments
+            .iter()
+            .map(|(emphasized, text)| ((*emphasized, Token::Plain), text.clone()))
+            .collect();
+    };
+    let line: String = segments.iter().map(|(_, text)| text.as_str()).collect();
+    let mut kinds = highlighter
+        .tokens(&line)
+        .into_iter()
+        .flat_map(|(token, text)| std::iter::repeat_n(token, text.chars().count()));
+
+    let mut runs: Runs = Vec::new();
+    for (emphasized, text) in segments {
+        for c in text.chars() {
+            let part = (*emphasized, kinds.next().unwrap_or(Token::Plain));
+            match runs.last_mut() {
+                Some((last, text)) if *last == part => text.push(c),
+                _ => runs.push((part, c.to_string())),
+            }
+        }
+    }
+    runs
+}
+
+/// Color runs by their change, reversing the parts that differ from the other side
+///
+/// Highlighted lines keep their syntax colours over a tinted background instead.
+fn styled(runs: &Runs, change: Change, highlighter: Option<&Highlighter>) -> String {
+    let highlighter = highlighter.filter(|h| h.covers(change));
+    runs.iter()
+        .map(|((emphasized, token), text)| {
+            if let Some(highlighter) = highlighter {
+                return highlighter
+                    .paint(text, *token, change, *emphasized)
+                    .to_string();
+            }
+            let text = match change {
+                Change::Deleted => text.red(),
+                Change::Inserted => text.green(),
+                Change::Context => text.dimmed(),
+            };
+            if *emphasized {
+                text.reversed().to_string()
+            } else {
+                text.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Rows of a hunk with left lines above right lines
+pub(super) fn unified_rows(
+    hunk: &Hunk,
+    whitespace_only: bool,
+    highlighter: Option<&Highlighter>,
+) -> Vec<String> {
+    let row = |marker: ColoredString, segments: &Segments, change: Change| {
+        let runs = runs(&shown(segments, whitespace_only), change, highlighter);
+        format!("{}{}", marker, styled(&runs, change, highlighter))
+    };
+    let context = |line: &String| row(" ".dimmed(), &vec![(false, line.clone())], Change::Context);
+    let (left, right) = hunk_segments(&hunk.left_lines, &hunk.right_lines);
+
+    let mut rows: Vec<String> = hunk.context_before.iter().map(context).collect();
+    for segments in &left {
+        rows.push(row("-".red(), segments, Change::Deleted));
+    }
+    for segments in &right {
+        rows.push(row("+".green(), segments, Change::Inserted));
+    }
+    rows.extend(hunk.context_after.iter().map(context));
+    rows
+}
+
+/// One side of a side-by-side row: line number, marker and text
+struct Cell {
+    number: usize,
+    marker: char,
+    change: Change,
+    runs: Runs,
+}
+
+/// Split marked text into pieces of at most `width` columns
+fn wrap<T: Copy + PartialEq>(parts: &[(T, String)], width: usize) -> Vec<Vec<(T, String)>> {
+    let mut pieces: Vec<Vec<(T, String)>> = vec![Vec::new()];
+    let mut used = 0;
+    for (emphasized, text) in parts {
+        for c in text.chars() {
+            let char_width = measure_text_width(c.encode_utf8(&mut [0; 4]));
+            if used + char_width > width && used > 0 {
+                pieces.push(Vec::new());
+                used = 0;
+            }
+            if let Some(piece) = pieces.last_mut() {
+                match piece.last_mut() {
+                    Some((last, text)) if last == emphasized => text.push(c),
+                    _ => piece.push((*emphasized, c.to_string())),
+                }
+            }
+            used += char_width;
+        }
+    }
+    pieces
+}
+
+/// Rows of a hunk with left and right versions in two columns of a total `width`
+///
+/// Changed lines are paired by position, and long lines wrap within their
+/// column. Returns None when the columns would be too narrow to read.
+pub(super) fn side_by_side_rows(
+    hunk: &Hunk,
+    width: usize,
+    whitespace_only: bool,
+    highlighter: Option<&Highlighter>,
+) -> Option<Vec<String>> {
+    let last_line = (hunk.left_start + hunk.left_count).max(hunk.right_start + hunk.right_count)
+        + hunk.context_after.len();
+    let number_width = last_line.to_string().len().max(3);
+    // Each side has a number, a space and a marker; the separator takes three columns
+    let column = width.checked_sub(2 * (number_width + 2) + 3)? / 2;
+    if column < 8 {
+        return None;
+    }
+    let cell = |number: usize, marker: char, change: Change, segments: &Segments| {
+        let segments: Segments = shown(segments, whitespace_only)
+            .into_iter()
+            .map(|(emphasized, text)| (emphasized, text.replace('\t', "    ")))
+            .collect();
+        Cell {
+            number,
+            marker,
+            change,
+            runs: runs(&segments, change, highlighter),
+        }
+    };
+    let context = |number: usize, line: &String| {
+        cell(number, ' ', Change::Context, &vec![(false, line.clone())])
+    };
+    let (left, right) = hunk_segments(&hunk.left_lines, &hunk.right_lines);
+
+    // Context lines sit at the same offsets before and after the change on both sides
+    let before = hunk.context_before.len();
+    let left_first = hunk.left_start.saturating_sub(before) + 1;
+    let right_first = hunk.right_start.saturating_sub(before) + 1;
+    let left_after = hunk.left_start + hunk.left_count + 1;
+    let right_after = hunk.right_start + hunk.right_count + 1;
+
+    let mut pairs: Vec<(Option<Cell>, Option<Cell>)> = Vec::new();
+    for (i, line) in hunk.context_before.iter().enumerate() {
+        pairs.push((
+            Some(context(left_first + i, line)),
+            Some(context(right_first + i, line)),
+        ));
+    }
+    for i in 0..left.len().max(right.len()) {
+        pairs.push((
+            left.get(i)
+                .map(|segments| cell(hunk.left_start + i + 1, '-', Change::Deleted, segments)),
+            right
+                .get(i)
+                .map(|segments| cell(hunk.right_start + i + 1, '+', Change::Inserted, segments)),
+        ));
+    }
+    for (i, line) in hunk.context_after.iter().enumerate() {
+        pairs.push((
+            Some(context(left_after + i, line)),
+            Some(context(right_after + i, line)),
+        ));
+    }
+
+    // Each cell becomes one or more lines of exactly the column's width
+    let cell_lines = |cell: Option<Cell>| -> Vec<String> {
+        let Some(cell) = cell else {
+            return vec![" ".repeat(number_width + 2 + column)];
+        };
+        let color: fn(&str) -> ColoredString = match cell.change {
+            Change::Deleted => |text| text.red(),
+            Change::Inserted => |text| text.green(),
+            Change::Context => |text| text.dimmed(),
         };
-        println!("  {}", format!(" {}", display_line).dimmed());
+        wrap(&cell.runs, column)
+            .iter()
+            .enumerate()
+            .map(|(i, piece)| {
+                // Continued lines have neither a number nor a marker
+                let (number, marker) = if i == 0 {
+                    let number = format!("{:>width$}", cell.number, width = number_width);
+                    (number, cell.marker)
+                } else {
+                    (" ".repeat(number_width), ' ')
+                };
+                let piece_width: usize =
+                    piece.iter().map(|(_, text)| measure_text_width(text)).sum();
+                let padding = " ".repeat(column - piece_width);
+                format!(
+                    "{} {}{}{}",
+                    number.dimmed(),
+                    color(&marker.to_string()),
+                    styled(piece, cell.change, highlighter),
+                    padding
+                )
+            })
+            .collect()
+    };
+
+    let blank = " ".repeat(number_width + 2 + column);
+    let mut rows = Vec::new();
+    for (left, right) in pairs {
+        let left = cell_lines(left);
+        let right = cell_lines(right);
+        for i in 0..left.len().max(right.len()) {
+            rows.push(format!(
+                "{} {} {}",
+                left.get(i).unwrap_or(&blank),
+                "│".dimmed(),
+                right.get(i).unwrap_or(&blank)
+            ));
+        }
+    }
+    Some(rows)
+}
+
+/// Display a conflicting three-way hunk with the base version in between
+pub fn display_three_way_hunk(hunk: &ThreeWayHunk, index: usize, total: usize, file_path: &Path) {
+    println!();
+    println!(
+        "{} {} in {}",
+        format!("[{}/{}]", index + 1, total).cyan().bold(),
+        "Conflict".red().bold(),
+        file_path.display().to_string().white()
+    );
+
+    println!(
+        "  @@@ -{},{} ={},{} +{},{} @@@",
+        hunk.left_start + 1,
+        hunk.left_lines.len(),
+        hunk.base_start + 1,
+        hunk.base_lines.len(),
+        hunk.right_start + 1,
+        hunk.right_lines.len()
+    );
+
+    for line in &hunk.left_lines {
+        println!("  {}", format!("-{}", line).red());
+    }
+    for line in &hunk.base_lines {
+        println!("  {}", format!("={}", line).dimmed());
+    }
+    for line in &hunk.right_lines {
+        println!("  {}", format!("+{}", line).green());
     }
 }
 
@@ -110,7 +412,19 @@ pub fn display_hunk(hunk: &Hunk, index: usize, total: usize, file_path: &Path) {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HunkUserChoice {
     Choice(HunkChoice),
+    /// Resolve every whitespace-only hunk to one side, this one included
+    AllWhitespace(HunkChoice),
+    /// Split the hunk into one-line pieces
+    Split,
+    /// Write the merged result in an editor
+    Edit,
+    /// Go back to the previous hunk
+    Previous,
+    /// Go on to the next hunk without deciding this one
+    Next,
     SkipFile,
+    /// Revert the last applied change
+    Undo,
     Quit,
 }
 
@@ -124,14 +438,51 @@ pub fn prompt_for_hunk_choice() -> HunkUserChoice {
         "(s)".yellow().bold()
     );
     print!(
-        "skip {}ile / {}uit > ",
+        "{}oth left first / {}oth right first / ",
+        "(b)".cyan().bold(),
+        "(B)".cyan().bold()
+    );
+    print!(
+        "{}/{} all whitespace-only to left/right / ",
+        "(w)".cyan().bold(),
+        "(W)".cyan().bold()
+    );
+    print!(
+        "{}plit / {}dit / {} prev / {} next / skip {}ile / {}ndo / {}uit > ",
+        "(S)".cyan().bold(),
+        "(e)".cyan().bold(),
+        "(k)".blue().bold(),
+        "(j)".blue().bold(),
         "(f)".yellow().bold(),
+        "(u)".blue().bold(),
         "(q)".magenta().bold()
     );
     io::stdout().flush().unwrap();
 
     loop {
         let input = read_single_char();
+        // `S` splits while `s` skips, `B` puts the right side first while
+        // `b` puts the left side first, and `W` takes right while `w` takes
+        // left, so they are checked before lowercasing
+        match input.as_str() {
+            "S" => return HunkUserChoice::Split,
+            "w" => {
+                println!("{}", " Using left for whitespace-only hunks".green());
+                return HunkUserChoice::AllWhitespace(HunkChoice::Left);
+            }
+            "W" => {
+                println!("{}", " Using right for whitespace-only hunks".green());
+                return HunkUserChoice::AllWhitespace(HunkChoice::Right);
+            }
+            "B" => {
+                println!(
+                    "{}",
+                    " Keeping both, right first (updates both files)".green()
+                );
+                return HunkUserChoice::Choice(HunkChoice::Both(BothOrder::RightFirst));
+            }
+            _ => {}
+        }
         match input.to_lowercase().as_str() {
             "l" => {
                 println!("{}", " Using left (will update right file)".green());
@@ -149,6 +500,17 @@ pub fn prompt_for_hunk_choice() -> HunkUserChoice {
                 println!("{}", " Skipping file...".yellow());
                 return HunkUserChoice::SkipFile;
             }
+            "b" => {
+                println!(
+                    "{}",
+                    " Keeping both, left first (updates both files)".green()
+                );
+                return HunkUserChoice::Choice(HunkChoice::Both(BothOrder::LeftFirst));
+            }
+            "e" => return HunkUserChoice::Edit,
+            "k" => return HunkUserChoice::Previous,
+            "j" => return HunkUserChoice::Next,
+            "u" => return HunkUserChoice::Undo,
             "q" => {
                 println!("{}", " Quitting...".red());
                 return HunkUserChoice::Quit;
@@ -170,19 +532,6 @@ fn read_single_char() -> String {
 mod tests {
     use super::*;
 
-    fn create_test_hunk(left_lines: Vec<&str>, right_lines: Vec<&str>) -> Hunk {
-        Hunk {
-            left_start: 0,
-            left_count: left_lines.len(),
-            right_start: 0,
-            right_count: right_lines.len(),
-            left_lines: left_lines.into_iter().map(String::from).collect(),
-            right_lines: right_lines.into_iter().map(String::from).collect(),
-            context_before: vec![],
-            context_after: vec![],
-        }
-    }
-
     #[test]
     fn test_visualize_whitespace_space() {
         // Given: A string containing a space
@@ -252,7 +601,7 @@ mod tests {
     #[test]
     fn test_is_whitespace_only_diff_true_spaces() {
         // Given: A hunk where only the number of spaces differs
-        let hunk = create_test_hunk(vec!["hello world\n"], vec!["hello  world\n"]);
+        let hunk = Hunk::for_test(&["hello world\n"], &["hello  world\n"]);
 
         // When: Checking if it's a whitespace-only diff
 
@@ -263,7 +612,7 @@ mod tests {
     #[test]
     fn test_is_whitespace_only_diff_true_tabs_vs_spaces() {
         // Given: A hunk where tabs and spaces are interchanged
-        let hunk = create_test_hunk(vec!["\thello\n"], vec!["    hello\n"]);
+        let hunk = Hunk::for_test(&["\thello\n"], &["    hello\n"]);
 
         // When: Checking if it's a whitespace-only diff
 
@@ -274,7 +623,7 @@ mod tests {
     #[test]
     fn test_is_whitespace_only_diff_true_trailing_newline() {
         // Given: A hunk where only trailing newline differs
-        let hunk = create_test_hunk(vec!["hello"], vec!["hello\n"]);
+        let hunk = Hunk::for_test(&["hello"], &["hello\n"]);
 
         // When: Checking if it's a whitespace-only diff
 
@@ -285,7 +634,7 @@ mod tests {
     #[test]
     fn test_is_whitespace_only_diff_false_different_content() {
         // Given: A hunk with different non-whitespace content
-        let hunk = create_test_hunk(vec!["hello\n"], vec!["world\n"]);
+        let hunk = Hunk::for_test(&["hello\n"], &["world\n"]);
 
         // When: Checking if it's a whitespace-only diff
 
@@ -296,7 +645,7 @@ mod tests {
     #[test]
     fn test_is_whitespace_only_diff_false_additional_content() {
         // Given: A hunk where right has additional non-whitespace content
-        let hunk = create_test_hunk(vec!["hello\n"], vec!["hello world\n"]);
+        let hunk = Hunk::for_test(&["hello\n"], &["hello world\n"]);
 
         // When: Checking if it's a whitespace-only diff
 
@@ -307,7 +656,7 @@ mod tests {
     #[test]
     fn test_is_whitespace_only_diff_empty_lines() {
         // Given: A hunk with no lines on either side
-        let hunk = create_test_hunk(vec![], vec![]);
+        let hunk = Hunk::for_test(&[], &[]);
 
         // When: Checking if it's a whitespace-only diff
 
@@ -318,11 +667,157 @@ mod tests {
     #[test]
     fn test_is_whitespace_only_diff_one_side_empty() {
         // Given: A hunk with whitespace-only content on one side
-        let hunk = create_test_hunk(vec!["   \n"], vec![]);
+        let hunk = Hunk::for_test(&["   \n"], &[]);
 
         // When: Checking if it's a whitespace-only diff
 
         // Then: Returns true (left has only whitespace)
         assert!(is_whitespace_only_diff(&hunk));
     }
+
+    // ==========================================================================
+    // Side-by-side tests
+    // ==========================================================================
+
+    fn plain(rows: &[String]) -> Vec<String> {
+        rows.iter()
+            .map(|row| console::strip_ansi_codes(row).trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_side_by_side_pairs_lines_with_numbers() {
+        // Given: A hunk replacing line 2 with two lines, with context around it
+        let mut hunk = Hunk::for_test(&["b\n"], &["B\n", "C\n"]);
+        hunk.left_start = 1;
+        hunk.right_start = 1;
+        hunk.context_before = vec!["a\n".to_string()];
+        hunk.context_after = vec!["d\n".to_string()];
+
+        // When: Rendering it 40 columns wide
+        let rows = side_by_side_rows(&hunk, 40, false, None).unwrap();
+
+        // Then: Each side has its own line numbers and changed lines are paired
+        assert_eq!(
+            plain(&rows),
+            vec![
+                "  1  a             │   1  a",
+                "  2 -b             │   2 +B",
+                "                   │   3 +C",
+                "  3  d             │   4  d",
+            ]
+        );
+        assert!(rows
+            .iter()
+            .all(|row| console::measure_text_width(row) == 40 - 1));
+    }
+
+    #[test]
+    fn test_side_by_side_wraps_long_lines() {
+        // Given: A changed line longer than its column
+        let hunk = Hunk::for_test(&["0123456789abcdefghij\n"], &["short\n"]);
+
+        // When: Rendering it with 13-column sides
+        let rows = side_by_side_rows(&hunk, 40, false, None).unwrap();
+
+        // Then: The rest continues on the next row without a line number
+        assert_eq!(
+            plain(&rows),
+            vec!["  1 -0123456789abc │   1 +short", "     defghij       │",]
+        );
+    }
+
+    #[test]
+    fn test_side_by_side_needs_room_for_two_columns() {
+        // Given/When/Then: Too narrow a width gives no rows
+        let hunk = Hunk::for_test(&["a\n"], &["b\n"]);
+        assert!(side_by_side_rows(&hunk, 20, false, None).is_none());
+    }
+
+    #[test]
+    fn test_wrap_keeps_emphasis_across_pieces() {
+        // Given: An emphasized word that crosses the column edge
+        let segments = vec![(false, "key: ".to_string()), (true, "value".to_string())];
+
+        // When: Wrapping it at 8 columns
+        let pieces = wrap(&segments, 8);
+
+        // Then: Both pieces keep the emphasis of the word
+        assert_eq!(
+            pieces,
+            vec![
+                vec![(false, "key: ".to_string()), (true, "val".to_string())],
+                vec![(true, "ue".to_string())],
+            ]
+        );
+    }
+
+    // ==========================================================================
+    // Inline highlight tests
+    // ==========================================================================
+
+    #[test]
+    fn test_unified_rows_keep_text_of_changed_lines() {
+        // Given: A hunk changing one word, with trailing spaces on the left
+        let mut hunk = Hunk::for_test(&["size = 3  \n"], &["size = 5\n"]);
+        hunk.context_before = vec!["[pool]\n".to_string()];
+
+        // When: Rendering it in the unified layout
+        let rows = unified_rows(&hunk, false, None);
+
+        // Then: Lines read as before, without trailing whitespace
+        assert_eq!(plain(&rows), vec![" [pool]", "-size = 3", "+size = 5"]);
+    }
+
+    #[test]
+    fn test_inline_highlight_stacks_with_visible_whitespace() {
+        // Given: A whitespace-only hunk changing indentation
+        let hunk = Hunk::for_test(&["  key\n"], &["\tkey\n"]);
+
+        // When: Showing the changed lines of each side
+        let (left, right) = hunk_segments(&hunk.left_lines, &hunk.right_lines);
+        let left = shown(&left[0], true);
+        let right = shown(&right[0], true);
+
+        // Then: The indentation is both visible and emphasized
+        assert_eq!(left[0], (true, "··".to_string()));
+        assert_eq!(right[0], (true, "→".to_string()));
+        assert_eq!(
+            plain(&unified_rows(&hunk, true, None)),
+            vec!["-··key↵", "+→key↵"]
+        );
+    }
+
+    #[test]
+    fn test_highlighted_runs_follow_syntax() {
+        // Given: A YAML context line and a highlighter for YAML
+        let highlighter = Highlighter::for_path(Path::new("app.yaml"), Default::default());
+        let segments = vec![(false, "replicas: 3".to_string())];
+
+        // When: Splitting it into runs, with and without the highlighter
+        let highlighted = runs(&segments, Change::Context, highlighter.as_ref());
+        let plain = runs(&segments, Change::Context, None);
+
+        // Then: Only the highlighted line is split by syntax
+        assert_eq!(
+            highlighted,
+            vec![
+                ((false, Token::Key), "replicas".to_string()),
+                ((false, Token::Plain), ": ".to_string()),
+                ((false, Token::Number), "3".to_string()),
+            ]
+        );
+        assert_eq!(
+            plain,
+            vec![((false, Token::Plain), "replicas: 3".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_layout_parses_option_values() {
+        // Given/When/Then: The values accepted by --layout
+        assert_eq!("unified".parse(), Ok(Layout::Unified));
+        assert_eq!("side-by-side".parse(), Ok(Layout::SideBySide));
+        assert!("columns".parse::<Layout>().is_err());
+    }
 }
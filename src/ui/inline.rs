@@ -0,0 +1,142 @@
+use similar::{ChangeTag, TextDiff};
+
+/// Text of a line in parts, each marked when it differs from the other side
+pub(super) type Segments = Vec<(bool, String)>;
+
+/// Lines of both sides of a hunk split into segments, emphasizing the words
+/// that differ from the other side
+///
+/// Lines are aligned by `similar`'s inline diff, which leaves lines with
+/// little in common unemphasized.
+pub(super) fn hunk_segments(
+    left_lines: &[String],
+    right_lines: &[String],
+) -> (Vec<Segments>, Vec<Segments>) {
+    let whole = |lines: &[String]| -> Vec<Segments> {
+        lines
+            .iter()
+            .map(|line| vec![(false, line.clone())])
+            .collect()
+    };
+    let mut left = whole(left_lines);
+    let mut right = whole(right_lines);
+
+    let left_text = left_lines.concat();
+    let right_text = right_lines.concat();
+    let diff = TextDiff::from_lines(&left_text, &right_text);
+    for op in diff.ops() {
+        for change in diff.iter_inline_changes(op) {
+            let line = match change.tag() {
+                ChangeTag::Delete => change.old_index().and_then(|i| left.get_mut(i)),
+                ChangeTag::Insert => change.new_index().and_then(|i| right.get_mut(i)),
+                ChangeTag::Equal => None,
+            };
+            if let Some(line) = line {
+                *line = Vec::new();
+                for (emphasized, text) in change.iter_strings_lossy() {
+                    push(line, emphasized, &text);
+                }
+            }
+        }
+    }
+    (left, right)
+}
+
+/// Append text, joining it to the last segment when both are marked alike
+fn push(segments: &mut Segments, emphasized: bool, text: &str) {
+    match segments.last_mut() {
+        Some((last, last_text)) if *last == emphasized => last_text.push_str(text),
+        _ => segments.push((emphasized, text.to_string())),
+    }
+}
+
+/// Segments without trailing whitespace
+pub(super) fn trim_end(segments: &Segments) -> Segments {
+    let mut trimmed = segments.clone();
+    while let Some((_, text)) = trimmed.last_mut() {
+        let kept = text.trim_end().len();
+        text.truncate(kept);
+        if !text.is_empty() {
+            break;
+        }
+        trimmed.pop();
+    }
+    trimmed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn emphasized(segments: &Segments) -> Vec<&str> {
+        segments
+            .iter()
+            .filter(|(emphasized, _)| *emphasized)
+            .map(|(_, text)| text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_changed_word_is_emphasized() {
+        // Given: A line where one value changed
+        let left = lines(&["replicas: 3 # default\n"]);
+        let right = lines(&["replicas: 5 # default\n"]);
+
+        // When: Splitting it into segments
+        let (left, right) = hunk_segments(&left, &right);
+
+        // Then: Only the value is emphasized on each side
+        assert_eq!(emphasized(&left[0]), vec!["3"]);
+        assert_eq!(emphasized(&right[0]), vec!["5"]);
+        let text: String = left[0].iter().map(|(_, text)| text.as_str()).collect();
+        assert_eq!(text, "replicas: 3 # default\n");
+    }
+
+    #[test]
+    fn test_unrelated_lines_are_not_emphasized() {
+        // Given: Lines with nothing in common, and a line only on the left
+        let left = lines(&["alpha beta\n", "gamma\n"]);
+        let right = lines(&["one two three four\n"]);
+
+        // When: Splitting them into segments
+        let (left, right) = hunk_segments(&left, &right);
+
+        // Then: Every line stays a single unemphasized segment
+        assert_eq!(left.len(), 2);
+        assert!(left
+            .iter()
+            .chain(&right)
+            .all(|line| emphasized(line).is_empty()));
+    }
+
+    #[test]
+    fn test_changed_whitespace_is_emphasized() {
+        // Given: Lines differing in indentation only
+        let left = lines(&["  key: value\n"]);
+        let right = lines(&["    key: value\n"]);
+
+        // When: Splitting them into segments
+        let (left, right) = hunk_segments(&left, &right);
+
+        // Then: The indentation is the emphasized part
+        assert_eq!(emphasized(&left[0]), vec!["  "]);
+        assert_eq!(emphasized(&right[0]), vec!["    "]);
+    }
+
+    #[test]
+    fn test_trim_end_drops_trailing_whitespace_segments() {
+        // Given: Segments ending in an emphasized space and a newline
+        let segments = vec![
+            (false, "a".to_string()),
+            (true, " ".to_string()),
+            (false, "\n".to_string()),
+        ];
+
+        // When/Then: Trimming leaves the text only
+        assert_eq!(trim_end(&segments), vec![(false, "a".to_string())]);
+    }
+}
@@ -1,7 +1,20 @@
 mod display;
+mod editor;
 mod hunk_display;
+mod inline;
 mod prompt;
+mod syntax;
+mod tui;
 
 pub use display::display_diff;
-pub use hunk_display::{display_hunk, prompt_for_hunk_choice, HunkUserChoice};
+pub use editor::edit_hunk;
+pub use hunk_display::{
+    display_hunk, display_three_way_hunk, is_whitespace_only_diff, prompt_for_hunk_choice,
+    HunkUserChoice, Layout,
+};
 pub use prompt::{prompt_for_action, UserChoice};
+pub use syntax::{Highlighter, Theme};
+pub use tui::{
+    action_for, help, hunk_lines, pane_width, tree_width, Action, FileTree, Focus, Frame, Mark,
+    PaneHunk, Screen, TreeRow,
+};
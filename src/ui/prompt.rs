@@ -19,7 +19,10 @@ pub fn prompt_for_action(diff_type: &DiffType) -> UserChoice {
         DiffType::LeftOnly => prompt_left_only(),
         DiffType::RightOnly => prompt_right_only(),
         DiffType::Modified => prompt_modified(),
-        DiffType::TypeMismatch => prompt_type_mismatch(),
+        DiffType::TypeMismatch
+        | DiffType::SymlinkChanged
+        | DiffType::MetadataOnly
+        | DiffType::Renamed { .. } => prompt_type_mismatch(),
     }
 }
 
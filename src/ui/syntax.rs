@@ -0,0 +1,507 @@
+use colored::{Color, ColoredString, Colorize};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Colours used for syntax highlighting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// For terminals with a dark background
+    #[default]
+    Dark,
+    /// For terminals with a light background
+    Light,
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dark" => Ok(Theme::Dark),
+            "light" => Ok(Theme::Light),
+            _ => Err(format!("invalid value '{}' (expected dark or light)", s)),
+        }
+    }
+}
+
+/// File types that can be highlighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Terraform,
+    Yaml,
+    Json,
+    Rust,
+}
+
+impl Language {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "tf" | "tfvars" | "hcl" => Some(Language::Terraform),
+            "yaml" | "yml" => Some(Language::Yaml),
+            "json" => Some(Language::Json),
+            "rs" => Some(Language::Rust),
+            _ => None,
+        }
+    }
+
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Language::Terraform => &[
+                "resource",
+                "data",
+                "variable",
+                "output",
+                "module",
+                "provider",
+                "locals",
+                "terraform",
+                "moved",
+                "import",
+                "removed",
+                "check",
+                "for",
+                "in",
+                "if",
+                "true",
+                "false",
+                "null",
+            ],
+            Language::Yaml => &["true", "false", "null", "yes", "no", "on", "off", "~"],
+            Language::Json => &["true", "false", "null"],
+            Language::Rust => &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+                "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match",
+                "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct",
+                "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+            ],
+        }
+    }
+
+    fn line_comments(self) -> &'static [&'static str] {
+        match self {
+            Language::Terraform => &["#", "//"],
+            Language::Yaml => &["#"],
+            Language::Json => &[],
+            Language::Rust => &["//"],
+        }
+    }
+
+    /// Whether words may contain dashes, as in `instance-type`
+    fn dashed_words(self) -> bool {
+        matches!(self, Language::Terraform | Language::Yaml)
+    }
+}
+
+/// Kind of a piece of source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Token {
+    Plain,
+    Comment,
+    String,
+    Number,
+    Keyword,
+    /// A mapping key or attribute name
+    Key,
+    /// A type name (Rust only)
+    Type,
+}
+
+/// How a line takes part in a diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Change {
+    Context,
+    Deleted,
+    Inserted,
+}
+
+/// Syntax highlighting for the lines of one file
+#[derive(Debug, Clone, Copy)]
+pub struct Highlighter {
+    language: Language,
+    theme: Theme,
+    truecolor: bool,
+}
+
+impl Highlighter {
+    /// Highlighter for a file, or None when its type is not known
+    pub fn for_path(path: &Path, theme: Theme) -> Option<Self> {
+        let truecolor = matches!(
+            std::env::var("COLORTERM").as_deref(),
+            Ok("truecolor" | "24bit")
+        );
+        Some(Highlighter {
+            language: Language::from_path(path)?,
+            theme,
+            truecolor,
+        })
+    }
+
+    /// Whether lines of this kind are highlighted
+    ///
+    /// Changed lines need a background tint to keep showing the diff under
+    /// the syntax colours, which only truecolor terminals get.
+    pub(super) fn covers(&self, change: Change) -> bool {
+        change == Change::Context || self.truecolor
+    }
+
+    /// Split a line into pieces of one kind each
+    pub(super) fn tokens(&self, line: &str) -> Vec<(Token, String)> {
+        let mut kinds = classify(line, self.language);
+        mark_keys(line, self.language, &mut kinds);
+
+        let mut tokens: Vec<(Token, String)> = Vec::new();
+        for (c, kind) in line.chars().zip(kinds) {
+            match tokens.last_mut() {
+                Some((last, text)) if *last == kind => text.push(c),
+                _ => tokens.push((kind, c.to_string())),
+            }
+        }
+        tokens
+    }
+
+    /// Paint text of one kind, tinting the background of changed lines
+    pub(super) fn paint(
+        &self,
+        text: &str,
+        token: Token,
+        change: Change,
+        emphasized: bool,
+    ) -> ColoredString {
+        let text = match (self.truecolor, self.foreground(token)) {
+            (_, None) => text.normal(),
+            (true, Some((r, g, b))) => text.truecolor(r, g, b),
+            (false, Some(_)) => text.color(basic(token)),
+        };
+        match change {
+            Change::Context => text.dimmed(),
+            Change::Deleted | Change::Inserted => {
+                let (r, g, b) = self.background(change, emphasized);
+                text.on_truecolor(r, g, b)
+            }
+        }
+    }
+
+    fn foreground(&self, token: Token) -> Option<(u8, u8, u8)> {
+        let colour = match (self.theme, token) {
+            (_, Token::Plain) => return None,
+            (Theme::Dark, Token::Comment) => (92, 99, 112),
+            (Theme::Dark, Token::String) => (152, 195, 121),
+            (Theme::Dark, Token::Number) => (209, 154, 102),
+            (Theme::Dark, Token::Keyword) => (198, 120, 221),
+            (Theme::Dark, Token::Key) => (97, 175, 239),
+            (Theme::Dark, Token::Type) => (229, 192, 123),
+            (Theme::Light, Token::Comment) => (140, 141, 147),
+            (Theme::Light, Token::String) => (56, 130, 55),
+            (Theme::Light, Token::Number) => (152, 104, 1),
+            (Theme::Light, Token::Keyword) => (166, 38, 164),
+            (Theme::Light, Token::Key) => (34, 90, 200),
+            (Theme::Light, Token::Type) => (160, 110, 0),
+        };
+        Some(colour)
+    }
+
+    fn background(&self, change: Change, emphasized: bool) -> (u8, u8, u8) {
+        match (self.theme, change, emphasized) {
+            (Theme::Dark, Change::Inserted, false) => (24, 56, 32),
+            (Theme::Dark, Change::Inserted, true) => (36, 110, 52),
+            (Theme::Dark, _, false) => (64, 24, 28),
+            (Theme::Dark, _, true) => (128, 36, 44),
+            (Theme::Light, Change::Inserted, false) => (220, 255, 220),
+            (Theme::Light, Change::Inserted, true) => (160, 240, 160),
+            (Theme::Light, _, false) => (255, 224, 224),
+            (Theme::Light, _, true) => (255, 168, 168),
+        }
+    }
+}
+
+/// Colours for terminals without truecolor, left to the terminal's palette
+fn basic(token: Token) -> Color {
+    match token {
+        Token::Plain => Color::White,
+        Token::Comment => Color::BrightBlack,
+        Token::String => Color::Green,
+        Token::Number => Color::Yellow,
+        Token::Keyword => Color::Magenta,
+        Token::Key => Color::Blue,
+        Token::Type => Color::Cyan,
+    }
+}
+
+/// Kind of each character of a line
+///
+/// Lines are read on their own, so comments and strings spanning several
+/// lines are only recognised on their first line.
+fn classify(line: &str, language: Language) -> Vec<Token> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut kinds = vec![Token::Plain; chars.len()];
+    let starts_with = |i: usize, prefix: &str| {
+        prefix
+            .chars()
+            .enumerate()
+            .all(|(j, c)| chars.get(i + j) == Some(&c))
+    };
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let after_space = i == 0 || chars[i - 1].is_whitespace();
+        let start = i;
+        let kind = if language
+            .line_comments()
+            .iter()
+            // A YAML `#` only starts a comment after whitespace, as in `a#b` it does not
+            .any(|prefix| starts_with(i, prefix) && (language != Language::Yaml || after_space))
+        {
+            i = chars.len();
+            Token::Comment
+        } else if language != Language::Yaml && language != Language::Json && starts_with(i, "/*") {
+            i = (i + 2..chars.len())
+                .find(|&j| starts_with(j, "*/"))
+                .map_or(chars.len(), |j| j + 2);
+            Token::Comment
+        } else if c == '"' || (c == '\'' && language == Language::Yaml && after_space) {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+            Token::String
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit) && after_space)
+        {
+            i += 1;
+            while i < chars.len() && (is_word(chars[i]) || chars[i] == '.') {
+                i += 1;
+            }
+            Token::Number
+        } else if is_word(c) || c == '~' {
+            i += 1;
+            while i < chars.len()
+                && (is_word(chars[i]) || (chars[i] == '-' && language.dashed_words()))
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if language.keywords().contains(&word.as_str()) {
+                Token::Keyword
+            } else if language == Language::Rust && c.is_uppercase() {
+                Token::Type
+            } else {
+                Token::Plain
+            }
+        } else {
+            i += 1;
+            Token::Plain
+        };
+        kinds[start..i].fill(kind);
+    }
+    kinds
+}
+
+/// Mark mapping keys and attribute names
+///
+/// Keys are quoted or bare text before `:` (JSON and YAML) or a name before
+/// `=` (Terraform).
+fn mark_keys(line: &str, language: Language, kinds: &mut [Token]) {
+    let chars: Vec<char> = line.chars().collect();
+    let next_visible = |from: usize| (from..chars.len()).find(|&j| !chars[j].is_whitespace());
+
+    match language {
+        Language::Json | Language::Yaml => {
+            // Quoted keys
+            let mut i = 0;
+            while i < chars.len() {
+                if kinds[i] != Token::String {
+                    i += 1;
+                    continue;
+                }
+                let start = i;
+                while i < chars.len() && kinds[i] == Token::String {
+                    i += 1;
+                }
+                if next_visible(i).is_some_and(|j| chars[j] == ':') {
+                    kinds[start..i].fill(Token::Key);
+                }
+            }
+            if language == Language::Json {
+                return;
+            }
+            // Bare YAML keys, after indentation and list dashes
+            let mut start = 0;
+            while start < chars.len() && (chars[start].is_whitespace() || chars[start] == '-') {
+                start += 1;
+            }
+            if kinds
+                .get(start)
+                .is_some_and(|kind| matches!(kind, Token::String | Token::Key | Token::Comment))
+            {
+                return;
+            }
+            let colon = (start..chars.len())
+                .find(|&j| chars[j] == ':' && chars.get(j + 1).is_none_or(|c| c.is_whitespace()));
+            if let Some(colon) = colon {
+                if kinds[start..colon]
+                    .iter()
+                    .all(|kind| *kind != Token::Comment)
+                {
+                    kinds[start..colon].fill(Token::Key);
+                }
+            }
+        }
+        Language::Terraform => {
+            let mut start = 0;
+            while start < chars.len() && chars[start].is_whitespace() {
+                start += 1;
+            }
+            let mut end = start;
+            while end < chars.len() && (chars[end].is_alphanumeric() || "_-".contains(chars[end])) {
+                end += 1;
+            }
+            let assigned = next_visible(end)
+                .is_some_and(|j| chars[j] == '=' && chars.get(j + 1) != Some(&'='));
+            if end > start && assigned {
+                kinds[start..end].fill(Token::Key);
+            }
+        }
+        Language::Rust => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlighter(path: &str) -> Highlighter {
+        Highlighter {
+            language: Language::from_path(Path::new(path)).unwrap(),
+            theme: Theme::Dark,
+            truecolor: true,
+        }
+    }
+
+    fn of_kind(tokens: &[(Token, String)], kind: Token) -> Vec<&str> {
+        tokens
+            .iter()
+            .filter(|(token, _)| *token == kind)
+            .map(|(_, text)| text.trim())
+            .collect()
+    }
+
+    #[test]
+    fn test_language_from_extension() {
+        // Given/When/Then: Only the supported extensions get a highlighter
+        assert!(Highlighter::for_path(Path::new("main.tf"), Theme::Dark).is_some());
+        assert!(Highlighter::for_path(Path::new("deploy/app.yml"), Theme::Dark).is_some());
+        assert!(Highlighter::for_path(Path::new("package.json"), Theme::Dark).is_some());
+        assert!(Highlighter::for_path(Path::new("src/lib.rs"), Theme::Dark).is_some());
+        assert!(Highlighter::for_path(Path::new("notes.txt"), Theme::Dark).is_none());
+        assert!(Highlighter::for_path(Path::new("Makefile"), Theme::Dark).is_none());
+    }
+
+    #[test]
+    fn test_terraform_tokens() {
+        // Given: A Terraform block header and an attribute with a comment
+        let highlighter = highlighter("main.tf");
+
+        // When: Splitting the lines into tokens
+        let header = highlighter.tokens("resource \"aws_instance\" \"web\" {\n");
+        let attribute = highlighter.tokens("  instance_type = \"t3.micro\" # cheap\n");
+
+        // Then: Keywords, strings, keys and comments are told apart
+        assert_eq!(of_kind(&header, Token::Keyword), vec!["resource"]);
+        assert_eq!(
+            of_kind(&header, Token::String),
+            vec!["\"aws_instance\"", "\"web\""]
+        );
+        assert_eq!(of_kind(&attribute, Token::Key), vec!["instance_type"]);
+        assert_eq!(of_kind(&attribute, Token::String), vec!["\"t3.micro\""]);
+        assert_eq!(of_kind(&attribute, Token::Comment), vec!["# cheap"]);
+    }
+
+    #[test]
+    fn test_yaml_tokens() {
+        // Given: YAML mappings, a list item and a URL containing `#`
+        let highlighter = highlighter("app.yaml");
+
+        // When: Splitting the lines into tokens
+        let mapping = highlighter.tokens("  replicas: 3 # default\n");
+        let item = highlighter.tokens("- name: web\n");
+        let url = highlighter.tokens("url: http://host/a#b\n");
+        let flag = highlighter.tokens("enabled: true\n");
+
+        // Then: Keys, numbers, keywords and real comments are found
+        assert_eq!(of_kind(&mapping, Token::Key), vec!["replicas"]);
+        assert_eq!(of_kind(&mapping, Token::Number), vec!["3"]);
+        assert_eq!(of_kind(&mapping, Token::Comment), vec!["# default"]);
+        assert_eq!(of_kind(&item, Token::Key), vec!["name"]);
+        assert!(of_kind(&url, Token::Comment).is_empty());
+        assert_eq!(of_kind(&flag, Token::Keyword), vec!["true"]);
+    }
+
+    #[test]
+    fn test_json_tokens() {
+        // Given: A JSON member with a string value and one with a number
+        let highlighter = highlighter("package.json");
+
+        // When: Splitting the lines into tokens
+        let name = highlighter.tokens("  \"name\": \"ddmerge\",\n");
+        let count = highlighter.tokens("  \"count\" : -12.5\n");
+
+        // Then: Quoted keys are keys and quoted values are strings
+        assert_eq!(of_kind(&name, Token::Key), vec!["\"name\""]);
+        assert_eq!(of_kind(&name, Token::String), vec!["\"ddmerge\""]);
+        assert_eq!(of_kind(&count, Token::Key), vec!["\"count\""]);
+        assert_eq!(of_kind(&count, Token::Number), vec!["-12.5"]);
+    }
+
+    #[test]
+    fn test_rust_tokens() {
+        // Given: A Rust line with a type, a string containing `//` and a comment
+        let highlighter = highlighter("lib.rs");
+
+        // When: Splitting it into tokens
+        let tokens = highlighter.tokens("let url: String = \"a//b\".into(); // note\n");
+
+        // Then: The string is not cut short by the slashes inside it
+        assert_eq!(of_kind(&tokens, Token::Keyword), vec!["let"]);
+        assert_eq!(of_kind(&tokens, Token::Type), vec!["String"]);
+        assert_eq!(of_kind(&tokens, Token::String), vec!["\"a//b\""]);
+        assert_eq!(of_kind(&tokens, Token::Comment), vec!["// note"]);
+    }
+
+    #[test]
+    fn test_tokens_keep_the_text() {
+        // Given: A line with multi-byte characters and an unterminated string
+        let highlighter = highlighter("main.tf");
+        let line = "  name = \"café # ☕\n";
+
+        // When: Splitting it into tokens
+        let tokens = highlighter.tokens(line);
+
+        // Then: Joined together, the tokens give back the line
+        let joined: String = tokens.iter().map(|(_, text)| text.as_str()).collect();
+        assert_eq!(joined, line);
+    }
+
+    #[test]
+    fn test_changed_lines_need_truecolor() {
+        // Given: The same highlighter with and without truecolor
+        let mut highlighter = highlighter("main.tf");
+
+        // When/Then: Without truecolor only context lines are highlighted
+        assert!(highlighter.covers(Change::Inserted));
+        highlighter.truecolor = false;
+        assert!(highlighter.covers(Change::Context));
+        assert!(!highlighter.covers(Change::Deleted));
+        assert!(!highlighter.covers(Change::Inserted));
+    }
+
+    #[test]
+    fn test_theme_parses_option_values() {
+        // Given/When/Then: The values accepted by --theme
+        assert_eq!("dark".parse(), Ok(Theme::Dark));
+        assert_eq!("light".parse(), Ok(Theme::Light));
+        assert!("solarized".parse::<Theme>().is_err());
+    }
+}
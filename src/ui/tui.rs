@@ -0,0 +1,780 @@
+use colored::{ColoredString, Colorize};
+use console::{pad_str, Alignment, Key, Term};
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use super::hunk_display::{is_whitespace_only_diff, side_by_side_rows, unified_rows, Layout};
+use super::syntax::Highlighter;
+use crate::diff::{BothOrder, DiffEntry, DiffType, Hunk, HunkChoice};
+
+/// Switch to the terminal's alternate screen, keeping the scrollback intact
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[2J";
+const LEAVE_SCREEN: &str = "\x1b[?1049l";
+
+/// Full-screen terminal, restored when dropped
+pub struct Screen {
+    term: Term,
+}
+
+impl Screen {
+    /// Whether both input and output are terminals, so the full screen can be used
+    pub fn is_available() -> bool {
+        io::stdin().is_terminal() && Term::stdout().is_term()
+    }
+
+    pub fn enter() -> io::Result<Self> {
+        let term = Term::stdout();
+        term.write_str(ENTER_SCREEN)?;
+        term.hide_cursor()?;
+        Ok(Self { term })
+    }
+
+    /// Width and height in columns and rows
+    pub fn size(&self) -> (usize, usize) {
+        let (rows, columns) = self.term.size();
+        (usize::from(columns), usize::from(rows))
+    }
+
+    /// Replace the screen with the given lines
+    pub fn draw(&self, lines: &[String]) -> io::Result<()> {
+        let mut out = String::from("\x1b[H");
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                out.push_str("\r\n");
+            }
+            out.push_str(line);
+            out.push_str("\x1b[0m\x1b[K");
+        }
+        self.term.write_str(&out)?;
+        self.term.flush()
+    }
+
+    /// Wait for a key; Ctrl-C is returned as a key instead of stopping the process
+    pub fn read_key(&self) -> io::Result<Key> {
+        self.term.read_key_raw()
+    }
+
+    /// Leave the full screen while `f` runs, e.g. to open an editor
+    pub fn suspend<T>(&self, f: impl FnOnce() -> T) -> io::Result<T> {
+        self.leave()?;
+        let result = f();
+        self.term.write_str(ENTER_SCREEN)?;
+        self.term.hide_cursor()?;
+        Ok(result)
+    }
+
+    fn leave(&self) -> io::Result<()> {
+        self.term.show_cursor()?;
+        self.term.write_str(LEAVE_SCREEN)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+/// Progress of an entry, shown as a marker in the file tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mark {
+    #[default]
+    Pending,
+    /// Some hunks of a text file are decided
+    Partial,
+    Resolved,
+    Skipped,
+    /// A write was refused or failed
+    Failed,
+}
+
+impl Mark {
+    /// Whether the entry still needs a decision
+    pub fn is_open(self) -> bool {
+        matches!(self, Mark::Pending | Mark::Partial | Mark::Failed)
+    }
+
+    fn symbol(self) -> ColoredString {
+        match self {
+            Mark::Pending => "·".dimmed(),
+            Mark::Partial => "~".yellow(),
+            Mark::Resolved => "✓".green(),
+            Mark::Skipped => "-".dimmed(),
+            Mark::Failed => "!".red().bold(),
+        }
+    }
+}
+
+/// One-character tag for the kind of difference
+fn kind_tag(diff_type: &DiffType) -> ColoredString {
+    match diff_type {
+        DiffType::LeftOnly => "<".red(),
+        DiffType::RightOnly => ">".green(),
+        DiffType::Modified => "M".yellow(),
+        DiffType::TypeMismatch => "T".magenta(),
+        DiffType::SymlinkChanged => "@".cyan(),
+        DiffType::MetadataOnly => "P".blue(),
+        DiffType::Renamed { .. } => "R".cyan(),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// A row of the file tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeRow {
+    Dir {
+        path: PathBuf,
+        depth: usize,
+        collapsed: bool,
+    },
+    /// An entry, by its index in the list the tree was built from
+    Entry { index: usize, depth: usize },
+}
+
+/// Collapsible tree of entry paths with a cursor
+#[derive(Debug)]
+pub struct FileTree {
+    paths: Vec<PathBuf>,
+    /// Entry indices sorted by path
+    order: Vec<usize>,
+    collapsed: BTreeSet<PathBuf>,
+    cursor: usize,
+}
+
+impl FileTree {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let mut order: Vec<usize> = (0..paths.len()).collect();
+        order.sort_by(|a, b| paths[*a].cmp(&paths[*b]));
+        Self {
+            paths,
+            order,
+            collapsed: BTreeSet::new(),
+            cursor: 0,
+        }
+    }
+
+    /// Visible rows, directories before their contents
+    pub fn rows(&self) -> Vec<TreeRow> {
+        let mut rows = Vec::new();
+        // Directories of the previous entry, outermost first
+        let mut open: Vec<&Path> = Vec::new();
+        for &index in &self.order {
+            let path = &self.paths[index];
+            while open.last().is_some_and(|dir| !path.starts_with(dir)) {
+                open.pop();
+            }
+            let mut dirs: Vec<&Path> = path
+                .ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect();
+            dirs.reverse();
+            for dir in dirs.into_iter().skip(open.len()) {
+                if !self.is_hidden(dir) {
+                    rows.push(TreeRow::Dir {
+                        path: dir.to_path_buf(),
+                        depth: open.len(),
+                        collapsed: self.collapsed.contains(dir),
+                    });
+                }
+                open.push(dir);
+            }
+            if !self.is_hidden(path) {
+                rows.push(TreeRow::Entry {
+                    index,
+                    depth: open.len(),
+                });
+            }
+        }
+        rows
+    }
+
+    /// Whether a collapsed directory contains the path
+    fn is_hidden(&self, path: &Path) -> bool {
+        path.ancestors()
+            .skip(1)
+            .any(|dir| self.collapsed.contains(dir))
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn selected(&self) -> Option<TreeRow> {
+        self.rows().into_iter().nth(self.cursor)
+    }
+
+    /// Index of the entry under the cursor
+    pub fn selected_entry(&self) -> Option<usize> {
+        match self.selected()? {
+            TreeRow::Entry { index, .. } => Some(index),
+            TreeRow::Dir { .. } => None,
+        }
+    }
+
+    pub fn up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.rows().len().saturating_sub(1));
+    }
+
+    /// Fold or unfold the directory under the cursor
+    pub fn toggle(&mut self) {
+        if let Some(TreeRow::Dir { path, .. }) = self.selected() {
+            if !self.collapsed.remove(&path) {
+                self.collapsed.insert(path);
+            }
+        }
+    }
+
+    /// Unfold the directory under the cursor
+    pub fn expand(&mut self) {
+        if let Some(TreeRow::Dir { path, .. }) = self.selected() {
+            self.collapsed.remove(&path);
+        }
+    }
+
+    /// Fold the directory under the cursor, or move to the parent directory
+    pub fn collapse(&mut self) {
+        let path = match self.selected() {
+            Some(TreeRow::Dir {
+                path,
+                collapsed: false,
+                ..
+            }) => {
+                self.collapsed.insert(path);
+                return;
+            }
+            Some(TreeRow::Dir { path, .. }) => path,
+            Some(TreeRow::Entry { index, .. }) => self.paths[index].clone(),
+            None => return,
+        };
+        let parent = path.parent().map(Path::to_path_buf);
+        if let Some(position) = self.rows().iter().position(
+            |row| matches!(row, TreeRow::Dir { path, .. } if Some(path) == parent.as_ref()),
+        ) {
+            self.cursor = position;
+        }
+    }
+
+    /// Move the cursor to an entry, unfolding the directories it is in
+    pub fn select(&mut self, index: usize) {
+        for dir in self.paths[index].ancestors().skip(1) {
+            self.collapsed.remove(dir);
+        }
+        if let Some(position) = self
+            .rows()
+            .iter()
+            .position(|row| matches!(row, TreeRow::Entry { index: i, .. } if *i == index))
+        {
+            self.cursor = position;
+        }
+    }
+
+    /// Entry indices in the order they are listed, including folded ones
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Rendered rows; `entries` is indexed like the tree's paths
+    pub fn lines(&self, entries: &[(&DiffEntry, Mark)], focused: bool) -> Vec<String> {
+        self.rows()
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let pointer = match (i == self.cursor, focused) {
+                    (true, true) => ">".cyan().bold(),
+                    (true, false) => ">".dimmed(),
+                    _ => " ".normal(),
+                };
+                let label = match row {
+                    TreeRow::Dir {
+                        path,
+                        depth,
+                        collapsed,
+                    } => {
+                        let arrow = if *collapsed { "▸" } else { "▾" };
+                        let name = format!("{} {}/", arrow, file_name(path));
+                        format!("{}{}", "  ".repeat(*depth), name.blue().bold())
+                    }
+                    TreeRow::Entry { index, depth } => {
+                        let (entry, mark) = entries[*index];
+                        let name = match &entry.diff_type {
+                            DiffType::Renamed { from, to, .. } => {
+                                format!("{} → {}", file_name(from), to.display())
+                            }
+                            _ => file_name(&entry.path),
+                        };
+                        format!(
+                            "{}{} {} {}",
+                            "  ".repeat(*depth),
+                            mark.symbol(),
+                            kind_tag(&entry.diff_type),
+                            name
+                        )
+                    }
+                };
+                format!("{}{}", pointer, label)
+            })
+            .collect()
+    }
+}
+
+/// Which pane the movement keys act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Focus {
+    #[default]
+    Tree,
+    Hunks,
+}
+
+/// A hunk shown in the pane with a description of its decision, if any
+pub struct PaneHunk<'a> {
+    pub hunk: &'a Hunk,
+    pub decision: Option<&'a str>,
+}
+
+/// Lines of the hunk pane and the line where the selected hunk starts
+pub fn hunk_lines(
+    hunks: &[PaneHunk],
+    selected: usize,
+    layout: Layout,
+    highlighter: Option<&Highlighter>,
+    width: usize,
+) -> (Vec<String>, usize) {
+    let mut lines = Vec::new();
+    let mut anchor = 0;
+    for (i, item) in hunks.iter().enumerate() {
+        let hunk = item.hunk;
+        if i == selected {
+            anchor = lines.len();
+        }
+        let pointer = if i == selected {
+            ">".cyan().bold()
+        } else {
+            " ".normal()
+        };
+        let decision = match item.decision {
+            Some(decision) => format!("→ {}", decision).cyan(),
+            None => "undecided".dimmed(),
+        };
+        lines.push(format!(
+            "{}{} @@ -{},{} +{},{} @@  {}",
+            pointer,
+            format!("[{}/{}]", i + 1, hunks.len()).cyan().bold(),
+            hunk.left_start + 1,
+            hunk.left_count,
+            hunk.right_start + 1,
+            hunk.right_count,
+            decision
+        ));
+        let whitespace_only = is_whitespace_only_diff(hunk);
+        let rows = match layout {
+            Layout::SideBySide => {
+                side_by_side_rows(hunk, width.saturating_sub(1), whitespace_only, highlighter)
+            }
+            Layout::Unified => None,
+        };
+        match rows {
+            Some(rows) => lines.extend(rows.into_iter().map(|row| format!(" {}", row))),
+            // Also used when the pane is too narrow for two columns
+            None => lines.extend(
+                unified_rows(hunk, whitespace_only, highlighter)
+                    .into_iter()
+                    .map(|row| format!("   {}", row.replace('\t', "    "))),
+            ),
+        }
+        lines.push(String::new());
+    }
+    (lines, anchor)
+}
+
+/// Width of the file tree for a screen width
+pub fn tree_width(width: usize) -> usize {
+    (width / 3).clamp(16, 40).min(width / 2)
+}
+
+/// Width of the hunk pane for a screen width
+pub fn pane_width(width: usize) -> usize {
+    width.saturating_sub(tree_width(width) + 1)
+}
+
+/// Everything shown on one screen of the full-screen UI
+pub struct Frame<'a> {
+    pub title: &'a str,
+    pub tree: Vec<String>,
+    /// Tree row kept in view
+    pub tree_cursor: usize,
+    pub pane_title: &'a str,
+    pub pane: Vec<String>,
+    /// Pane line scrolled to the top when possible
+    pub pane_anchor: usize,
+    pub status: &'a str,
+    pub help: &'a str,
+}
+
+/// Fit text with colours into exactly `width` columns
+fn fit(text: &str, width: usize) -> String {
+    pad_str(text, width, Alignment::Left, Some("…")).into_owned()
+}
+
+impl Frame<'_> {
+    /// Lay out the frame as `height` lines of `width` columns
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        if width < 20 || height < 5 {
+            let mut lines = vec![fit("Terminal too small", width)];
+            lines.resize(height, String::new());
+            return lines;
+        }
+        // Title, status and help take one line each
+        let body = height - 3;
+        let tree_width = tree_width(width);
+        let pane_width = pane_width(width);
+        let tree_offset = self.tree_cursor.saturating_sub(body - 1);
+        // The pane's own title takes its first line
+        let visible = body - 1;
+        let pane_offset = self
+            .pane_anchor
+            .min(self.pane.len().saturating_sub(visible));
+
+        let mut lines = Vec::with_capacity(height);
+        lines.push(fit(&self.title.reversed().to_string(), width));
+        for row in 0..body {
+            let tree = self
+                .tree
+                .get(tree_offset + row)
+                .map(String::as_str)
+                .unwrap_or("");
+            let pane = if row == 0 {
+                self.pane_title.bold().underline().to_string()
+            } else {
+                self.pane
+                    .get(pane_offset + row - 1)
+                    .cloned()
+                    .unwrap_or_default()
+            };
+            lines.push(format!(
+                "{}{}{}",
+                fit(tree, tree_width),
+                "│".dimmed(),
+                fit(&pane, pane_width)
+            ));
+        }
+        lines.push(fit(self.status, width));
+        lines.push(fit(&self.help.dimmed().to_string(), width));
+        lines
+    }
+}
+
+/// What a key asks for; its meaning depends on the focused pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    /// Fold a directory, or go back to the file tree
+    Left,
+    /// Unfold a directory, or go to the hunks
+    Right,
+    Enter,
+    SwitchFocus,
+    Choose(HunkChoice),
+    /// Resolve the whitespace-only hunks of a file to one side
+    Whitespace(HunkChoice),
+    Copy,
+    Delete,
+    Edit,
+    NextPending,
+    ToggleLayout,
+    Undo,
+    Rediff,
+    Quit,
+}
+
+/// Action bound to a key, if any
+pub fn action_for(key: &Key) -> Option<Action> {
+    let action = match key {
+        Key::ArrowUp | Key::Char('k') => Action::Up,
+        Key::ArrowDown | Key::Char('j') => Action::Down,
+        Key::ArrowLeft | Key::Escape | Key::BackTab => Action::Left,
+        Key::ArrowRight => Action::Right,
+        Key::Enter => Action::Enter,
+        Key::Tab => Action::SwitchFocus,
+        Key::Char('l') => Action::Choose(HunkChoice::Left),
+        Key::Char('r') => Action::Choose(HunkChoice::Right),
+        Key::Char('s') => Action::Choose(HunkChoice::Skip),
+        Key::Char('b') => Action::Choose(HunkChoice::Both(BothOrder::LeftFirst)),
+        Key::Char('B') => Action::Choose(HunkChoice::Both(BothOrder::RightFirst)),
+        Key::Char('w') => Action::Whitespace(HunkChoice::Left),
+        Key::Char('W') => Action::Whitespace(HunkChoice::Right),
+        Key::Char('c') => Action::Copy,
+        Key::Char('d') => Action::Delete,
+        Key::Char('e') => Action::Edit,
+        Key::Char('n') => Action::NextPending,
+        Key::Char('v') => Action::ToggleLayout,
+        Key::Char('u') => Action::Undo,
+        Key::Char('R') => Action::Rediff,
+        Key::Char('q') | Key::CtrlC => Action::Quit,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// Key help shown in the footer
+pub fn help(focus: Focus) -> &'static str {
+    match focus {
+        Focus::Tree => {
+            "↑↓ move  ←→ fold  Enter hunks  l/r/s/b/B all hunks  w/W whitespace-only  \
+             c copy  d delete  n next  v view  u undo  R re-diff  q quit"
+        }
+        Focus::Hunks => {
+            "↑↓ hunk  ← files  l left  r right  s skip  b/B both  w/W whitespace-only  \
+             e edit  n next  v view  u undo  R re-diff  q quit"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use console::{measure_text_width, strip_ansi_codes};
+
+    fn create_test_tree(paths: &[&str]) -> FileTree {
+        FileTree::new(paths.iter().map(PathBuf::from).collect())
+    }
+
+    fn plain(lines: &[String]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| strip_ansi_codes(line).trim_end().to_string())
+            .collect()
+    }
+
+    // ==========================================================================
+    // File tree tests
+    // ==========================================================================
+
+    #[test]
+    fn test_tree_groups_entries_under_directories() {
+        // Given: Unsorted entries in nested directories
+        let tree = create_test_tree(&["src/ui/tui.rs", "README.md", "src/main.rs"]);
+
+        // When: Listing the rows
+        let rows = tree.rows();
+
+        // Then: Directories come before their entries, indented by depth
+        assert_eq!(
+            rows,
+            vec![
+                TreeRow::Entry { index: 1, depth: 0 },
+                TreeRow::Dir {
+                    path: PathBuf::from("src"),
+                    depth: 0,
+                    collapsed: false
+                },
+                TreeRow::Entry { index: 2, depth: 1 },
+                TreeRow::Dir {
+                    path: PathBuf::from("src/ui"),
+                    depth: 1,
+                    collapsed: false
+                },
+                TreeRow::Entry { index: 0, depth: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collapse_hides_directory_contents() {
+        // Given: A tree with the cursor on an entry inside a directory
+        let mut tree = create_test_tree(&["a.txt", "dir/b.txt", "dir/c.txt"]);
+        tree.select(1);
+
+        // When: Collapsing twice (to the directory, then folding it)
+        tree.collapse();
+        tree.collapse();
+
+        // Then: Only the folded directory and the top-level entry remain
+        assert_eq!(tree.rows().len(), 2);
+        assert_eq!(
+            tree.selected(),
+            Some(TreeRow::Dir {
+                path: PathBuf::from("dir"),
+                depth: 0,
+                collapsed: true
+            })
+        );
+        assert_eq!(tree.selected_entry(), None);
+    }
+
+    #[test]
+    fn test_select_unfolds_directories() {
+        // Given: A folded directory
+        let mut tree = create_test_tree(&["dir/b.txt", "dir/c.txt"]);
+        tree.toggle();
+        assert_eq!(tree.rows().len(), 1);
+
+        // When: Selecting an entry inside it
+        tree.select(1);
+
+        // Then: The directory is open and the cursor is on the entry
+        assert_eq!(tree.rows().len(), 3);
+        assert_eq!(tree.selected_entry(), Some(1));
+    }
+
+    #[test]
+    fn test_cursor_stays_within_rows() {
+        // Given: A tree of two entries
+        let mut tree = create_test_tree(&["a", "b"]);
+
+        // When: Moving past both ends
+        tree.up();
+        assert_eq!(tree.cursor(), 0);
+        tree.down();
+        tree.down();
+
+        // Then: The cursor stops at the last row
+        assert_eq!(tree.cursor(), 1);
+    }
+
+    #[test]
+    fn test_tree_lines_show_marks_and_kinds() {
+        // Given: A modified and a left-only entry
+        let tree = create_test_tree(&["a.txt", "b.txt"]);
+        let modified = DiffEntry::modified(PathBuf::from("a.txt"));
+        let left_only = DiffEntry::left_only(PathBuf::from("b.txt"), false);
+
+        // When: Rendering with the first one resolved
+        let lines = tree.lines(
+            &[(&modified, Mark::Resolved), (&left_only, Mark::Pending)],
+            true,
+        );
+
+        // Then: Each row has the cursor, status marker, kind and name
+        assert_eq!(plain(&lines), vec![">✓ M a.txt", " · < b.txt"]);
+    }
+
+    // ==========================================================================
+    // Hunk pane tests
+    // ==========================================================================
+
+    #[test]
+    fn test_unified_hunk_lines() {
+        // Given: Two hunks, the second decided
+        let first = Hunk {
+            context_before: vec!["ctx\n".to_string()],
+            ..Hunk::for_test(&["old\n"], &["new\n"])
+        };
+        let second = Hunk {
+            context_before: vec!["ctx\n".to_string()],
+            ..Hunk::for_test(&["a\n"], &[])
+        };
+        let hunks = [
+            PaneHunk {
+                hunk: &first,
+                decision: None,
+            },
+            PaneHunk {
+                hunk: &second,
+                decision: Some("left"),
+            },
+        ];
+
+        // When: Rendering with the second one selected
+        let (lines, anchor) = hunk_lines(&hunks, 1, Layout::Unified, None, 80);
+
+        // Then: Both hunks are listed and the anchor is the second header
+        let lines = plain(&lines);
+        assert_eq!(
+            lines[..5],
+            [
+                " [1/2] @@ -1,1 +1,1 @@  undecided",
+                "    ctx",
+                "   -old",
+                "   +new",
+                ""
+            ]
+        );
+        assert_eq!(anchor, 5);
+        assert_eq!(lines[anchor], ">[2/2] @@ -1,1 +1,0 @@  → left");
+    }
+
+    #[test]
+    fn test_side_by_side_hunk_lines() {
+        // Given: A hunk replacing one line with two
+        let mut hunk = Hunk {
+            context_before: vec!["ctx\n".to_string()],
+            ..Hunk::for_test(&["old\n"], &["new\n", "more\n"])
+        };
+        hunk.left_start = 1;
+        hunk.right_start = 1;
+        let hunks = [PaneHunk {
+            hunk: &hunk,
+            decision: None,
+        }];
+
+        // When: Rendering side by side, and in a pane too narrow for it
+        let (lines, _) = hunk_lines(&hunks, 0, Layout::SideBySide, None, 41);
+        let (narrow, _) = hunk_lines(&hunks, 0, Layout::SideBySide, None, 20);
+
+        // Then: Context is on both sides and changed lines are paired,
+        // while the narrow pane falls back to the unified layout
+        let lines = plain(&lines);
+        assert_eq!(lines[1], "   1  ctx           │   1  ctx");
+        assert_eq!(lines[2], "   2 -old           │   2 +new");
+        assert_eq!(lines[3], "                    │   3 +more");
+        assert_eq!(plain(&narrow)[2], "   -old");
+    }
+
+    // ==========================================================================
+    // Frame tests
+    // ==========================================================================
+
+    #[test]
+    fn test_frame_fills_the_screen() {
+        // Given: A frame with more tree rows than fit
+        let frame = Frame {
+            title: "ddmerge",
+            tree: (0..50).map(|i| format!("file{}", i)).collect(),
+            tree_cursor: 30,
+            pane_title: "file30",
+            pane: vec!["a very long line ".repeat(10)],
+            pane_anchor: 0,
+            status: "",
+            help: help(Focus::Tree),
+        };
+
+        // When: Rendering it at 60x20
+        let lines = frame.render(60, 20);
+
+        // Then: Every line is exactly as wide as the screen and the cursor row is visible
+        assert_eq!(lines.len(), 20);
+        assert!(lines.iter().all(|line| measure_text_width(line) == 60));
+        assert!(plain(&lines).iter().any(|line| line.starts_with("file30")));
+    }
+
+    #[test]
+    fn test_keys_map_to_actions() {
+        // Given/When/Then: Choice keys are case-sensitive like in the prompt
+        assert_eq!(
+            action_for(&Key::Char('b')),
+            Some(Action::Choose(HunkChoice::Both(BothOrder::LeftFirst)))
+        );
+        assert_eq!(
+            action_for(&Key::Char('B')),
+            Some(Action::Choose(HunkChoice::Both(BothOrder::RightFirst)))
+        );
+        assert_eq!(
+            action_for(&Key::Char('W')),
+            Some(Action::Whitespace(HunkChoice::Right))
+        );
+        assert_eq!(action_for(&Key::CtrlC), Some(Action::Quit));
+        assert_eq!(action_for(&Key::Char('x')), None);
+    }
+}